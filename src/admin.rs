@@ -0,0 +1,67 @@
+/*!
+The admin API, for inspecting a running instance.
+
+Routes:
+ - `GET /stats`: per-generator event and byte counters, failed sends by
+   cause, and events dropped by the queue.
+ - `POST /trigger/<scenario>/start`, `POST /trigger/<scenario>/stop`: fire or
+   end an anomaly scenario.
+
+The same controls are offered over gRPC by `grpc`.
+*/
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+
+use crate::sink::Event;
+use crate::{stats, trigger};
+
+fn json(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    return Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("admin response should be valid");
+}
+
+async fn route(tx: Sender<Event>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let path: Vec<&str> = req.uri().path().trim_matches('/').split('/').collect();
+    let response = match (req.method(), path.as_slice()) {
+        (&Method::GET, ["stats"]) => json(StatusCode::OK, stats::snapshot()),
+        (&Method::POST, ["trigger", name, action @ ("start" | "stop")]) => {
+            match trigger::fire(&tx, name, *action == "stop").await {
+                Ok(result) => json(StatusCode::OK, json!({ "result": result })),
+                Err(e) => json(StatusCode::BAD_REQUEST, json!({ "error": e })),
+            }
+        }
+        _ => json(StatusCode::NOT_FOUND, json!({ "error": "not found" })),
+    };
+    return Ok(response);
+}
+
+pub fn serve(address: SocketAddr, tx: &Sender<Event>) {
+    let tx = tx.clone();
+    let make_service = make_service_fn(move |_| {
+        let tx = tx.clone();
+        async move {
+            return Ok::<_, Infallible>(service_fn(move |req| route(tx.clone(), req)));
+        }
+    });
+    let server = match Server::try_bind(&address) {
+        Ok(builder) => builder.serve(make_service),
+        Err(e) => {
+            println!("Could not start admin API on {}: {}", address, e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            println!("Admin API stopped: {}", e);
+        }
+    });
+}
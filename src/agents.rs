@@ -0,0 +1,96 @@
+/*!
+Mimicked log shipping agents.
+
+Real aggregation tiers receive logs from a mix of agents: current and legacy
+Datadog Agents, Fluent Bit, Logstash and other Vector instances, each with
+its own `ddsource` and request header fingerprint. With agent mimicry on,
+every batch is sent as one of these agents, so lessons on normalizing
+heterogeneous input have something to normalize. How many batches each agent
+sent goes into the answer key.
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{self, json};
+
+use crate::random;
+
+pub struct Agent {
+    pub name: &'static str,
+    /// The `ddsource` the agent's events carry, replacing dynamo's default.
+    pub ddsource: &'static str,
+    pub headers: &'static [(&'static str, &'static str)],
+    batches: AtomicU64,
+}
+
+pub static AGENTS: [Agent; 5] = [
+    Agent {
+        name: "datadog-agent-7",
+        ddsource: "datadog-agent",
+        headers: &[
+            ("user-agent", "datadog-agent/7.43.1"),
+            ("dd-evp-origin", "agent"),
+            ("dd-evp-origin-version", "7.43.1"),
+        ],
+        batches: AtomicU64::new(0),
+    },
+    Agent {
+        name: "datadog-agent-6",
+        ddsource: "syslog",
+        headers: &[
+            ("user-agent", "datadog-agent/6.9.0"),
+            ("dd-evp-origin", "agent"),
+            ("dd-evp-origin-version", "6.9.0"),
+        ],
+        batches: AtomicU64::new(0),
+    },
+    Agent {
+        name: "fluent-bit",
+        ddsource: "fluentbit",
+        headers: &[("user-agent", "Fluent-Bit/2.0.9")],
+        batches: AtomicU64::new(0),
+    },
+    Agent {
+        name: "logstash",
+        ddsource: "logstash",
+        headers: &[("user-agent", "logstash-output-datadog_logs/0.5.1")],
+        batches: AtomicU64::new(0),
+    },
+    Agent {
+        name: "vector",
+        ddsource: "vector",
+        headers: &[
+            ("user-agent", "Vector/0.29.1 (x86_64-unknown-linux-gnu)"),
+            ("dd-evp-origin", "vector"),
+            ("dd-evp-origin-version", "0.29.1"),
+        ],
+        batches: AtomicU64::new(0),
+    },
+];
+
+/// Picks the agent to send the next batch as.
+pub fn pick() -> &'static Agent {
+    let agent = random::pick(&AGENTS);
+    agent.batches.fetch_add(1, Ordering::Relaxed);
+    return agent;
+}
+
+impl Agent {
+    /// Gives an event this agent's `ddsource`, unless it was overridden from
+    /// dynamo's default.
+    pub fn apply(&self, event: &mut serde_json::Value) {
+        if event["ddsource"] == "dynamo" {
+            event["ddsource"] = json!(self.ddsource);
+        }
+    }
+}
+
+pub fn summary() -> serde_json::Value {
+    let mut batches = serde_json::Map::new();
+    for agent in AGENTS.iter() {
+        batches.insert(
+            agent.name.to_owned(),
+            json!(agent.batches.load(Ordering::Relaxed)),
+        );
+    }
+    return json!({ "batches": batches });
+}
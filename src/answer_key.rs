@@ -0,0 +1,66 @@
+/*!
+The answer key records the ground truth of a run: when each incident started
+and stopped, and exact counts for anything students are expected to measure.
+Instructors and graders compare what students found in Vector against it.
+*/
+use std::sync::Mutex;
+use std::time::Duration;
+
+use json_patch::merge;
+use serde_json::{self, json};
+
+use crate::{agents, attackers, clock, embed, hosts, orders, sampling, severity};
+
+static KEY: Mutex<serde_json::Value> = Mutex::new(serde_json::Value::Null);
+
+/// Merges `patch` into the answer key.
+pub fn record(patch: serde_json::Value) {
+    let mut key = KEY.lock().expect("answer key lock should not be poisoned");
+    merge(&mut key, &patch);
+}
+
+/// Current time, by the clock generated content uses, in the same format as
+/// event timestamps, for recording when something happened.
+pub fn now() -> i64 {
+    return clock::now().timestamp_micros() / 1000;
+}
+
+pub fn snapshot() -> serde_json::Value {
+    let mut key = KEY
+        .lock()
+        .expect("answer key lock should not be poisoned")
+        .clone();
+    merge(
+        &mut key,
+        &json!({
+            "agents": agents::summary(),
+            "attackers": { "ssh_brute_force": attackers::summary() },
+            "embedded_anomalies": embed::summary(),
+            "hosts": hosts::mapping(),
+            "orders": orders::summary(),
+            "sampling": sampling::summary(),
+            "severity": severity::summary(),
+        }),
+    );
+    return key;
+}
+
+pub fn write(path: &str) {
+    let tmp = format!("{}.tmp", path);
+    let contents = serde_json::to_string_pretty(&snapshot()).expect("answer key should serialize");
+    if let Err(e) = std::fs::write(&tmp, contents).and_then(|_| std::fs::rename(&tmp, path)) {
+        println!("Could not write answer key to {}: {}", path, e);
+    }
+}
+
+/// Rewrites the answer key at `path` every few seconds, so it is up to date
+/// even if dynamo is killed rather than stopped cleanly.
+pub fn spawn_writer(path: &str) {
+    let path = path.to_owned();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            write(&path);
+        }
+    });
+}
@@ -0,0 +1,153 @@
+/*!
+Application logs from the storedog backend services.
+
+Each line carries a log level drawn from a configurable mix, which makes
+level-based filtering exercises possible. During the DEBUG flood one service
+accidentally ships with DEBUG logging enabled and its volume grows twentyfold,
+for lessons on dropping or dynamically sampling noisy levels.
+*/
+use std::sync::OnceLock;
+
+use serde_json::{self, json};
+
+use crate::incident::Incident;
+use crate::{cascade, clock, hosts, random, severity};
+
+pub const LEVELS: [&str; 4] = ["DEBUG", "INFO", "WARN", "ERROR"];
+const SERVICES: [&str; 3] = ["storedog-cart", "storedog-catalog", "storedog-payments"];
+pub const FLOOD_SERVICE: &str = "storedog-payments";
+pub const DEFAULT_LEVEL_MIX: &str = "debug=0,info=80,warn=15,error=5";
+const FLOOD_FACTOR: usize = 20;
+
+pub static FLOOD: Incident = Incident::new("debug_flood", flood_details);
+static MIX: OnceLock<LevelMix> = OnceLock::new();
+
+/// The largest weight of a level, so that the four add up within a u32.
+const MAX_WEIGHT: u32 = 1_000_000;
+
+/// Relative weights of DEBUG, INFO, WARN and ERROR lines.
+#[derive(Clone, Debug)]
+pub struct LevelMix([u32; 4]);
+
+/// Parses a level mix like `debug=0,info=80,warn=15,error=5`. Levels that are
+/// left out get a weight of zero.
+pub fn parse_level_mix(s: &str) -> Result<LevelMix, String> {
+    let mut weights = [0; 4];
+    for part in s.split(',').filter(|p| !p.is_empty()) {
+        let (level, weight) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected level=weight, got `{}`", part))?;
+        let index = LEVELS
+            .iter()
+            .position(|l| l.eq_ignore_ascii_case(level.trim()))
+            .ok_or_else(|| format!("unknown log level `{}`", level))?;
+        weights[index] = weight
+            .trim()
+            .parse()
+            .ok()
+            .filter(|w| *w <= MAX_WEIGHT)
+            .ok_or_else(|| {
+                format!(
+                    "invalid weight `{}` for {}; use a number up to {}",
+                    weight, level, MAX_WEIGHT
+                )
+            })?;
+    }
+
+    if weights.iter().all(|w| *w == 0) {
+        return Err("at least one level needs a non-zero weight".to_owned());
+    }
+    return Ok(LevelMix(weights));
+}
+
+pub fn set_level_mix(mix: LevelMix) {
+    MIX.set(mix).expect("level mix should only be set once");
+}
+
+fn pick_level() -> &'static str {
+    let weights = &MIX
+        .get_or_init(|| parse_level_mix(DEFAULT_LEVEL_MIX).expect("default level mix should parse"))
+        .0;
+    let total: u32 = weights.iter().sum();
+    let mut n = random::range(0..total);
+    for (level, weight) in LEVELS.iter().zip(weights) {
+        if n < *weight {
+            return level;
+        }
+        n -= weight;
+    }
+    unreachable!("weights should cover the whole range");
+}
+
+fn body(level: &str) -> String {
+    return match level {
+        "DEBUG" => format!(
+            "cache lookup key=session:{} hit={} took={}us",
+            random::username(),
+            random::chance(0.7),
+            random::range(5..400),
+        ),
+        "WARN" => format!(
+            "slow query on orders table took {}ms",
+            random::range(500..3000)
+        ),
+        "ERROR" => format!(
+            "upstream request failed: connection reset by peer (attempt {}/3)",
+            random::range(1..4)
+        ),
+        _ => format!(
+            "processed request for user {} in {}ms",
+            random::username(),
+            random::range(3..150)
+        ),
+    };
+}
+
+fn line(service: &str, level: &str, body: String) -> serde_json::Value {
+    return json!({
+        "message": format!(
+            "{} {} [{}] {}",
+            clock::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            level,
+            service,
+            body,
+        ),
+        "service": service,
+        "hostname": hosts::backend(service).hostname,
+        "status": severity::from_level(level),
+    });
+}
+
+pub fn generate() -> serde_json::Value {
+    let service = random::pick(&SERVICES);
+    let normal = match cascade::symptom(service) {
+        Some((level, body)) => line(service, level, body),
+        None => {
+            let level = pick_level();
+            line(service, level, body(level))
+        }
+    };
+    if !FLOOD.active() {
+        return normal;
+    }
+
+    let mut lines = vec![normal];
+    for _ in 1..FLOOD_FACTOR {
+        lines.push(line(FLOOD_SERVICE, "DEBUG", body("DEBUG")));
+    }
+    return json!(lines);
+}
+
+fn flood_details() -> serde_json::Value {
+    return json!({ "service": FLOOD_SERVICE, "factor": FLOOD_FACTOR });
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn bounds_weights() {
+        assert!(super::parse_level_mix("info=1000000,warn=1000000").is_ok());
+        assert!(super::parse_level_mix("info=4000000000,warn=4000000000").is_err());
+        assert!(super::parse_level_mix("debug=0").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+/*!
+Attacker infrastructure for the SSH brute-force attack.
+
+Attacks come from a small, fixed set of addresses inside country-specific
+ranges, so GeoIP lookups in Vector return coherent results and the same
+attackers show up for the whole run. The addresses are derived from the ranges
+rather than drawn at random, so they are also stable between runs. The
+attackers and their countries go into the answer key.
+*/
+use std::net::Ipv4Addr;
+use std::sync::OnceLock;
+
+use serde_json::{self, json};
+
+use crate::random;
+
+pub const DEFAULT_RANGES: &str = "CN=218.92.0.0/16,RU=95.165.0.0/16,VN=113.160.0.0/11";
+const ATTACKERS_PER_RANGE: u64 = 2;
+
+static ATTACKERS: OnceLock<Vec<Attacker>> = OnceLock::new();
+
+#[derive(Clone, Debug)]
+pub struct Range {
+    country: String,
+    network: u32,
+    prefix: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Ranges(Vec<Range>);
+
+#[derive(Clone, Debug)]
+pub struct Attacker {
+    pub ip: String,
+    pub country: String,
+}
+
+/// Parses ranges like `CN=218.92.0.0/16,RU=95.165.0.0/16`, each a two-letter
+/// country code and an IPv4 CIDR block registered to it.
+pub fn parse_ranges(s: &str) -> Result<Ranges, String> {
+    let mut ranges = vec![];
+    for part in s.split(',').filter(|p| !p.is_empty()) {
+        let (country, cidr) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected country=cidr, got `{}`", part))?;
+        let country = country.trim();
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("`{}` is not a two-letter country code", country));
+        }
+        let (address, prefix) = cidr
+            .trim()
+            .split_once('/')
+            .ok_or_else(|| format!("expected a CIDR block, got `{}`", cidr))?;
+        let address: Ipv4Addr = address
+            .parse()
+            .map_err(|_| format!("invalid IPv4 address `{}`", address))?;
+        let prefix: u32 = match prefix.parse() {
+            Ok(prefix) if (8..=30).contains(&prefix) => prefix,
+            _ => return Err(format!("prefix length in `{}` should be 8 to 30", cidr)),
+        };
+        ranges.push(Range {
+            country: country.to_ascii_uppercase(),
+            network: u32::from(address) & (u32::MAX << (32 - prefix)),
+            prefix,
+        });
+    }
+
+    if ranges.is_empty() {
+        return Err("at least one attacker range is needed".to_owned());
+    }
+    return Ok(Ranges(ranges));
+}
+
+fn attackers_in(range: &Range) -> Vec<Attacker> {
+    // Skip the network and broadcast addresses.
+    let hosts = (1u64 << (32 - range.prefix)) - 2;
+    return (0..ATTACKERS_PER_RANGE)
+        .map(|i| {
+            let key = format!("{}/{}#{}", range.network, range.prefix, i);
+            let offset = 1 + random::fnv1a(key.as_bytes()) % hosts;
+            return Attacker {
+                ip: Ipv4Addr::from(range.network + offset as u32).to_string(),
+                country: range.country.clone(),
+            };
+        })
+        .collect();
+}
+
+pub fn set_ranges(ranges: Ranges) {
+    ATTACKERS
+        .set(ranges.0.iter().flat_map(attackers_in).collect())
+        .expect("attacker ranges should only be set once");
+}
+
+fn attackers() -> &'static [Attacker] {
+    return ATTACKERS.get_or_init(|| {
+        let ranges = parse_ranges(DEFAULT_RANGES).expect("default attacker ranges should parse");
+        return ranges.0.iter().flat_map(attackers_in).collect();
+    });
+}
+
+/// One of the attackers, picked at random.
+pub fn pick() -> &'static Attacker {
+    return random::pick(attackers());
+}
+
+pub fn summary() -> serde_json::Value {
+    let mut countries: Vec<&str> = attackers().iter().map(|a| a.country.as_str()).collect();
+    countries.sort();
+    countries.dedup();
+    return json!({
+        "ips": attackers().iter().map(|a| json!({ "ip": a.ip, "country": a.country })).collect::<Vec<_>>(),
+        "countries": countries,
+    });
+}
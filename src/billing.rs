@@ -0,0 +1,193 @@
+/*!
+AWS Cost and Usage Report style billing logs.
+
+Each event is a single CUR line item, serialized as JSON in the message so that
+students have to parse it before aggregating costs. While the crypto mining
+incident is active, GPU instance usage for the compromised host is billed
+alongside the normal line items, producing a cost spike.
+
+The same line items are also available as a CSV export, one row per event.
+A header row starts the export, and is repeated periodically when writing to a
+file, as if the export had been split into several files.
+*/
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use serde_json::{self, json};
+
+use crate::{clock, mining, random};
+
+pub const CSV_SERVICE: &str = "aws.billing_export";
+pub const CSV_COLUMNS: [&str; 9] = [
+    "lineItem/UsageStartDate",
+    "lineItem/UsageEndDate",
+    "lineItem/UsageAccountId",
+    "lineItem/ProductCode",
+    "lineItem/UsageType",
+    "lineItem/ResourceId",
+    "lineItem/UsageAmount",
+    "lineItem/UnblendedCost",
+    "lineItem/CurrencyCode",
+];
+/// Rows between repeated header rows.
+const CSV_ROWS_PER_FILE: u64 = 500;
+
+static CSV_ROWS: AtomicU64 = AtomicU64::new(0);
+static CSV_PERIODIC_HEADERS: AtomicBool = AtomicBool::new(false);
+
+/// Product code, usage type, resource and the range of unblended cost in
+/// dollars for normal line items.
+const LINE_ITEMS: [(&str, &str, &str, f64, f64); 5] = [
+    (
+        "AmazonEC2",
+        "BoxUsage:m5.large",
+        "i-03f1a2b4c5d6e7f80",
+        0.05,
+        0.12,
+    ),
+    (
+        "AmazonEC2",
+        "BoxUsage:t3.medium",
+        "i-0a9b8c7d6e5f40312",
+        0.02,
+        0.05,
+    ),
+    (
+        "AmazonS3",
+        "TimedStorage-ByteHrs",
+        "storedog-assets",
+        0.01,
+        0.03,
+    ),
+    (
+        "AmazonRDS",
+        "InstanceUsage:db.r5.large",
+        "storedog-db",
+        0.15,
+        0.30,
+    ),
+    (
+        "AWSDataTransfer",
+        "DataTransfer-Out-Bytes",
+        "storedog-lb",
+        0.01,
+        0.09,
+    ),
+];
+
+fn record(product: &str, usage_type: &str, resource: &str, cost: f64) -> serde_json::Value {
+    let end = clock::now();
+    let start = end - chrono::Duration::hours(1);
+
+    return json!({
+        "lineItem/UsageStartDate": start.format("%Y-%m-%dT%H:00:00Z").to_string(),
+        "lineItem/UsageEndDate": end.format("%Y-%m-%dT%H:00:00Z").to_string(),
+        "lineItem/UsageAccountId": "1234567890",
+        "lineItem/ProductCode": product,
+        "lineItem/UsageType": usage_type,
+        "lineItem/ResourceId": resource,
+        "lineItem/UsageAmount": (random::range(0.5..1.0) * 10000.0_f64).round() / 10000.0,
+        "lineItem/UnblendedCost": (cost * 10000.0).round() / 10000.0,
+        "lineItem/CurrencyCode": "USD",
+    });
+}
+
+/// The line items billed for one event: a normal one, plus the GPU spike while
+/// the mining incident is active.
+fn records() -> Vec<serde_json::Value> {
+    let (product, usage_type, resource, low, high) = random::pick(&LINE_ITEMS);
+    let mut records = vec![record(
+        product,
+        usage_type,
+        resource,
+        random::range(*low..*high),
+    )];
+
+    if mining::active() {
+        records.push(record(
+            "AmazonEC2",
+            "BoxUsage:p3.16xlarge",
+            mining::COMPROMISED_INSTANCE_ID,
+            random::range(24.0..24.5),
+        ));
+    }
+    return records;
+}
+
+pub fn generate() -> serde_json::Value {
+    let events: Vec<serde_json::Value> = records()
+        .into_iter()
+        .map(|record| json!({ "message": record.to_string(), "service": "aws.billing" }))
+        .collect();
+    if events.len() == 1 {
+        return events[0].clone();
+    }
+    return json!(events);
+}
+
+/// Repeats the CSV header every so often, for file sinks.
+pub fn set_csv_periodic_headers(periodic: bool) {
+    CSV_PERIODIC_HEADERS.store(periodic, Ordering::Relaxed);
+}
+
+/// Starts a new CSV export, so the next row is preceded by a header.
+pub fn restart_csv_export() {
+    CSV_ROWS.store(0, Ordering::Relaxed);
+}
+
+fn csv_field(value: &serde_json::Value) -> String {
+    let text = match value {
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    if text.contains([',', '"', '\n']) {
+        return format!("\"{}\"", text.replace('"', "\"\""));
+    }
+    return text;
+}
+
+fn csv_event(message: String) -> serde_json::Value {
+    return json!({ "message": message, "service": CSV_SERVICE });
+}
+
+pub fn generate_csv() -> serde_json::Value {
+    let mut events = vec![];
+    for record in records() {
+        let row = CSV_ROWS.fetch_add(1, Ordering::Relaxed);
+        let periodic = CSV_PERIODIC_HEADERS.load(Ordering::Relaxed);
+        if row == 0 || (periodic && row.is_multiple_of(CSV_ROWS_PER_FILE)) {
+            events.push(csv_event(CSV_COLUMNS.join(",")));
+        }
+        events.push(csv_event(csv_row(&record)));
+    }
+    return json!(events);
+}
+
+fn csv_row(record: &serde_json::Value) -> String {
+    let fields: Vec<String> = CSV_COLUMNS.iter().map(|c| csv_field(&record[c])).collect();
+    return fields.join(",");
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{csv_event, csv_field, csv_row, records, CSV_COLUMNS};
+    use crate::validate;
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let header = csv_event(CSV_COLUMNS.join(","));
+        assert!(validate::event(&header).is_ok());
+        for record in records() {
+            let row = csv_event(csv_row(&record));
+            assert!(validate::event(&row).is_ok(), "{}", row);
+        }
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field(&json!("plain")), "plain");
+        assert_eq!(csv_field(&json!(1.5)), "1.5");
+        assert_eq!(csv_field(&json!("a,\"b\"")), "\"a,\"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,119 @@
+/*!
+The disk buffer stress scenario.
+
+When triggered, dynamo sends application logs as fast as the sink accepts them
+until it has sent a configured volume, sized to overflow Vector's default
+memory buffers. Students watch the buffer fill and then tune its type and size
+until the burst gets through. The answer key records exactly how many events
+and bytes the burst sent and when it started and finished.
+
+The burst's events are tagged `scenario:buffer_stress` and counted in the
+stats of their own generator.
+*/
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+
+use crate::sink::Event;
+use crate::stats::Counters;
+use crate::{answer_key, applog, stats, webhook};
+
+pub const GENERATOR: &str = "buffer-stress";
+pub const TAG: &str = "scenario:buffer_stress";
+
+static TARGET_BYTES: AtomicU64 = AtomicU64::new(1_000_000_000);
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Sets how much the burst sends, in gigabytes of uncompressed events.
+pub fn set_target_gb(gb: f64) {
+    TARGET_BYTES.store((gb * 1e9) as u64, Ordering::Relaxed);
+}
+
+/// A snapshot of the burst's counters, to measure it against.
+struct Sent {
+    events: u64,
+    dropped: u64,
+    bytes_uncompressed: u64,
+    bytes_wire: u64,
+}
+
+impl Sent {
+    fn now(counters: &Counters) -> Sent {
+        return Sent {
+            events: counters.sent.load(Ordering::Relaxed),
+            dropped: counters.dropped.load(Ordering::Relaxed),
+            bytes_uncompressed: counters.bytes_uncompressed.load(Ordering::Relaxed),
+            bytes_wire: counters.bytes_wire.load(Ordering::Relaxed),
+        };
+    }
+
+    fn since(&self, start: &Sent) -> serde_json::Value {
+        return json!({
+            "events_sent": self.events - start.events,
+            "events_dropped": self.dropped - start.dropped,
+            "bytes_uncompressed": self.bytes_uncompressed - start.bytes_uncompressed,
+            "bytes_wire": self.bytes_wire - start.bytes_wire,
+        });
+    }
+}
+
+/// Starts the burst. Only one burst runs at a time.
+pub fn start(tx: &Sender<Event>) -> Result<(), String> {
+    if RUNNING.swap(true, Ordering::Relaxed) {
+        return Err("buffer-stress is already running".to_owned());
+    }
+
+    let target = TARGET_BYTES.load(Ordering::Relaxed);
+    let counters = stats::counters(GENERATOR);
+    let start = Sent::now(counters);
+    let started = json!({ "target_bytes": target, "started_at": answer_key::now() });
+    answer_key::record(json!({ "buffer_stress": started.clone() }));
+    webhook::phase("buffer_stress", true, started);
+
+    let tx2 = tx.clone();
+    tokio::spawn(async move {
+        // Bytes are counted once the sender has encoded the events, so the
+        // burst may overshoot the target by the events still queued.
+        'burst: while RUNNING.load(Ordering::Relaxed)
+            && counters.bytes_uncompressed.load(Ordering::Relaxed) - start.bytes_uncompressed
+                < target
+        {
+            let mut events = applog::generate();
+            if !events.is_array() {
+                events = json!([events]);
+            }
+            for event in events.as_array_mut().into_iter().flatten() {
+                event["ddtags"] = json!(TAG);
+                if !crate::emit(&tx2, GENERATOR, event.take()).await {
+                    break 'burst;
+                }
+            }
+        }
+        RUNNING.store(false, Ordering::Relaxed);
+
+        // Wait for the queued events to be sent, so the totals are exact.
+        while counters.generated.load(Ordering::Relaxed)
+            > counters.sent.load(Ordering::Relaxed) + counters.dropped.load(Ordering::Relaxed)
+        {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let mut finished = Sent::now(counters).since(&start);
+        finished["target_bytes"] = json!(target);
+        finished["reached_target"] = json!(finished["bytes_uncompressed"].as_u64() >= Some(target));
+        finished["stopped_at"] = json!(answer_key::now());
+        answer_key::record(json!({ "buffer_stress": finished.clone() }));
+        webhook::phase("buffer_stress", false, finished);
+    });
+    return Ok(());
+}
+
+/// Stops the burst early.
+pub fn stop() -> Result<(), String> {
+    if !RUNNING.swap(false, Ordering::Relaxed) {
+        return Err("buffer-stress is not running".to_owned());
+    }
+    return Ok(());
+}
@@ -0,0 +1,281 @@
+/*!
+Offline bundles: a whole run rendered to static files.
+
+`dynamo export` steps a simulated clock through the run second by second,
+instead of sleeping, and writes every event to one file per generator in the
+configured line encoding, raw messages by default, so each file holds a
+single log format. Incidents and the feature flag regression start and stop
+at their configured offsets in simulated time, and the answer key, with times
+from the same clock, is written alongside the logs. With `--backfill` the run
+ends at the current time instead of starting there, and every second's events
+follow the weekday rates and diurnal curve, so the bundle holds a stretch of
+shaped history. The result can be copied
+into an air-gapped classroom or handed out as a homework dataset, or replayed
+to Vector with `dynamo serve`, which reads the bundle's `bundle.json`
+manifest of its encoding, start time, duration and files. Encodings other
+than JSON only hold each event's text, so each file's services are listed
+one per line in a `.services` file alongside it, for `dynamo serve` to
+restore.
+
+Generators that only talk to their own targets, the protobuf payments stream
+and the MQTT telemetry, aren't included, and neither are warmup, a scenario
+pack's timeline or a placed buffer stress burst.
+*/
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use chrono::Duration;
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::encoding::{self, Encoding};
+use crate::incident::Incident;
+use crate::{answer_key, clock, embed, flags, shape, storedog, Generator};
+
+pub const ANSWER_KEY: &str = "answer_key.json";
+pub const MANIFEST: &str = "bundle.json";
+const SERVICES_EXTENSION: &str = "services";
+
+pub struct Run {
+    pub duration_s: u64,
+    /// Whether the run ends now, rather than starting now.
+    pub backfill: bool,
+    pub encoding: Encoding,
+    /// Generators by name, with their rates.
+    pub sources: Vec<(&'static str, usize, Generator)>,
+    /// Incidents, with when they start and how long they last.
+    pub incidents: Vec<(&'static Incident, u64, u64)>,
+    /// One-off scenarios, with when they happen. Only the card leak is
+    /// exported.
+    pub one_offs: Vec<(&'static str, u64)>,
+    /// When the feature flag regression starts and is rolled back.
+    pub flag_regression: (u64, u64),
+    pub out: String,
+}
+
+/// Parses a duration such as `3600`, `90s`, `30m` or `1h` into seconds.
+pub fn parse_duration(s: &str) -> Result<u64, String> {
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, unit)) if unit.is_ascii_alphabetic() => (&s[..i], unit),
+        _ => (s, 's'),
+    };
+    let multiplier = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        _ => return Err(format!("unknown unit `{}`; use s, m or h", unit)),
+    };
+    let invalid = || format!("`{}` is not a duration such as 90s, 30m or 1h", s);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    return number.checked_mul(multiplier).ok_or_else(invalid);
+}
+
+/// Whether something starting `at_s` seconds in and lasting `duration_s`
+/// changes state at `second`: `Some(true)` when it starts, `Some(false)` when
+/// it stops. A zero `at_s` means never, and a zero duration means forever.
+fn transition(second: u64, at_s: u64, duration_s: u64) -> Option<bool> {
+    if at_s == 0 {
+        return None;
+    }
+    if second == at_s {
+        return Some(true);
+    }
+    if duration_s > 0 && Some(second) == at_s.checked_add(duration_s) {
+        return Some(false);
+    }
+    return None;
+}
+
+fn extension(encoding: Encoding) -> &'static str {
+    return match encoding {
+        Encoding::Json => "jsonl",
+        _ => "log",
+    };
+}
+
+struct Writer {
+    encoding: Encoding,
+    dir: String,
+    files: HashMap<&'static str, BufWriter<File>>,
+    /// The service of each line in the files, for encodings that drop it.
+    services: HashMap<&'static str, BufWriter<File>>,
+    events: u64,
+}
+
+fn create(dir: &str, name: &str) -> Result<BufWriter<File>, String> {
+    let path = Path::new(dir).join(name);
+    let file =
+        File::create(&path).map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+    return Ok(BufWriter::new(file));
+}
+
+impl Writer {
+    fn write(&mut self, generator: &'static str, value: serde_json::Value) -> Result<(), String> {
+        let events: Vec<serde_json::Value> = match value {
+            serde_json::Value::Array(events) => events,
+            event => vec![event],
+        };
+        for mut event in events {
+            crate::prepare(generator, &mut event);
+            if !self.files.contains_key(generator) {
+                let name = format!("{}.{}", generator, extension(self.encoding));
+                self.files.insert(generator, create(&self.dir, &name)?);
+                if self.encoding != Encoding::Json {
+                    let name = format!("{}.{}", generator, SERVICES_EXTENSION);
+                    self.services.insert(generator, create(&self.dir, &name)?);
+                }
+            }
+            let service = event["service"].as_str().unwrap_or_default().to_owned();
+            let (_, body) = encoding::batch(self.encoding, &[event]);
+            // Once for every line, so multi-line messages stay in step.
+            if let Some(services) = self.services.get_mut(generator) {
+                for _ in body.iter().filter(|b| **b == b'\n') {
+                    writeln!(services, "{}", service)
+                        .map_err(|e| format!("could not write {} services: {}", generator, e))?;
+                }
+            }
+            self.files
+                .get_mut(generator)
+                .expect("the file was just opened")
+                .write_all(&body)
+                .map_err(|e| format!("could not write {} events: {}", generator, e))?;
+            self.events += 1;
+        }
+        return Ok(());
+    }
+}
+
+pub fn export(run: &Run) -> Result<(), String> {
+    if !run.encoding.is_line() {
+        return Err("export only supports line encodings".to_owned());
+    }
+    std::fs::create_dir_all(&run.out)
+        .map_err(|e| format!("could not create {}: {}", run.out, e))?;
+
+    let mut writer = Writer {
+        encoding: run.encoding,
+        dir: run.out.clone(),
+        files: HashMap::new(),
+        services: HashMap::new(),
+        events: 0,
+    };
+    let start = match run.backfill {
+        true => i64::try_from(run.duration_s)
+            .ok()
+            .filter(|seconds| *seconds <= i64::MAX / 1000)
+            .and_then(|seconds| clock::now().checked_sub_signed(Duration::seconds(seconds)))
+            .ok_or_else(|| format!("cannot backfill {} seconds", run.duration_s))?,
+        false => clock::now(),
+    };
+    // Fractions of an event that shaping owes each generator, carried into the
+    // next second.
+    let mut owed = vec![0.0; run.sources.len()];
+    for second in 0..run.duration_s {
+        let at = start + Duration::seconds(second as i64);
+        clock::freeze(Some(at));
+
+        for (incident, at_s, duration_s) in run.incidents.iter() {
+            if let Some(active) = transition(second, *at_s, *duration_s) {
+                incident.set(active);
+            }
+        }
+        for (scenario, at_s) in run.one_offs.iter() {
+            if second == *at_s && *scenario == "card-leak" {
+                let events = storedog::leak();
+                storedog::record_leak(&events);
+                writer.write("storedog-leak", events)?;
+            }
+        }
+        let (at_s, rollback_after_s) = run.flag_regression;
+        if let Some(enabled) = transition(second, at_s, rollback_after_s) {
+            writer.write(
+                "feature-flags",
+                flags::flag_change_event(flags::CHECKOUT_V2, enabled),
+            )?;
+            flags::apply_checkout_v2(enabled);
+        }
+
+        let multiplier = shape::multiplier(at);
+        for ((name, rate_limit_per_s, generator), owed) in run.sources.iter().zip(&mut owed) {
+            *owed += *rate_limit_per_s as f64 * multiplier;
+            let events = owed.floor();
+            *owed -= events;
+            let events = events as i64;
+            for i in 0..events {
+                let offset = Duration::microseconds(i * 1_000_000 / events);
+                clock::freeze(Some(at + offset));
+                writer.write(name, embed::generate(name, *generator))?;
+            }
+        }
+    }
+
+    for (generator, file) in writer.files.iter_mut().chain(writer.services.iter_mut()) {
+        file.flush()
+            .map_err(|e| format!("could not write {} events: {}", generator, e))?;
+    }
+    answer_key::write(&Path::new(&run.out).join(ANSWER_KEY).to_string_lossy());
+    clock::freeze(None);
+
+    let files: serde_json::Map<String, serde_json::Value> = writer
+        .files
+        .keys()
+        .map(|generator| {
+            let file = format!("{}.{}", generator, extension(run.encoding));
+            return (generator.to_string(), json!(file));
+        })
+        .collect();
+    let services: serde_json::Map<String, serde_json::Value> = writer
+        .services
+        .keys()
+        .map(|generator| {
+            let file = format!("{}.{}", generator, SERVICES_EXTENSION);
+            return (generator.to_string(), json!(file));
+        })
+        .collect();
+    let manifest = json!({
+        "encoding": run.encoding.to_possible_value().expect("encodings should not be skipped").get_name(),
+        "started_at": start.timestamp_micros() / 1000,
+        "duration_s": run.duration_s,
+        "files": files,
+        "services": services,
+    });
+    let path = Path::new(&run.out).join(MANIFEST);
+    let contents = serde_json::to_string_pretty(&manifest).expect("manifest should serialize");
+    std::fs::write(&path, contents)
+        .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+    println!(
+        "Wrote {} events from {} generators and the answer key to {}",
+        writer.events,
+        writer.files.len(),
+        run.out
+    );
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parses_durations() {
+        assert_eq!(super::parse_duration("90").unwrap(), 90);
+        assert_eq!(super::parse_duration("90s").unwrap(), 90);
+        assert_eq!(super::parse_duration("30m").unwrap(), 1800);
+        assert_eq!(super::parse_duration("1h").unwrap(), 3600);
+        assert!(super::parse_duration("1d").is_err());
+        assert!(super::parse_duration("h").is_err());
+        assert!(super::parse_duration("9999999999999999h").is_err());
+    }
+
+    #[test]
+    fn transitions_at_offsets() {
+        assert_eq!(super::transition(10, 10, 5), Some(true));
+        assert_eq!(super::transition(15, 10, 5), Some(false));
+        assert_eq!(super::transition(12, 10, 5), None);
+        assert_eq!(super::transition(0, 0, 5), None);
+        assert_eq!(super::transition(1000, 10, 0), None);
+        // Something that would stop after the end of time never does.
+        assert_eq!(super::transition(9, 10, u64::MAX), None);
+        assert_eq!(super::transition(u64::MAX, 10, u64::MAX), None);
+    }
+}
@@ -0,0 +1,28 @@
+/*!
+The cardinality explosion scenario.
+
+Partway through the run, a well-meaning change starts tagging every storedog
+request with its own UUID. Each tag value is unique, so any metric derived from
+these logs would gain a new series per request. Students are expected to spot
+the tag and drop or rewrite it in Vector before it reaches the backend.
+*/
+use serde_json::json;
+
+use crate::incident::Incident;
+use crate::random;
+
+pub const TAG: &str = "request_id";
+
+pub static INCIDENT: Incident = Incident::new("cardinality_explosion", details);
+
+/// Extra tags for a storedog request, if any.
+pub fn request_tags() -> Option<String> {
+    if !INCIDENT.active() {
+        return None;
+    }
+    return Some(format!("{}:{}", TAG, random::uuid_v4()));
+}
+
+fn details() -> serde_json::Value {
+    return json!({ "tag": TAG });
+}
@@ -0,0 +1,81 @@
+/*!
+The dependency failure cascade.
+
+storedog's services depend on each other: the storefront calls the cart
+service to check out, and the cart calls the payments service. When this
+incident starts, the payments service slows down, and the failure propagates
+up the dependency graph with a lag at every hop: first checkouts start failing
+in the cart service, then the storefront answers checkouts with 502s. There is
+one true origin, and the order in which symptoms appear leads back to it.
+*/
+use serde_json::{self, json};
+
+use crate::incident::Incident;
+use crate::random;
+
+pub const ORIGIN: &str = "storedog-payments";
+
+/// Each service, the service it depends on, and how many seconds a failure
+/// in the dependency takes to reach it.
+const DEPENDENCIES: [(&str, &str, i64); 2] = [
+    ("storedog-cart", "storedog-payments", 15),
+    ("storedog", "storedog-cart", 15),
+];
+
+/// Chance that a log line from an impacted backend service shows the failure,
+/// rather than being one of its normal lines.
+const SYMPTOM_CHANCE: f64 = 0.5;
+
+pub static INCIDENT: Incident = Incident::new("dependency_cascade", details);
+
+/// Seconds after the origin fails that `service` is impacted, or `None` if the
+/// failure never reaches it.
+fn lag_s(service: &str) -> Option<i64> {
+    if service == ORIGIN {
+        return Some(0);
+    }
+    let (_, dependency, lag) = DEPENDENCIES.iter().find(|(s, _, _)| *s == service)?;
+    return lag_s(dependency).map(|l| l + lag);
+}
+
+/// Whether the cascade has reached `service` yet.
+pub fn impacted(service: &str) -> bool {
+    return match (INCIDENT.active_for(), lag_s(service)) {
+        (Some(elapsed), Some(lag)) => elapsed.num_seconds() >= lag,
+        _ => false,
+    };
+}
+
+/// The level and body of a log line showing the failure in an impacted
+/// backend service, some of the time.
+pub fn symptom(service: &str) -> Option<(&'static str, String)> {
+    if !impacted(service) || !random::chance(SYMPTOM_CHANCE) {
+        return None;
+    }
+
+    return match service {
+        "storedog-payments" => Some((
+            "WARN",
+            format!(
+                "payment gateway call took {}ms (slow threshold 1000ms)",
+                random::range(2500..6000)
+            ),
+        )),
+        "storedog-cart" => Some((
+            "ERROR",
+            format!(
+                "checkout failed: request to {} timed out after 5000ms",
+                ORIGIN
+            ),
+        )),
+        _ => None,
+    };
+}
+
+fn details() -> serde_json::Value {
+    let mut propagation = vec![json!({ "service": ORIGIN, "impacted_after_s": 0 })];
+    for (service, _, _) in DEPENDENCIES.iter() {
+        propagation.push(json!({ "service": service, "impacted_after_s": lag_s(service) }));
+    }
+    return json!({ "origin": ORIGIN, "propagation": propagation });
+}
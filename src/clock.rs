@@ -0,0 +1,22 @@
+/*!
+The time used for generated content.
+
+Generators read the time from here rather than from `Utc::now` directly, so
+that it can be frozen on a thread when rendering golden outputs.
+*/
+use std::cell::Cell;
+
+use chrono::prelude::*;
+
+thread_local! {
+    static FROZEN: Cell<Option<DateTime<Utc>>> = const { Cell::new(None) };
+}
+
+pub fn now() -> DateTime<Utc> {
+    return FROZEN.with(|f| f.get()).unwrap_or_else(Utc::now);
+}
+
+/// Freezes the clock on the current thread at `at`, or unfreezes it.
+pub fn freeze(at: Option<DateTime<Utc>>) {
+    FROZEN.with(|f| f.set(at));
+}
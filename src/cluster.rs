@@ -0,0 +1,161 @@
+/*!
+Coordinating several dynamo instances.
+
+One instance can't always produce enough load to show an aggregator tier at
+real scale, so the load can be split over workers on several machines, with a
+coordinator keeping their timelines in step:
+
+ - Each worker runs with `--slice <i>/<n>` and the same rates as a single
+   instance would, and generates its share of every rate, so the workers
+   together produce the configured totals. With `--seed`, each slice draws
+   from its own stream of the seed. Each slice's generators are labelled
+   with it in `dynamo_generator`, such as `vpc-accept@2/3`, so that
+   `dynamo verify-sequence` can check the workers' combined output.
+ - The coordinator runs with `--worker <admin address>` for each worker, whose
+   admin API must be enabled. Every scenario it starts or stops, whether
+   scheduled, from a scenario pack or triggered by hand, is forwarded to all
+   workers at once, so incidents start at the same moment everywhere. One-off
+   scenarios, the card leak and the buffer burst, only happen on the
+   coordinator, so the cluster produces exactly one of each.
+
+The fleet, attackers and incident details are fixed, so every instance
+simulates the same entities. Timings go into the coordinator's answer key;
+workers' answer keys only count what they generated themselves.
+*/
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+
+use serde_json::json;
+
+use crate::{answer_key, trigger};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slice {
+    /// Which slice this instance generates, from 1.
+    pub index: usize,
+    pub count: usize,
+}
+
+static SLICE: OnceLock<Slice> = OnceLock::new();
+static WORKERS: OnceLock<Vec<SocketAddr>> = OnceLock::new();
+
+pub fn parse_slice(s: &str) -> Result<Slice, String> {
+    let invalid = || format!("`{}` is not a slice such as 1/3", s);
+    let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+    let slice = Slice {
+        index: index.parse().map_err(|_| invalid())?,
+        count: count.parse().map_err(|_| invalid())?,
+    };
+    if slice.index == 0 || slice.index > slice.count {
+        return Err(invalid());
+    }
+    return Ok(slice);
+}
+
+pub fn set_slice(slice: Slice) {
+    SLICE.set(slice).expect("slice should only be set once");
+    answer_key::record(json!({
+        "cluster": { "slice": format!("{}/{}", slice.index, slice.count) },
+    }));
+}
+
+pub fn set_workers(workers: Vec<SocketAddr>) {
+    let names: Vec<String> = workers.iter().map(|w| w.to_string()).collect();
+    answer_key::record(json!({ "cluster": { "workers": names } }));
+    WORKERS
+        .set(workers)
+        .expect("workers should only be set once");
+}
+
+/// Offsets `seed` so each slice generates different content.
+pub fn seed(seed: u64) -> u64 {
+    let index = SLICE.get().map(|s| s.index).unwrap_or(1);
+    return slice_seed(seed, index);
+}
+
+/// Thread RNGs add their thread number to the seed, so slices go in the
+/// upper half to keep their threads' seeds apart.
+fn slice_seed(seed: u64, index: usize) -> u64 {
+    return seed ^ ((index as u64 - 1) << 32);
+}
+
+/// `name` with this instance's slice, if it has one.
+pub fn label(name: &str) -> String {
+    return match SLICE.get() {
+        Some(slice) => format!("{}@{}/{}", name, slice.index, slice.count),
+        None => name.to_owned(),
+    };
+}
+
+/// This instance's share of `rate_per_s`. The remainder of an uneven split
+/// goes to the first slices.
+pub fn share(rate_per_s: usize) -> usize {
+    return match SLICE.get() {
+        Some(slice) => share_of(slice, rate_per_s),
+        None => rate_per_s,
+    };
+}
+
+fn share_of(slice: &Slice, rate_per_s: usize) -> usize {
+    let extra = (slice.index <= rate_per_s % slice.count) as usize;
+    return rate_per_s / slice.count + extra;
+}
+
+/// Starts, or with `stop` ends, the named scenario on every worker.
+pub fn forward(scenario: &'static str, stop: bool) {
+    for worker in WORKERS.get().into_iter().flatten() {
+        let worker = *worker;
+        tokio::spawn(async move {
+            if let Err(e) = trigger::request(worker, scenario, stop).await {
+                println!("Could not forward {} to worker {}: {}", scenario, worker, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Slice;
+
+    #[test]
+    fn parses_slices() {
+        assert_eq!(
+            super::parse_slice("2/3").unwrap(),
+            Slice { index: 2, count: 3 }
+        );
+        assert!(super::parse_slice("0/3").is_err());
+        assert!(super::parse_slice("4/3").is_err());
+        assert!(super::parse_slice("3").is_err());
+    }
+
+    #[test]
+    fn slices_draw_different_streams() {
+        use rand::{Rng, SeedableRng};
+
+        let first = |seed: u64| rand::rngs::StdRng::seed_from_u64(seed).gen::<u64>();
+        // Slice 2's first thread against slice 1's second.
+        let seed = 423;
+        assert_ne!(
+            first(super::slice_seed(seed, 2)),
+            first(super::slice_seed(seed, 1) + 1)
+        );
+        let mut seeds: Vec<u64> = (1..=4)
+            .flat_map(|index| (0..64).map(move |thread| super::slice_seed(seed, index) + thread))
+            .collect();
+        seeds.sort_unstable();
+        seeds.dedup();
+        assert_eq!(seeds.len(), 4 * 64);
+    }
+
+    #[test]
+    fn shares_add_up() {
+        for count in 1..5 {
+            for rate in [0, 1, 7, 100] {
+                let total: usize = (1..=count)
+                    .map(|index| super::share_of(&Slice { index, count }, rate))
+                    .sum();
+                assert_eq!(total, rate);
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+/*!
+Checks for scenario packs before class.
+
+A typo in a pack's manifest otherwise surfaces mid-lesson: an unknown option
+stops dynamo at startup, and a misspelled timeline key is silently ignored.
+`dynamo config validate <pack>` checks a manifest the way loading it would,
+and also for keys dynamo doesn't know and for the problems it checks before
+starting a run, exiting non-zero if there are any. `dynamo config schema`
+prints a JSON Schema for manifests, generated from dynamo's own options and
+scenarios, so editors and CI can check packs without running dynamo.
+*/
+use std::path::Path;
+
+use clap::builder::ValueParser;
+use clap::{ArgAction, CommandFactory, Parser};
+use serde_json::{self, json};
+
+use crate::{pack, preflight, trigger, Args};
+
+/// Options that can't be set from a pack.
+const NOT_PACK_OPTIONS: [&str; 3] = ["help", "version", "scenario-pack"];
+
+fn is<T: 'static>(parser: &ValueParser, value: &T) -> bool {
+    return parser.type_id() == value.into();
+}
+
+/// The schema for one option's value in a pack's `args`.
+fn option(arg: &clap::Arg) -> serde_json::Value {
+    let mut value = match arg.get_action() {
+        ArgAction::SetTrue => json!({ "type": "boolean" }),
+        _ => {
+            let parser = arg.get_value_parser();
+            let possible: Vec<String> = arg
+                .get_possible_values()
+                .iter()
+                .map(|v| v.get_name().to_owned())
+                .collect();
+            if !possible.is_empty() {
+                json!({ "enum": possible })
+            } else if is(parser, &0u64) || is(parser, &0usize) || is(parser, &0u32) {
+                json!({ "type": "integer", "minimum": 0 })
+            } else if is(parser, &0i64) || is(parser, &0i32) {
+                json!({ "type": "integer" })
+            } else if is(parser, &0f64) {
+                json!({ "type": "number" })
+            } else {
+                json!({ "type": "string" })
+            }
+        }
+    };
+    // Repeatable options take one value or a list.
+    if matches!(arg.get_action(), ArgAction::Append) {
+        value = json!({ "anyOf": [value, { "type": "array", "items": value }] });
+    }
+    if let Some(help) = arg.get_help() {
+        value["description"] = json!(help.to_string());
+    }
+    return value;
+}
+
+pub fn schema() -> serde_json::Value {
+    let command = Args::command();
+    let mut options = serde_json::Map::new();
+    for arg in command.get_arguments() {
+        match arg.get_long() {
+            Some(long) if !NOT_PACK_OPTIONS.contains(&long) => {
+                options.insert(long.to_owned(), option(arg));
+            }
+            _ => {}
+        }
+    }
+    let scenarios: Vec<&str> = trigger::SCENARIOS.iter().map(|s| s.name).collect();
+
+    return json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "dynamo scenario pack",
+        "description": "A scenario pack's pack.json manifest.",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "name": { "type": "string" },
+            "version": { "type": "string" },
+            "args": {
+                "description": "Command line options by their long name, without the dashes.",
+                "type": "object",
+                "additionalProperties": false,
+                "properties": options,
+            },
+            "timeline": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "additionalProperties": false,
+                    "required": ["at_s"],
+                    "anyOf": [
+                        { "required": ["scenario"] },
+                        { "required": ["narration"] },
+                        { "required": ["deploy"] },
+                    ],
+                    "properties": {
+                        "at_s": { "type": "integer", "minimum": 0 },
+                        "scenario": { "enum": scenarios },
+                        "stop": { "type": "boolean" },
+                        "narration": { "type": "string" },
+                        "deploy": {
+                            "type": "object",
+                            "additionalProperties": false,
+                            "required": ["service", "version"],
+                            "properties": {
+                                "service": { "type": "string" },
+                                "version": { "type": "string" },
+                            },
+                        },
+                    },
+                },
+            },
+            "grading": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["incident", "question"],
+                    "properties": {
+                        "incident": { "type": "string" },
+                        "question": { "type": "string" },
+                        "subtlety": { "type": "string" },
+                        "points": { "type": "integer", "minimum": 0 },
+                        "evidence": { "type": "array", "items": { "type": "string" } },
+                    },
+                },
+            },
+        },
+    });
+}
+
+pub fn print_schema() -> Result<(), String> {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema()).expect("schema should serialize")
+    );
+    return Ok(());
+}
+
+/// Keys of `value` that aren't in `known`, as problems found in `place`.
+fn unknown_keys(value: &serde_json::Value, known: &[&str], place: &str) -> Vec<String> {
+    return value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(key, _)| key)
+        .filter(|key| !known.contains(&key.as_str()))
+        .map(|key| format!("{}: unknown key `{}`", place, key))
+        .collect();
+}
+
+/// Every problem with a manifest.
+fn problems(manifest: &serde_json::Value) -> Vec<String> {
+    let mut problems = unknown_keys(manifest, &pack::KEYS, "pack");
+    for (i, step) in manifest["timeline"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        let place = format!("timeline step {}", i + 1);
+        problems.extend(unknown_keys(step, &pack::STEP_KEYS, &place));
+    }
+    if let Err(e) = pack::check(manifest) {
+        problems.push(e);
+    }
+
+    let args = match pack::args(manifest) {
+        Ok(args) => args,
+        Err(e) => {
+            problems.push(e);
+            return problems;
+        }
+    };
+    match Args::try_parse_from(std::iter::once("dynamo".to_owned()).chain(args)) {
+        Ok(args) => problems.extend(preflight::check(&args)),
+        Err(e) => {
+            // Only the error and its tip, without the usage clap appends.
+            let message = e.render().to_string();
+            let message = message.split("\n\nUsage:").next().unwrap_or_default();
+            problems.push(message.trim().to_owned());
+        }
+    }
+    return problems;
+}
+
+/// Checks the pack in `dir`, which may also be the path to its manifest or
+/// tarball, or the name of a built-in pack.
+pub fn validate(dir: &str) -> Result<(), String> {
+    let manifest = if Path::new(dir).is_file() && !pack::is_tarball(dir) {
+        let contents =
+            std::fs::read_to_string(dir).map_err(|e| format!("could not read {}: {}", dir, e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("{} is not valid JSON: {}", dir, e))?
+    } else {
+        pack::manifest(dir)?
+    };
+
+    let problems = problems(&manifest);
+    if problems.is_empty() {
+        println!("{} is valid", dir);
+        return Ok(());
+    }
+    for problem in problems.iter() {
+        println!("{}", problem);
+    }
+    return Err(format!("{} problems found in {}", problems.len(), dir));
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn schema_describes_options() {
+        let schema = super::schema();
+        let options = &schema["properties"]["args"]["properties"];
+        assert_eq!(options["seed"]["type"], "integer");
+        assert_eq!(options["keyboard-triggers"]["type"], "boolean");
+        assert_eq!(options["queue-policy"]["enum"][0], "block");
+        assert_eq!(options["hostname"]["anyOf"][1]["type"], "array");
+        assert!(options["scenario-pack"].is_null());
+    }
+
+    #[test]
+    fn finds_typos() {
+        let manifest = json!({
+            "name": "typos",
+            "args": { "http-log-rate-limit-per-sec": 5, "seed": 7 },
+            "timeline": [{ "at_s": 60, "scenario": "card-leak", "stpo": true }],
+            "gradnig": [],
+        });
+        let problems = super::problems(&manifest);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert_eq!(problems[0], "pack: unknown key `gradnig`");
+        assert_eq!(problems[1], "timeline step 1: unknown key `stpo`");
+        assert!(problems[2].contains("--http-log-rate-limit-per-sec"));
+    }
+
+    #[test]
+    fn built_in_packs_have_no_problems() {
+        for pack in ["storedog-incident-day", "soc-analyst-shift"] {
+            let manifest = crate::pack::manifest(pack).unwrap();
+            assert_eq!(super::problems(&manifest), Vec::<String>::new());
+        }
+    }
+}
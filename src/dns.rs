@@ -0,0 +1,120 @@
+/*!
+Windows DNS Server debug logs.
+
+The domain controller's DNS server logs every packet it receives and sends in
+its debug log format: fixed-ish columns padded with runs of spaces, a
+bracketed flags section, and question names written as length-prefixed
+labels. Each lookup produces the query and the response, as two lines.
+
+During the DGA incident, the compromised worker host starts looking up
+algorithmically generated domains, most of which don't exist, as malware
+does when searching for its command and control server. The lookups come
+from a dropped binary, whose connections to the DNS server show up in the
+Falco stream.
+*/
+use serde_json::{self, json};
+
+use crate::incident::Incident;
+use crate::{clock, hosts, random};
+
+pub const SERVICE: &str = "windows-dns";
+/// Names the fleet looks up in normal operation.
+const NAMES: [&str; 8] = [
+    "db.storedog.internal",
+    "cart.storedog.internal",
+    "catalog.storedog.internal",
+    "payments.storedog.internal",
+    "api.stripe.com",
+    "s3.us-east-1.amazonaws.com",
+    "time.windows.com",
+    "registry.npmjs.org",
+];
+const DGA_TLDS: [&str; 3] = ["ru", "top", "xyz"];
+/// Share of lookups that come from the compromised host during the incident.
+const DGA_SHARE: f64 = 0.4;
+/// The malware making the lookups.
+pub const DGA_PROCESS_PATH: &str = "/var/tmp/.cache/.updater";
+
+pub static INCIDENT: Incident = Incident::new("dga_lookups", details);
+
+fn details() -> serde_json::Value {
+    return json!({
+        "client_ip": hosts::COMPROMISED.ip,
+        "client_hostname": hosts::COMPROMISED.hostname,
+        "client_process": DGA_PROCESS_PATH,
+        "tlds": DGA_TLDS,
+        "pattern": "12 to 20 random lowercase letters",
+    });
+}
+
+/// A domain made of random letters, like those a DGA produces.
+fn dga_domain() -> String {
+    let label: String = (0..random::range(12..=20))
+        .map(|_| random::range(b'a'..=b'z') as char)
+        .collect();
+    return format!("{}.{}", label, random::pick(&DGA_TLDS));
+}
+
+/// A question name in wire-like notation, e.g. `(3)www(7)example(3)com(0)`.
+fn wire_name(name: &str) -> String {
+    let labels: String = name
+        .split('.')
+        .map(|label| format!("({}){}", label.len(), label))
+        .collect();
+    return format!("{}(0)", labels);
+}
+
+/// One packet line. `response` is None for queries, and the response code
+/// for responses.
+fn line(client_ip: &str, xid: u16, qtype: &str, name: &str, response: Option<&str>) -> String {
+    let (direction, r, flags, flag_chars, rcode) = match response {
+        None => ("Rcv", ' ', 0x0001, "  D ", "NOERROR"),
+        Some(rcode) => {
+            let code = if rcode == "NXDOMAIN" { 3 } else { 0 };
+            ("Snd", 'R', 0x8180 | code, "  DR", rcode)
+        }
+    };
+    return format!(
+        "{} {:04X} PACKET  {:016X} UDP {} {:<15} {:04x} {} Q [{:04x} {}{:>9}] {:<6} {}",
+        clock::now().format("%-m/%-d/%Y %-I:%M:%S %p"),
+        random::range(0x0400u16..0x1000),
+        random::range(0x0000_0200_0000_0000u64..0x0000_03ff_ffff_ffff),
+        direction,
+        client_ip,
+        xid,
+        r,
+        flags,
+        flag_chars,
+        rcode,
+        qtype,
+        wire_name(name),
+    );
+}
+
+pub fn generate() -> serde_json::Value {
+    let (client_ip, name, rcode) = if INCIDENT.active() && random::chance(DGA_SHARE) {
+        let rcode = if random::chance(0.9) {
+            "NXDOMAIN"
+        } else {
+            "NOERROR"
+        };
+        (hosts::COMPROMISED.ip, dga_domain(), rcode)
+    } else {
+        let client = random::pick(&[hosts::web(), random::pick(&hosts::BACKEND)]).ip;
+        (client, random::pick(&NAMES).to_string(), "NOERROR")
+    };
+    let qtype = if random::chance(0.8) { "A" } else { "AAAA" };
+    let xid = random::range(0..=u16::MAX);
+
+    let event = |message: String| {
+        return json!({
+            "message": message,
+            "service": SERVICE,
+            "hostname": hosts::DNS.hostname,
+        });
+    };
+    return json!([
+        event(line(client_ip, xid, qtype, &name, None)),
+        event(line(client_ip, xid, qtype, &name, Some(rcode))),
+    ]);
+}
@@ -0,0 +1,111 @@
+/*!
+Anomalies embedded in normal streams.
+
+Anomalies usually arrive from generators of their own, at their own rates, so
+a student can find them by rate alone. With `--embed-anomaly
+storedog-ok=storedog-leak:10000`, each storedog-ok event has a 1 in 10,000
+chance of being a leak instead, sent as part of the storedog-ok stream with
+its identity and sequence numbers. Combined with a zero rate for the
+anomaly's own generator, the anomaly can only be found by its content.
+
+How many anomalies were embedded in each stream goes into the answer key.
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use serde_json::{self, json};
+
+use crate::{random, Generator};
+
+/// A host generator, an anomaly generator, and the odds of one in how many
+/// events being the anomaly.
+pub type Spec = (String, String, u64);
+
+struct Embedding {
+    host: &'static str,
+    anomaly: &'static str,
+    generator: Generator,
+    one_in: u64,
+    embedded: AtomicU64,
+}
+
+static EMBEDDINGS: OnceLock<Vec<Embedding>> = OnceLock::new();
+
+/// Parses `<generator>=<anomaly generator>:<one in>`.
+pub fn parse(s: &str) -> Result<Spec, String> {
+    let invalid = || {
+        return format!("`{}` should look like storedog-ok=storedog-leak:10000", s);
+    };
+    let (host, rest) = s.split_once('=').ok_or_else(invalid)?;
+    let (anomaly, one_in) = rest.split_once(':').ok_or_else(invalid)?;
+    let one_in: u64 = one_in.parse().map_err(|_| invalid())?;
+    if one_in == 0 {
+        return Err(format!("the odds in `{}` should be at least 1", s));
+    }
+    return Ok((host.to_owned(), anomaly.to_owned(), one_in));
+}
+
+/// Resolves the configured embeddings against the generators by name.
+pub fn configure(specs: &[Spec], generators: &[(&'static str, Generator)]) -> Result<(), String> {
+    let find = |name: &str| {
+        return generators
+            .iter()
+            .find(|(n, _)| *n == name)
+            .copied()
+            .ok_or_else(|| format!("unknown generator `{}` to embed anomalies in", name));
+    };
+
+    let mut embeddings = vec![];
+    for (host, anomaly, one_in) in specs {
+        let (host, _) = find(host)?;
+        let (anomaly, generator) = find(anomaly)?;
+        embeddings.push(Embedding {
+            host,
+            anomaly,
+            generator,
+            one_in: *one_in,
+            embedded: AtomicU64::new(0),
+        });
+    }
+    EMBEDDINGS
+        .set(embeddings)
+        .map_err(|_| "embedded anomalies should only be configured once".to_owned())?;
+    return Ok(());
+}
+
+/// Runs `generator` for the `host` stream, or one of the anomalies embedded in
+/// it.
+pub fn generate(host: &str, generator: Generator) -> serde_json::Value {
+    for embedding in EMBEDDINGS.get().into_iter().flatten() {
+        if embedding.host == host && random::range(0..embedding.one_in) == 0 {
+            embedding.embedded.fetch_add(1, Ordering::Relaxed);
+            return (embedding.generator)();
+        }
+    }
+    return generator();
+}
+
+pub fn summary() -> serde_json::Value {
+    let mut summary = json!({});
+    for embedding in EMBEDDINGS.get().into_iter().flatten() {
+        summary[embedding.host][embedding.anomaly] = json!({
+            "one_in": embedding.one_in,
+            "embedded": embedding.embedded.load(Ordering::Relaxed),
+        });
+    }
+    return summary;
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parses_embeddings() {
+        assert_eq!(
+            super::parse("storedog-ok=storedog-leak:10000").unwrap(),
+            ("storedog-ok".to_owned(), "storedog-leak".to_owned(), 10000)
+        );
+        assert!(super::parse("storedog-ok=storedog-leak").is_err());
+        assert!(super::parse("storedog-ok:10").is_err());
+        assert!(super::parse("storedog-ok=storedog-leak:0").is_err());
+    }
+}
@@ -0,0 +1,234 @@
+/*!
+Encodings for batches of events.
+
+Generators only build JSON values; how those values are written on the wire is
+decided here, from the sink configuration. The `datadog_agent` sink always
+sends JSON arrays and the OTLP sink always sends protobuf, while the generic
+HTTP sink can send any encoding, binary ones included, so the same generators
+can feed lessons on Vector's decoding options.
+*/
+use clap::ValueEnum;
+
+use crate::{avro, otlp};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    /// One JSON object per line.
+    Json,
+    /// A single JSON array holding the whole batch.
+    JsonArray,
+    /// One line of `key=value` pairs per event.
+    Logfmt,
+    /// Just the message of each event, one per line.
+    Raw,
+    /// ArcSight Common Event Format, one event per line.
+    Cef,
+    /// An OTLP `ExportLogsServiceRequest`, as sent to OTLP/HTTP receivers.
+    Protobuf,
+    /// An Avro object container file with the event schema embedded.
+    Avro,
+}
+
+impl Encoding {
+    pub fn content_type(&self) -> &'static str {
+        return match self {
+            Encoding::Json => "application/x-ndjson",
+            Encoding::JsonArray => "application/json",
+            Encoding::Protobuf => "application/x-protobuf",
+            Encoding::Avro => "avro/binary",
+            _ => "text/plain",
+        };
+    }
+
+    /// Whether the encoding writes one line per event, so that batches can
+    /// be appended to each other.
+    pub fn is_line(&self) -> bool {
+        return ![Encoding::JsonArray, Encoding::Protobuf, Encoding::Avro].contains(self);
+    }
+}
+
+/// Fields written first, in this order, by the line encodings.
+const LEADING_FIELDS: [&str; 6] = [
+    "timestamp",
+    "status",
+    "hostname",
+    "service",
+    "ddsource",
+    "ddtags",
+];
+
+/// Flattens nested objects into dotted keys, with the leading fields first
+/// and the rest in key order.
+pub fn fields(event: &serde_json::Value) -> Vec<(String, serde_json::Value)> {
+    fn flatten(
+        prefix: &str,
+        value: &serde_json::Value,
+        out: &mut Vec<(String, serde_json::Value)>,
+    ) {
+        match value.as_object() {
+            Some(map) => {
+                for (k, v) in map {
+                    let key = if prefix.is_empty() {
+                        k.clone()
+                    } else {
+                        format!("{}.{}", prefix, k)
+                    };
+                    flatten(&key, v, out);
+                }
+            }
+            None => out.push((prefix.to_owned(), value.clone())),
+        }
+    }
+
+    let mut out = vec![];
+    flatten("", event, &mut out);
+    out.sort_by_key(|(k, _)| {
+        let rank = LEADING_FIELDS
+            .iter()
+            .position(|f| f == k)
+            .unwrap_or(LEADING_FIELDS.len());
+        return (rank, k.clone());
+    });
+    return out;
+}
+
+pub fn text(value: &serde_json::Value) -> String {
+    return match value {
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+}
+
+pub fn logfmt(event: &serde_json::Value) -> String {
+    let pairs: Vec<String> = fields(event)
+        .into_iter()
+        .map(|(k, v)| {
+            let value = text(&v);
+            if !value.is_empty() && !value.contains([' ', '"', '=', '\\']) {
+                return format!("{}={}", k, value);
+            }
+            return format!("{}={}", k, serde_json::Value::String(value));
+        })
+        .collect();
+    return pairs.join(" ");
+}
+
+fn cef_severity(status: &str) -> u8 {
+    return match status.to_ascii_uppercase().as_str() {
+        "DEBUG" => 1,
+        "WARN" | "WARNING" => 6,
+        "ERROR" => 8,
+        _ => 3,
+    };
+}
+
+fn cef_header(s: &str) -> String {
+    return s.replace('\\', "\\\\").replace('|', "\\|");
+}
+
+fn cef_extension(s: &str) -> String {
+    return s
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\n', "\\n");
+}
+
+pub fn cef(event: &serde_json::Value) -> String {
+    let service = event["service"].as_str().unwrap_or("dynamo");
+    let mut extension = vec![
+        format!("rt={}", text(&event["timestamp"])),
+        format!("dhost={}", cef_extension(&text(&event["hostname"]))),
+        format!("msg={}", cef_extension(&text(&event["message"]))),
+    ];
+    for (k, v) in fields(event) {
+        if !["timestamp", "hostname", "message", "service", "status"].contains(&k.as_str()) {
+            extension.push(format!("{}={}", k, cef_extension(&text(&v))));
+        }
+    }
+
+    return format!(
+        "CEF:0|dynamo|dynamo|{}|{}|{}|{}|{}",
+        env!("CARGO_PKG_VERSION"),
+        cef_header(service),
+        cef_header(&format!("{} event", service)),
+        cef_severity(event["status"].as_str().unwrap_or("INFO")),
+        extension.join(" "),
+    );
+}
+
+fn line(encoding: Encoding, event: &serde_json::Value) -> String {
+    return match encoding {
+        Encoding::Json => event.to_string(),
+        Encoding::Logfmt => logfmt(event),
+        Encoding::Raw => text(&event["message"]),
+        Encoding::Cef => cef(event),
+        Encoding::JsonArray | Encoding::Protobuf | Encoding::Avro => {
+            unreachable!("{:?} is not a line encoding", encoding)
+        }
+    };
+}
+
+/// Encodes a batch, returning the body and the share of it that each event
+/// accounts for.
+pub fn batch(encoding: Encoding, events: &[serde_json::Value]) -> (Vec<u64>, Vec<u8>) {
+    if encoding == Encoding::JsonArray {
+        let encoded: Vec<String> = events.iter().map(|v| v.to_string()).collect();
+        let sizes = encoded.iter().map(|e| e.len() as u64 + 1).collect();
+        return (sizes, format!("[{}]", encoded.join(",")).into_bytes());
+    }
+    if encoding == Encoding::Protobuf {
+        let sizes = events.iter().map(|v| v.to_string().len() as u64).collect();
+        return (sizes, otlp::export_request(events));
+    }
+    if encoding == Encoding::Avro {
+        let sizes = events.iter().map(|v| avro::datum(v).len() as u64).collect();
+        return (sizes, avro::container(events));
+    }
+
+    let mut sizes = vec![];
+    let mut body = String::new();
+    for event in events {
+        let line = line(encoding, event);
+        sizes.push(line.len() as u64 + 1);
+        body.push_str(&line);
+        body.push('\n');
+    }
+    return (sizes, body.into_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn logfmt_quotes_values_that_need_it() {
+        let event = json!({
+            "message": "GET /a \"b\" 200",
+            "service": "storedog",
+            "timestamp": 1,
+            "flag": { "key": "k=v", "current": true },
+        });
+        assert_eq!(
+            super::logfmt(&event),
+            r#"timestamp=1 service=storedog flag.current=true flag.key="k=v" message="GET /a \"b\" 200""#
+        );
+    }
+
+    #[test]
+    fn cef_escapes_header_and_extension() {
+        let event = json!({
+            "message": "a=b\\c",
+            "service": "odd|service",
+            "hostname": "web-1",
+            "status": "ERROR",
+            "timestamp": 1,
+        });
+        assert_eq!(
+            super::cef(&event),
+            format!(
+                r"CEF:0|dynamo|dynamo|{}|odd\|service|odd\|service event|8|rt=1 dhost=web-1 msg=a\=b\\c",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+    }
+}
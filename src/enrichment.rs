@@ -0,0 +1,64 @@
+/*!
+CSV lookup tables for Vector's `enrichment_tables`, generated from the
+simulated fleet so lessons always have reference data that matches the logs.
+
+ - `ip_to_host.csv` maps private IPs, as seen in flow logs, to hostnames;
+ - `host_to_team.csv` maps hostnames to the team that operates them; and
+ - `service_to_owner.csv` maps every `service` dynamo emits to its owner.
+*/
+use std::path::Path;
+
+use crate::hosts;
+
+fn csv(header: &str, rows: Vec<String>) -> String {
+    let mut contents = format!("{}\n", header);
+    for row in rows {
+        contents.push_str(&row);
+        contents.push('\n');
+    }
+    return contents;
+}
+
+fn tables() -> Vec<(&'static str, String)> {
+    return vec![
+        (
+            "ip_to_host.csv",
+            csv(
+                "ip,hostname",
+                hosts::all()
+                    .map(|h| format!("{},{}", h.ip, h.hostname))
+                    .collect(),
+            ),
+        ),
+        (
+            "host_to_team.csv",
+            csv(
+                "hostname,team",
+                hosts::all()
+                    .map(|h| format!("{},{}", h.hostname, h.team))
+                    .collect(),
+            ),
+        ),
+        (
+            "service_to_owner.csv",
+            csv(
+                "service,owner",
+                hosts::OWNERS
+                    .iter()
+                    .map(|(service, owner)| format!("{},{}", service, owner))
+                    .collect(),
+            ),
+        ),
+    ];
+}
+
+pub fn export(dir: &str) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir, e))?;
+    for (name, contents) in tables() {
+        let path = Path::new(dir).join(name);
+        std::fs::write(&path, contents)
+            .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        println!("Wrote {}", path.display());
+    }
+    return Ok(());
+}
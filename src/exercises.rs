@@ -0,0 +1,477 @@
+/*!
+Parsing katas for Vector Remap Language.
+
+`dynamo exercises` writes two files for each enabled generator: a sample
+event, exactly as dynamo would send it, and a starter VRL program whose
+assertions fail until the sample's message is parsed into `.parsed`. Students
+fill in the program and run it against the sample with `vector vrl`, then use
+it in a `remap` transform against the live stream.
+
+The expected fields follow the same formats `validate` checks. For messages
+that are JSON or CSV, they are the sample's own keys or the export's columns.
+Generators that only send events during an incident, such as the mining
+flows, get no kata.
+*/
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::{billing, Generator};
+
+/// How many times to run a generator looking for a sample.
+const ATTEMPTS: usize = 100;
+/// Most keys of a JSON message to assert on.
+const JSON_KEYS: usize = 8;
+
+/// What a solution should produce: a hint at the VRL functions to reach for,
+/// and assertions as conditions with what they expect.
+struct Kata {
+    hint: &'static str,
+    checks: Vec<(String, String)>,
+}
+
+fn checks(checks: &[(&str, &str)]) -> Vec<(String, String)> {
+    return checks
+        .iter()
+        .map(|(condition, expects)| (condition.to_string(), expects.to_string()))
+        .collect();
+}
+
+/// A path under `.parsed`, quoted if the key isn't a plain identifier.
+fn parsed(key: &str) -> String {
+    if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return format!(".parsed.{}", key);
+    }
+    return format!(".parsed.{:?}", key);
+}
+
+/// Decodes a name in DNS wire format, such as `(4)cart(8)storedog(0)`.
+fn wire_name(name: &str) -> String {
+    return name
+        .split(['(', ')'])
+        .enumerate()
+        .filter(|(i, label)| i % 2 == 0 && !label.is_empty())
+        .map(|(_, label)| label)
+        .collect::<Vec<_>>()
+        .join(".");
+}
+
+fn kata(service: &str, message: &str) -> Kata {
+    return match service {
+        "storedog" if message.starts_with("ERROR") => Kata {
+            hint: "parse_regex, redact",
+            checks: checks(&[
+                (
+                    r"!match(string!(.message), r'\d{15,16}')",
+                    "the card number masked in .message",
+                ),
+                (
+                    "is_string(.parsed.card_last4)",
+                    "the card's last 4 digits in .parsed.card_last4",
+                ),
+            ]),
+        },
+        "storedog" => Kata {
+            hint: "parse_common_log",
+            checks: checks(&[
+                (
+                    "is_string(.parsed.host)",
+                    "the client address in .parsed.host",
+                ),
+                (
+                    "is_timestamp(.parsed.timestamp)",
+                    "the request time in .parsed.timestamp",
+                ),
+                (
+                    "includes([\"GET\", \"POST\"], .parsed.method)",
+                    "the method in .parsed.method",
+                ),
+                ("is_string(.parsed.path)", "the path in .parsed.path"),
+                (
+                    "is_integer(.parsed.status)",
+                    "the status code in .parsed.status",
+                ),
+                (
+                    "is_integer(.parsed.size)",
+                    "the response size in .parsed.size",
+                ),
+            ]),
+        },
+        "storedog-orders" => Kata {
+            hint: "parse_key_value, to_float",
+            checks: checks(&[
+                (
+                    r#"includes(["order_placed", "payment_captured", "refund_issued"], .parsed.event)"#,
+                    "the event name in .parsed.event",
+                ),
+                (
+                    "is_string(.parsed.order_id)",
+                    "the order ID in .parsed.order_id",
+                ),
+                (
+                    "is_string(.parsed.customer)",
+                    "the customer in .parsed.customer",
+                ),
+                (
+                    "is_float(.parsed.amount)",
+                    "the amount as a float in .parsed.amount",
+                ),
+                (
+                    "is_string(.parsed.currency)",
+                    "the currency in .parsed.currency",
+                ),
+            ]),
+        },
+        s if s.starts_with("storedog-") => Kata {
+            hint: "parse_regex, parse_timestamp",
+            checks: checks(&[
+                (
+                    "is_timestamp(.parsed.timestamp)",
+                    "the log time in .parsed.timestamp",
+                ),
+                (
+                    "includes([\"DEBUG\", \"INFO\", \"WARN\", \"ERROR\"], .parsed.level)",
+                    "the level in .parsed.level",
+                ),
+                (
+                    "is_string(.parsed.component)",
+                    "the bracketed service in .parsed.component",
+                ),
+                (
+                    "is_string(.parsed.msg)",
+                    "the rest of the line in .parsed.msg",
+                ),
+            ]),
+        },
+        "aws.vpc_flow_logs" => Kata {
+            hint: "parse_aws_vpc_flow_log",
+            checks: checks(&[
+                (
+                    "is_string(.parsed.srcaddr)",
+                    "the source address in .parsed.srcaddr",
+                ),
+                (
+                    "is_string(.parsed.dstaddr)",
+                    "the destination address in .parsed.dstaddr",
+                ),
+                (
+                    "is_integer(.parsed.srcport)",
+                    "the source port in .parsed.srcport",
+                ),
+                (
+                    "is_integer(.parsed.dstport)",
+                    "the destination port in .parsed.dstport",
+                ),
+                (
+                    "includes([\"ACCEPT\", \"REJECT\"], .parsed.action)",
+                    "the action in .parsed.action",
+                ),
+            ]),
+        },
+        "pos-terminal" => Kata {
+            hint: "parse_regex, to_float; every store delimits fields its own way",
+            checks: checks(&[
+                (
+                    "is_timestamp(.parsed.timestamp)",
+                    "the sale time in .parsed.timestamp",
+                ),
+                ("is_string(.parsed.store)", "the store in .parsed.store"),
+                (
+                    "is_string(.parsed.terminal)",
+                    "the terminal in .parsed.terminal",
+                ),
+                (
+                    "includes([\"CASH\", \"CARD\", \"GIFT\"], .parsed.tender)",
+                    "the tender in .parsed.tender",
+                ),
+                (
+                    "is_float(.parsed.amount)",
+                    "the amount as a float in .parsed.amount",
+                ),
+                (
+                    "includes([\"APPROVED\", \"DECLINED\", \"VOID\"], .parsed.result)",
+                    "the result in .parsed.result",
+                ),
+            ]),
+        },
+        "payment-gateway" => Kata {
+            hint: "parse_xml",
+            checks: checks(&[
+                (
+                    "is_object(.parsed.GatewayAccess)",
+                    "the document in .parsed",
+                ),
+                (
+                    "is_string(.parsed.GatewayAccess.\"@timestamp\")",
+                    "attributes prefixed with @",
+                ),
+            ]),
+        },
+        "windows-dns" => {
+            let query = wire_name(message.rsplit(' ').next().unwrap_or_default());
+            Kata {
+                hint: "parse_regex, replace",
+                checks: vec![
+                    (
+                        "includes([\"Snd\", \"Rcv\"], .parsed.direction)".to_owned(),
+                        "the direction in .parsed.direction".to_owned(),
+                    ),
+                    (
+                        "is_string(.parsed.client_ip)".to_owned(),
+                        "the remote address in .parsed.client_ip".to_owned(),
+                    ),
+                    (
+                        "includes([\"NOERROR\", \"NXDOMAIN\", \"SERVFAIL\"], .parsed.rcode)"
+                            .to_owned(),
+                        "the response code in .parsed.rcode".to_owned(),
+                    ),
+                    (
+                        format!(".parsed.query == {:?}", query),
+                        "the decoded name in .parsed.query".to_owned(),
+                    ),
+                ],
+            }
+        }
+        "vsftpd" => Kata {
+            hint: "parse_regex, parse_timestamp, to_int",
+            checks: checks(&[
+                (
+                    "is_timestamp(.parsed.timestamp)",
+                    "the transfer time in .parsed.timestamp",
+                ),
+                (
+                    "is_string(.parsed.remote_host)",
+                    "the client in .parsed.remote_host",
+                ),
+                ("is_integer(.parsed.bytes)", "the size in .parsed.bytes"),
+                (
+                    "is_string(.parsed.filename)",
+                    "the file in .parsed.filename",
+                ),
+                (
+                    "includes([\"o\", \"i\", \"d\"], .parsed.direction)",
+                    "the direction in .parsed.direction",
+                ),
+                (
+                    "is_string(.parsed.username)",
+                    "the user in .parsed.username",
+                ),
+            ]),
+        },
+        "openvpn" => Kata {
+            hint: "parse_regex, parse_timestamp",
+            checks: checks(&[
+                (
+                    "is_timestamp(.parsed.timestamp)",
+                    "the log time in .parsed.timestamp",
+                ),
+                (
+                    "is_string(.parsed.client_ip)",
+                    "the peer's address in .parsed.client_ip",
+                ),
+                (
+                    r#"includes(["connected", "assigned", "disconnected", "auth_failed"], .parsed.event)"#,
+                    "what happened in .parsed.event",
+                ),
+            ]),
+        },
+        "macos" => Kata {
+            hint: "parse_regex, parse_timestamp, to_int",
+            checks: checks(&[
+                (
+                    "is_timestamp(.parsed.timestamp)",
+                    "the entry time in .parsed.timestamp",
+                ),
+                (
+                    r#"includes(["Default", "Info", "Debug", "Error", "Fault"], .parsed.type)"#,
+                    "the entry type in .parsed.type",
+                ),
+                ("is_integer(.parsed.pid)", "the PID in .parsed.pid"),
+                (
+                    "is_string(.parsed.process)",
+                    "the process in .parsed.process",
+                ),
+                ("is_string(.parsed.msg)", "the entry itself in .parsed.msg"),
+            ]),
+        },
+        "cron" => Kata {
+            hint: "parse_regex, to_int",
+            checks: checks(&[
+                ("is_integer(.parsed.pid)", "the PID in .parsed.pid"),
+                ("is_string(.parsed.user)", "the user in .parsed.user"),
+                (
+                    "is_string(.parsed.command)",
+                    "the command in .parsed.command",
+                ),
+            ]),
+        },
+        "logrotate" => Kata {
+            hint: "parse_regex, to_int",
+            checks: checks(&[
+                (
+                    "is_string(.parsed.process)",
+                    "the process in .parsed.process",
+                ),
+                ("is_integer(.parsed.pid)", "the PID in .parsed.pid"),
+                (
+                    "is_string(.parsed.msg)",
+                    "the rest of the line in .parsed.msg",
+                ),
+            ]),
+        },
+        "aws.billing_export" => Kata {
+            hint: "parse_csv, then name each column after the export's header",
+            checks: billing::CSV_COLUMNS
+                .iter()
+                .map(|column| {
+                    (
+                        format!("exists({})", parsed(column)),
+                        format!("the {} column", column),
+                    )
+                })
+                .collect(),
+        },
+        _ => match serde_json::from_str::<serde_json::Value>(message) {
+            Ok(serde_json::Value::Object(record)) => Kata {
+                hint: "parse_json",
+                checks: record
+                    .keys()
+                    .take(JSON_KEYS)
+                    .map(|key| {
+                        (
+                            format!("exists({})", parsed(key)),
+                            format!("the {} key", key),
+                        )
+                    })
+                    .collect(),
+            },
+            _ => Kata {
+                hint: "parse_regex",
+                checks: checks(&[("is_object(.parsed)", "the message's fields in .parsed")]),
+            },
+        },
+    };
+}
+
+/// The starter program for `generator`, given its sample event.
+fn program(generator: &str, event: &serde_json::Value) -> String {
+    let service = event["service"].as_str().unwrap_or_default();
+    let message = event["message"].as_str().unwrap_or_default();
+    let kata = kata(service, message);
+
+    let mut program = String::new();
+    let _ = writeln!(
+        program,
+        "# Parsing kata for dynamo's {} generator (service `{}`).",
+        generator, service
+    );
+    let _ = writeln!(program, "#");
+    let _ = writeln!(
+        program,
+        "# Parse .message into .parsed until every assertion below passes:"
+    );
+    let _ = writeln!(program, "#");
+    let _ = writeln!(
+        program,
+        "#   vector vrl --input {0}.json --program {0}.vrl --print-object",
+        generator
+    );
+    let _ = writeln!(program, "#");
+    let _ = writeln!(program, "# The sample's message:");
+    let _ = writeln!(program, "#");
+    for line in message.lines() {
+        let _ = writeln!(program, "#   {}", line);
+    }
+    let _ = writeln!(program, "#");
+    let _ = writeln!(program, "# Hint: {}", kata.hint);
+    let _ = writeln!(program);
+    let _ = writeln!(program, ".parsed = {{}}");
+    let _ = writeln!(program);
+    for (condition, expects) in kata.checks {
+        let _ = writeln!(
+            program,
+            "assert!({}, message: {:?})",
+            condition,
+            format!("expected {}", expects)
+        );
+    }
+    return program;
+}
+
+/// A generated event to use as the sample: not a header row or directive.
+fn sample(generator: &'static str, f: Generator) -> Option<serde_json::Value> {
+    let header = billing::CSV_COLUMNS.join(",");
+    for _ in 0..ATTEMPTS {
+        let events = match f() {
+            serde_json::Value::Array(events) => events,
+            event => vec![event],
+        };
+        let event = events.into_iter().find(|e| {
+            let message = e["message"].as_str().unwrap_or_default();
+            return !message.starts_with('#') && message != header;
+        });
+        if let Some(mut event) = event {
+            crate::prepare(generator, &mut event);
+            return Some(event);
+        }
+    }
+    return None;
+}
+
+pub fn write(dir: &str, sources: &[(&'static str, usize, Generator)]) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir, e))?;
+    for (generator, rate, f) in sources.iter() {
+        if *rate == 0 {
+            continue;
+        }
+        let Some(event) = sample(generator, *f) else {
+            println!(
+                "Skipped {}: it only sends events during its incident",
+                generator
+            );
+            continue;
+        };
+        let files = [
+            (format!("{}.json", generator), format!("{}\n", event)),
+            (format!("{}.vrl", generator), program(generator, &event)),
+        ];
+        for (name, contents) in files {
+            let path = Path::new(dir).join(name);
+            std::fs::write(&path, contents)
+                .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+            println!("Wrote {}", path.display());
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{falco, random, storedog};
+
+    #[test]
+    fn decodes_wire_names() {
+        assert_eq!(
+            super::wire_name("(4)cart(8)storedog(8)internal(0)"),
+            "cart.storedog.internal"
+        );
+    }
+
+    #[test]
+    fn quotes_paths() {
+        assert_eq!(super::parsed("uid"), ".parsed.uid");
+        assert_eq!(super::parsed("id.orig_h"), ".parsed.\"id.orig_h\"");
+    }
+
+    #[test]
+    fn programs_assert_on_the_sample() {
+        random::reseed(7);
+        let event = super::sample(falco::GENERATOR, falco::generate).unwrap();
+        let program = super::program(falco::GENERATOR, &event);
+        assert!(program.contains("assert!(exists(.parsed.rule)"));
+
+        let event = super::sample("storedog-error", storedog::error).unwrap();
+        let program = super::program("storedog-error", &event);
+        assert!(program.contains("assert!(is_integer(.parsed.status)"));
+        assert!(program.contains(event["message"].as_str().unwrap()));
+    }
+}
@@ -0,0 +1,179 @@
+/*!
+Classification of failed sends.
+
+"Could not connect" covers a mistyped hostname, Vector not running, a source
+listening on another port and Vector refusing the payload, and each needs a
+different fix. Every failed send is classified, counted per class for the
+stats and the summary printed on exit, and reported with a hint at the cause.
+*/
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{self, json};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Class {
+    /// The target's hostname didn't resolve.
+    Dns,
+    /// Nothing is listening at the target.
+    Refused,
+    Timeout,
+    /// The target rejected the request with a 4xx status.
+    Rejected,
+    /// The target failed with a 5xx status.
+    ServerError,
+    Other,
+}
+
+const CLASSES: [Class; 6] = [
+    Class::Dns,
+    Class::Refused,
+    Class::Timeout,
+    Class::Rejected,
+    Class::ServerError,
+    Class::Other,
+];
+
+static COUNTS: [AtomicU64; 6] = [const { AtomicU64::new(0) }; 6];
+
+impl Class {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Class::Dns => "dns",
+            Class::Refused => "connection_refused",
+            Class::Timeout => "timeout",
+            Class::Rejected => "http_4xx",
+            Class::ServerError => "http_5xx",
+            Class::Other => "other",
+        };
+    }
+}
+
+pub struct Failure {
+    pub class: Class,
+    /// Where the send was going.
+    pub target: String,
+    pub detail: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hint = match self.class {
+            Class::Dns => "its hostname does not resolve",
+            Class::Refused => {
+                "nothing is listening there; check that Vector is running and the source's address"
+            }
+            Class::Timeout => "the request timed out",
+            Class::Rejected => {
+                "it rejected the batch; check the source's settings, such as its encoding"
+            }
+            Class::ServerError => "it failed to accept the batch; check its logs",
+            Class::Other => "the request failed",
+        };
+        return write!(
+            f,
+            "Could not send to {}: {} ({})",
+            self.target, hint, self.detail
+        );
+    }
+}
+
+/// Classifies a request that got no response.
+pub fn request(target: &str, e: &reqwest::Error) -> Failure {
+    let mut class = if e.is_timeout() {
+        Class::Timeout
+    } else {
+        Class::Other
+    };
+    let mut source: Option<&dyn Error> = Some(e);
+    while let (Class::Other, Some(error)) = (class, source) {
+        if let Some(io) = error.downcast_ref::<std::io::Error>() {
+            class = match io.kind() {
+                std::io::ErrorKind::ConnectionRefused => Class::Refused,
+                std::io::ErrorKind::TimedOut => Class::Timeout,
+                _ => Class::Other,
+            };
+        }
+        if error.to_string().starts_with("dns error") {
+            class = Class::Dns;
+        }
+        source = error.source();
+    }
+
+    return Failure {
+        class,
+        target: target.to_owned(),
+        detail: e.to_string(),
+    };
+}
+
+/// Classifies a request answered with an unsuccessful status.
+pub fn status(target: &str, status: reqwest::StatusCode) -> Failure {
+    return Failure {
+        class: if status.is_client_error() {
+            Class::Rejected
+        } else {
+            Class::ServerError
+        },
+        target: target.to_owned(),
+        detail: format!("HTTP status {}", status),
+    };
+}
+
+pub fn other(target: &str, detail: impl fmt::Display) -> Failure {
+    return Failure {
+        class: Class::Other,
+        target: target.to_owned(),
+        detail: detail.to_string(),
+    };
+}
+
+pub fn record(failure: &Failure) {
+    let i = CLASSES
+        .iter()
+        .position(|c| *c == failure.class)
+        .expect("every class should be counted");
+    COUNTS[i].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Failed sends so far, by class.
+pub fn summary() -> serde_json::Value {
+    let mut counts = serde_json::Map::new();
+    for (class, count) in CLASSES.iter().zip(COUNTS.iter()) {
+        counts.insert(
+            class.name().to_owned(),
+            json!(count.load(Ordering::Relaxed)),
+        );
+    }
+    return serde_json::Value::Object(counts);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Class;
+
+    #[tokio::test]
+    async fn classifies_connection_failures() {
+        // Bind and drop a listener to find a port nothing is listening on.
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let target = format!("http://127.0.0.1:{}", port);
+        let e = reqwest::get(&target).await.unwrap_err();
+        assert_eq!(super::request(&target, &e).class, Class::Refused);
+
+        let target = "http://does-not-exist.invalid";
+        let e = reqwest::get(target).await.unwrap_err();
+        assert_eq!(super::request(target, &e).class, Class::Dns);
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        let status = |code| super::status("vector", reqwest::StatusCode::from_u16(code).unwrap());
+        assert_eq!(status(413).class, Class::Rejected);
+        assert_eq!(status(503).class, Class::ServerError);
+    }
+}
@@ -0,0 +1,285 @@
+/*!
+Process and network events from an eBPF agent on the Linux fleet.
+
+Each event is an alert as Falco writes it with `json_output`: the rule, its
+priority, the formatted output and the fields it was built from, serialized as
+JSON in the message like the billing line items. Most are audit rules for
+process executions and outbound connections: cron jobs, logrotate, health
+checks, services calling each other and resolving names.
+
+The host-side attacks show up here too. While the crypto mining incident is
+active, the compromised host starts its miner and connects to the mining
+pool, which Falco's crypto mining rules flag. During the DGA incident the
+dropped binary on the same host connects to the DNS server, which only an
+audit rule records, so students have to notice the binary's path.
+*/
+use serde_json::{self, json};
+
+use crate::hosts::{self, Host};
+use crate::{clock, dns, mining, random};
+
+pub const GENERATOR: &str = "falco";
+pub const SERVICE: &str = "falco";
+/// Share of events from the compromised host while an attack is active.
+const ATTACK_SHARE: f64 = 0.3;
+/// The card processor's API, which storedog-payments calls.
+const PROCESSOR_IP: &str = "54.187.174.169";
+
+struct Process {
+    name: &'static str,
+    exepath: &'static str,
+    cmdline: &'static str,
+    user: &'static str,
+    parent: &'static str,
+}
+
+/// Processes started in normal operation, on any Linux host.
+const EXECS: [Process; 5] = [
+    Process {
+        name: "run-parts",
+        exepath: "/usr/bin/run-parts",
+        cmdline: "run-parts --report /etc/cron.hourly",
+        user: "root",
+        parent: "cron",
+    },
+    Process {
+        name: "logrotate",
+        exepath: "/usr/sbin/logrotate",
+        cmdline: "logrotate /etc/logrotate.conf",
+        user: "root",
+        parent: "systemd",
+    },
+    Process {
+        name: "curl",
+        exepath: "/usr/bin/curl",
+        cmdline: "curl -fsS http://localhost:3000/healthz",
+        user: "storedog",
+        parent: "sh",
+    },
+    Process {
+        name: "cleanup-sessions",
+        exepath: "/opt/storedog/bin/cleanup-sessions",
+        cmdline: "cleanup-sessions",
+        user: "storedog",
+        parent: "sh",
+    },
+    Process {
+        name: "sshd",
+        exepath: "/usr/sbin/sshd",
+        cmdline: "sshd: deploy [priv]",
+        user: "root",
+        parent: "sshd",
+    },
+];
+
+const RESOLVER: Process = Process {
+    name: "systemd-resolve",
+    exepath: "/lib/systemd/systemd-resolved",
+    cmdline: "systemd-resolved",
+    user: "systemd-resolve",
+    parent: "systemd",
+};
+
+const PUMA: Process = Process {
+    name: "ruby",
+    exepath: "/usr/local/bin/ruby",
+    cmdline: "puma: cluster worker 0: 1 [storedog]",
+    user: "storedog",
+    parent: "ruby",
+};
+
+const MINER: Process = Process {
+    name: "kswapd0",
+    exepath: mining::MINER_PATH,
+    cmdline: "kswapd0 -o stratum+tcp://45.9.148.125:3333 -u 4AdUndXHHZ6cfufTMvppY6JwXNouMBzSkbLYfpAV5Usx3skxNgYeYTRj5UzqtReoS44qo9mtmXCqY45DJ852K5Jv2684Rge --donate-level 1 -B",
+    user: "storedog",
+    parent: "sh",
+};
+
+const DGA: Process = Process {
+    name: ".updater",
+    exepath: dns::DGA_PROCESS_PATH,
+    cmdline: ".updater",
+    user: "storedog",
+    parent: "systemd",
+};
+
+fn linux_host() -> &'static Host {
+    let linux: Vec<&Host> = hosts::all()
+        .filter(|h| h.platform == hosts::Platform::Linux)
+        .collect();
+    let host: &&Host = random::pick(&linux);
+    return host;
+}
+
+fn status(priority: &str) -> &'static str {
+    return match priority {
+        "Emergency" | "Alert" | "Critical" | "Error" => "ERROR",
+        "Warning" | "Notice" => "WARNING",
+        _ => "INFO",
+    };
+}
+
+/// An alert for `process` on `host`. `output` follows Falco's convention of
+/// a description and the key fields in parentheses.
+fn alert(
+    host: &Host,
+    rule: &str,
+    priority: &str,
+    process: &Process,
+    output: String,
+    mut fields: serde_json::Value,
+) -> serde_json::Value {
+    let now = clock::now();
+    fields["evt.time"] = json!(now.timestamp_nanos());
+    fields["proc.name"] = json!(process.name);
+    fields["proc.exepath"] = json!(process.exepath);
+    fields["proc.cmdline"] = json!(process.cmdline);
+    fields["proc.pid"] = json!(random::range(1000..60000));
+    fields["proc.pname"] = json!(process.parent);
+    fields["user.name"] = json!(process.user);
+    fields["container.id"] = json!("host");
+
+    let record = json!({
+        "hostname": host.hostname,
+        "output": format!("{}: {} {}", now.format("%H:%M:%S%.9f"), priority, output),
+        "output_fields": fields,
+        "priority": priority,
+        "rule": rule,
+        "source": "syscall",
+        "tags": if priority == "Informational" { json!(["audit"]) } else { json!(["mitre_execution"]) },
+        "time": now.format("%Y-%m-%dT%H:%M:%S%.9fZ").to_string(),
+    });
+    return json!({
+        "message": record.to_string(),
+        "service": SERVICE,
+        "hostname": host.hostname,
+        "status": status(priority),
+    });
+}
+
+fn exec(host: &Host, rule: &str, priority: &str, process: &Process) -> serde_json::Value {
+    let output = format!(
+        "Process started (user={} process={} exepath={} parent={} command={} container_id=host)",
+        process.user, process.name, process.exepath, process.parent, process.cmdline
+    );
+    return alert(
+        host,
+        rule,
+        priority,
+        process,
+        output,
+        json!({ "evt.type": "execve" }),
+    );
+}
+
+fn connect(
+    host: &Host,
+    rule: &str,
+    priority: &str,
+    process: &Process,
+    (ip, port, proto): (&str, usize, &str),
+) -> serde_json::Value {
+    let connection = format!(
+        "{}:{}->{}:{}",
+        host.ip,
+        random::range(32768..61000),
+        ip,
+        port
+    );
+    let output = format!(
+        "Outbound connection (user={} process={} exepath={} connection={} proto={} container_id=host)",
+        process.user, process.name, process.exepath, connection, proto
+    );
+    return alert(
+        host,
+        rule,
+        priority,
+        process,
+        output,
+        json!({
+            "evt.type": "connect",
+            "fd.name": connection,
+            "fd.l4proto": proto,
+            "fd.sip": ip,
+            "fd.sport": port,
+        }),
+    );
+}
+
+fn attack() -> Option<serde_json::Value> {
+    let host = &hosts::COMPROMISED;
+    let (mining, dga) = (mining::active(), dns::INCIDENT.active());
+    if !(mining || dga) || !random::chance(ATTACK_SHARE) {
+        return None;
+    }
+
+    if mining && (!dga || random::chance(0.5)) {
+        // The miner is restarted now and then, and otherwise keeps talking to
+        // the pool.
+        if random::chance(0.2) {
+            return Some(exec(
+                host,
+                "Detect crypto miners using the Stratum protocol",
+                "Critical",
+                &MINER,
+            ));
+        }
+        return Some(connect(
+            host,
+            "Detect outbound connections to common miner pool ports",
+            "Critical",
+            &MINER,
+            (mining::POOL_IP, mining::POOL_PORT, "tcp"),
+        ));
+    }
+    return Some(connect(
+        host,
+        "Network Connect",
+        "Informational",
+        &DGA,
+        (hosts::DNS.ip, 53, "udp"),
+    ));
+}
+
+pub fn generate() -> serde_json::Value {
+    if let Some(event) = attack() {
+        return event;
+    }
+
+    let roll: f64 = random::range(0.0..1.0);
+    if roll < 0.4 {
+        return exec(
+            linux_host(),
+            "Process Exec",
+            "Informational",
+            random::pick(&EXECS),
+        );
+    }
+    if roll < 0.7 {
+        return connect(
+            linux_host(),
+            "Network Connect",
+            "Informational",
+            &RESOLVER,
+            (hosts::DNS.ip, 53, "udp"),
+        );
+    }
+    if roll < 0.9 {
+        let backend = random::pick(&hosts::BACKEND);
+        return connect(
+            hosts::web(),
+            "Network Connect",
+            "Informational",
+            &PUMA,
+            (backend.ip, 3000, "tcp"),
+        );
+    }
+    return connect(
+        hosts::backend("storedog-payments"),
+        "Network Connect",
+        "Informational",
+        &PUMA,
+        (PROCESSOR_IP, 443, "tcp"),
+    );
+}
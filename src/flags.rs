@@ -0,0 +1,90 @@
+/*!
+Feature flag audit events.
+
+Flag changes are the root cause of the latency regression storyline: the
+`storedog.checkout-v2` flag is enabled at a configured point in the run, and
+every storedog request served while it is on is dramatically slower. The audit
+event always arrives before the first slow request so students can work
+backwards from the symptom to the change that caused it.
+*/
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serde_json::{self, json};
+use tokio::sync::mpsc::Sender;
+
+use crate::sink::Event;
+use crate::{answer_key, cluster, random, webhook};
+
+pub const CHECKOUT_V2: &str = "storedog.checkout-v2";
+/// The trigger scenario that flips the flag.
+const REGRESSION_SCENARIO: &str = "latency-regression";
+
+static CHECKOUT_V2_ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn checkout_v2_enabled() -> bool {
+    return CHECKOUT_V2_ENABLED.load(Ordering::Relaxed);
+}
+
+/// Flips the flag without emitting an audit event, for rendering golden
+/// outputs.
+pub fn force_checkout_v2(enabled: bool) {
+    CHECKOUT_V2_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn flag_change_event(key: &str, enabled: bool) -> serde_json::Value {
+    let actor = random::username();
+    let verb = if enabled { "enabled" } else { "disabled" };
+
+    return json!({
+        "message": format!("Flag {} {} by {} for 100% of traffic", key, verb, actor),
+        "service": "feature-flags",
+        "flag": {
+            "key": key,
+            "previous": !enabled,
+            "current": enabled,
+        },
+        "actor": actor,
+    });
+}
+
+pub async fn set_checkout_v2(tx: &Sender<Event>, enabled: bool) -> bool {
+    // The audit event is queued before behavior changes, so it is always
+    // delivered ahead of the requests it affects.
+    let event = flag_change_event(CHECKOUT_V2, enabled);
+    let sent = crate::emit(tx, "feature-flags", event).await;
+    apply_checkout_v2(enabled);
+    return sent;
+}
+
+/// Flips the flag and records the change, once its audit event is out.
+pub fn apply_checkout_v2(enabled: bool) {
+    CHECKOUT_V2_ENABLED.store(enabled, Ordering::Relaxed);
+
+    let field = if enabled { "enabled_at" } else { "disabled_at" };
+    let record = json!({ "flag": CHECKOUT_V2, (field): answer_key::now() });
+    answer_key::record(json!({ "feature_flag_regression": record.clone() }));
+    webhook::phase("feature_flag_regression", enabled, record);
+}
+
+/// Enables the regressing flag `at_s` seconds after startup, and rolls it back
+/// `rollback_after_s` seconds later. A zero `at_s` disables the storyline, and
+/// a zero `rollback_after_s` leaves the flag on for the rest of the run.
+pub fn schedule_regression(tx: &Sender<Event>, at_s: u64, rollback_after_s: u64) {
+    if at_s == 0 {
+        return;
+    }
+
+    let tx2 = tx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(at_s)).await;
+        cluster::forward(REGRESSION_SCENARIO, false);
+        if !set_checkout_v2(&tx2, true).await || rollback_after_s == 0 {
+            return;
+        }
+
+        tokio::time::sleep(Duration::from_secs(rollback_after_s)).await;
+        cluster::forward(REGRESSION_SCENARIO, true);
+        set_checkout_v2(&tx2, false).await;
+    });
+}
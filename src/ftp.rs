@@ -0,0 +1,113 @@
+/*!
+vsftpd transfer logs in the xferlog format.
+
+storedog exchanges files with its partners over FTP: logistics partners upload
+shipment manifests and download order exports, and accounting pulls reports.
+vsftpd logs one xferlog line per completed or aborted transfer.
+
+During the bulk download incident, one employee account signs in from outside
+the partner network and downloads the customer export piece by piece, carrying
+the data leak storyline into another log dialect.
+*/
+use serde_json::{self, json};
+
+use crate::incident::Incident;
+use crate::{clock, hosts, random};
+
+pub const SERVICE: &str = "vsftpd";
+/// Partner accounts and the addresses they connect from.
+const PARTNERS: [(&str, &str); 3] = [
+    ("acme-logistics", "198.51.100.23"),
+    ("fulfillment-co", "198.51.100.87"),
+    ("accounting", "10.0.5.12"),
+];
+const UPLOADS: [&str; 2] = ["/incoming/manifests/manifest", "/incoming/returns/returns"];
+const DOWNLOADS: [&str; 2] = ["/exports/orders/orders", "/reports/daily/sales"];
+pub const EXFIL_USER: &str = "mkowalski";
+pub const EXFIL_IP: &str = "185.220.101.47";
+pub const EXFIL_DIRECTORY: &str = "/exports/customers";
+/// Share of transfers that are the bulk download during the incident.
+const EXFIL_SHARE: f64 = 0.6;
+
+pub static INCIDENT: Incident = Incident::new("bulk_download", details);
+
+fn details() -> serde_json::Value {
+    return json!({
+        "username": EXFIL_USER,
+        "client_ip": EXFIL_IP,
+        "directory": EXFIL_DIRECTORY,
+        "server_hostname": hosts::FTP.hostname,
+    });
+}
+
+struct Transfer {
+    client_ip: &'static str,
+    bytes: u64,
+    filename: String,
+    /// `o` for downloads, `i` for uploads.
+    direction: char,
+    username: &'static str,
+    complete: bool,
+}
+
+fn partner_transfer() -> Transfer {
+    let (username, client_ip) = *random::pick(&PARTNERS);
+    let upload = username != "accounting" && random::chance(0.4);
+    let prefix = random::pick(if upload { &UPLOADS } else { &DOWNLOADS });
+    return Transfer {
+        client_ip,
+        bytes: random::range(2_000..2_000_000),
+        filename: format!("{}-{}.csv", prefix, clock::now().format("%Y%m%d")),
+        direction: if upload { 'i' } else { 'o' },
+        username,
+        complete: random::chance(0.97),
+    };
+}
+
+fn exfil_transfer() -> Transfer {
+    return Transfer {
+        client_ip: EXFIL_IP,
+        bytes: random::range(20_000_000..60_000_000),
+        filename: format!(
+            "{}/customers-part-{:04}.csv",
+            EXFIL_DIRECTORY,
+            random::range(1..=400)
+        ),
+        direction: 'o',
+        username: EXFIL_USER,
+        complete: true,
+    };
+}
+
+/// An xferlog line: time, transfer seconds, remote host, bytes, filename,
+/// type, action flag, direction, access mode, user, service, authentication
+/// method, authenticated user ID and completion status.
+fn line(transfer: &Transfer) -> String {
+    // Transfers are logged when they end, at between 5 and 50MB/s.
+    let seconds = (transfer.bytes / random::range(5_000_000..50_000_000)).max(1);
+    return format!(
+        "{} {} {} {} {} b _ {} r {} ftp 0 * {}",
+        clock::now().format("%a %b %e %H:%M:%S %Y"),
+        seconds,
+        transfer.client_ip,
+        transfer.bytes,
+        transfer.filename,
+        transfer.direction,
+        transfer.username,
+        if transfer.complete { 'c' } else { 'i' },
+    );
+}
+
+pub fn generate() -> serde_json::Value {
+    let transfer = if INCIDENT.active() && random::chance(EXFIL_SHARE) {
+        exfil_transfer()
+    } else {
+        partner_transfer()
+    };
+    return json!({
+        "message": line(&transfer),
+        "service": SERVICE,
+        "hostname": hosts::FTP.hostname,
+        "status": if transfer.complete { "INFO" } else { "WARNING" },
+    });
+}
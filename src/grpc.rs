@@ -0,0 +1,292 @@
+/*!
+The admin API over gRPC.
+
+Lab orchestrators controlling many instances can make typed calls instead of
+HTTP requests: `--admin-grpc-address` serves the `dynamo.admin.v1.Admin`
+service published in `proto/admin.proto`, over plaintext HTTP/2. Its methods
+mirror the admin API's routes, and triggers go through the same path, so
+they are forwarded to workers and recorded in the answer key the same way.
+
+Messages are never compressed, and requests asking for compression are
+refused.
+*/
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, HeaderMap, Method, Request, Response, Server};
+use tokio::sync::mpsc::Sender;
+
+use crate::proto::{self, Value};
+use crate::sink::Event;
+use crate::{stats, trigger};
+
+const SERVICE: &str = "dynamo.admin.v1.Admin";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Code {
+    Ok = 0,
+    InvalidArgument = 3,
+    Unimplemented = 12,
+}
+
+/// Fields of the `Counts` message, by the stats' names for them.
+const COUNTS: [(u32, &str); 5] = [
+    (1, "events_generated"),
+    (2, "events_sent"),
+    (3, "events_dropped"),
+    (4, "bytes_uncompressed"),
+    (5, "bytes_wire"),
+];
+
+fn counts(value: &serde_json::Value) -> Vec<u8> {
+    let mut buf = vec![];
+    for (field, name) in COUNTS {
+        proto::uint64(&mut buf, field, value[name].as_u64().unwrap_or_default());
+    }
+    return buf;
+}
+
+/// Encodes a map field, whose entries are messages with the key in field 1
+/// and the value in field 2.
+fn map_entry(buf: &mut Vec<u8>, field: u32, key: &str, value: impl Fn(&mut Vec<u8>)) {
+    let mut entry = vec![];
+    proto::string(&mut entry, 1, key);
+    value(&mut entry);
+    proto::message(buf, field, &entry);
+}
+
+fn get_stats() -> Vec<u8> {
+    let snapshot = stats::snapshot();
+    let mut buf = vec![];
+    for (name, generator) in snapshot["generators"].as_object().into_iter().flatten() {
+        map_entry(&mut buf, 1, name, |entry| {
+            proto::message(entry, 2, &counts(generator));
+        });
+    }
+    proto::message(&mut buf, 2, &counts(&snapshot["total"]));
+    for (class, count) in snapshot["send_failures"].as_object().into_iter().flatten() {
+        map_entry(&mut buf, 3, class, |entry| {
+            proto::uint64(entry, 2, count.as_u64().unwrap_or_default());
+        });
+    }
+    let queue = &snapshot["queue"];
+    let mut message = vec![];
+    proto::string(
+        &mut message,
+        1,
+        queue["policy"].as_str().unwrap_or_default(),
+    );
+    proto::uint64(&mut message, 2, queue["depth"].as_u64().unwrap_or_default());
+    proto::uint64(
+        &mut message,
+        3,
+        queue["dropped"].as_u64().unwrap_or_default(),
+    );
+    proto::message(&mut buf, 4, &message);
+    return buf;
+}
+
+fn list_scenarios() -> Vec<u8> {
+    let mut buf = vec![];
+    for scenario in trigger::SCENARIOS.iter() {
+        let mut message = vec![];
+        proto::string(&mut message, 1, scenario.name);
+        proto::string(&mut message, 2, &scenario.key.to_string());
+        proto::string(&mut message, 3, scenario.description);
+        proto::message(&mut buf, 1, &message);
+    }
+    return buf;
+}
+
+async fn fire(tx: &Sender<Event>, request: &[u8]) -> Result<Vec<u8>, (Code, String)> {
+    let invalid = |e: String| (Code::InvalidArgument, e);
+    let mut scenario = String::new();
+    let mut stop = false;
+    for (field, value) in proto::decode(request).map_err(invalid)? {
+        match (field, value) {
+            (1, Value::Bytes(bytes)) => {
+                scenario = String::from_utf8(bytes.to_vec())
+                    .map_err(|_| invalid("scenario is not valid UTF-8".to_owned()))?;
+            }
+            (2, Value::Varint(value)) => stop = value != 0,
+            _ => {}
+        }
+    }
+
+    let result = trigger::fire(tx, &scenario, stop).await.map_err(invalid)?;
+    let mut buf = vec![];
+    proto::string(&mut buf, 1, &result);
+    return Ok(buf);
+}
+
+/// Takes the single message out of a gRPC request body, which is prefixed
+/// with a compression flag and its length as a 4-byte big-endian integer.
+fn unframe(body: &[u8]) -> Result<&[u8], (Code, String)> {
+    let invalid = || (Code::InvalidArgument, "malformed gRPC message".to_owned());
+    if body.len() < 5 {
+        return Err(invalid());
+    }
+    if body[0] != 0 {
+        return Err((
+            Code::Unimplemented,
+            "compressed messages are not supported".to_owned(),
+        ));
+    }
+    let len = u32::from_be_bytes(body[1..5].try_into().expect("4 bytes were checked")) as usize;
+    return body[5..].get(..len).ok_or_else(invalid);
+}
+
+fn frame(message: &[u8]) -> Vec<u8> {
+    let mut buf = vec![0];
+    buf.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    buf.extend_from_slice(message);
+    return buf;
+}
+
+/// Percent-encodes a `grpc-message`, which may only hold printable ASCII.
+fn escape(message: &str) -> String {
+    let mut escaped = String::new();
+    for byte in message.bytes() {
+        if (b' '..=b'~').contains(&byte) && byte != b'%' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    return escaped;
+}
+
+fn status(code: Code, message: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("grpc-status", (code as u32).into());
+    if !message.is_empty() {
+        headers.insert(
+            "grpc-message",
+            escape(message)
+                .parse()
+                .expect("escaped messages should be valid headers"),
+        );
+    }
+    return headers;
+}
+
+fn respond(result: Result<Vec<u8>, (Code, String)>) -> Response<Body> {
+    let builder = Response::builder().header("content-type", "application/grpc");
+    let response = match result {
+        Ok(message) => {
+            let (mut sender, body) = Body::channel();
+            tokio::spawn(async move {
+                if sender.send_data(frame(&message).into()).await.is_ok() {
+                    let _ = sender.send_trailers(status(Code::Ok, "")).await;
+                }
+            });
+            builder.body(body)
+        }
+        // Failures are sent as a response with only trailers, in its headers.
+        Err((code, message)) => {
+            let mut response = builder.body(Body::empty());
+            if let Ok(response) = response.as_mut() {
+                response.headers_mut().extend(status(code, &message));
+            }
+            response
+        }
+    };
+    return response.expect("gRPC response should be valid");
+}
+
+async fn route(tx: Sender<Event>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let method = match req.uri().path().strip_prefix(&format!("/{}/", SERVICE)) {
+        Some(method) if req.method() == Method::POST => method.to_owned(),
+        _ => {
+            let path = req.uri().path().to_owned();
+            return Ok(respond(Err((
+                Code::Unimplemented,
+                format!("unknown method {}", path),
+            ))));
+        }
+    };
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return Ok(respond(Err((Code::InvalidArgument, e.to_string())))),
+    };
+    let request = match unframe(&body) {
+        Ok(request) => request,
+        Err(e) => return Ok(respond(Err(e))),
+    };
+
+    let result = match method.as_str() {
+        "GetStats" => Ok(get_stats()),
+        "ListScenarios" => Ok(list_scenarios()),
+        "Trigger" => fire(&tx, request).await,
+        _ => Err((Code::Unimplemented, format!("unknown method {}", method))),
+    };
+    return Ok(respond(result));
+}
+
+pub fn serve(address: SocketAddr, tx: &Sender<Event>) {
+    let tx = tx.clone();
+    let make_service = make_service_fn(move |_| {
+        let tx = tx.clone();
+        async move {
+            return Ok::<_, Infallible>(service_fn(move |req| route(tx.clone(), req)));
+        }
+    });
+    let server = match Server::try_bind(&address) {
+        Ok(builder) => builder.http2_only(true).serve(make_service),
+        Err(e) => {
+            println!("Could not start gRPC admin API on {}: {}", address, e);
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            println!("gRPC admin API stopped: {}", e);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Request};
+    use tokio::sync::mpsc;
+
+    use crate::proto::{self, Value};
+
+    async fn call(method: &str, message: &[u8]) -> (hyper::Response<Body>, Vec<u8>) {
+        let (tx, _rx) = mpsc::channel(1);
+        let req = Request::post(format!("/dynamo.admin.v1.Admin/{}", method))
+            .body(Body::from(super::frame(message)))
+            .unwrap();
+        let (parts, body) = super::route(tx, req).await.unwrap().into_parts();
+        let body = hyper::body::to_bytes(body).await.unwrap();
+        return (
+            hyper::Response::from_parts(parts, Body::empty()),
+            body.to_vec(),
+        );
+    }
+
+    #[tokio::test]
+    async fn lists_scenarios() {
+        let (_, body) = call("ListScenarios", &[]).await;
+        let scenarios = proto::decode(super::unframe(&body).unwrap()).unwrap();
+        assert_eq!(scenarios.len(), crate::trigger::SCENARIOS.len());
+        let Value::Bytes(first) = scenarios[0].1 else {
+            panic!("scenarios should be messages");
+        };
+        assert_eq!(
+            proto::decode(first).unwrap()[0],
+            (1, Value::Bytes(b"card-leak"))
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_scenarios() {
+        let mut request = vec![];
+        proto::string(&mut request, 1, "meteor-strike");
+        let (response, body) = call("Trigger", &request).await;
+        assert_eq!(response.headers()["grpc-status"], "3");
+        assert!(body.is_empty());
+    }
+}
@@ -0,0 +1,89 @@
+/*!
+Heartbeats with watermarks, for completeness checks.
+
+A sequence gap shows an event went missing, but not that the last few never
+arrived. With `--heartbeat-interval-s`, dynamo periodically emits one
+heartbeat per generator, with how many events the generator has emitted so far
+and the timestamp of the last of them: the watermark. Everything that
+generator sent up to the watermark is numbered up to the count, so a pipeline
+that has received every sequence number up to it has everything up to the
+watermark. `dynamo verify-sequence` uses the latest heartbeat it reads to
+count events lost after the last one received.
+*/
+use std::time::Duration;
+
+use chrono::prelude::*;
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+
+use crate::sink::Event;
+use crate::{cluster, sequence};
+
+pub const GENERATOR: &str = "heartbeats";
+pub const FIELD: &str = "dynamo_heartbeat";
+
+/// A watermark, in milliseconds since the epoch, as an RFC 3339 timestamp.
+pub fn watermark(ms: i64) -> String {
+    return Utc
+        .timestamp_millis_opt(ms)
+        .single()
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default();
+}
+
+fn heartbeat(generator: &str, count: u64, watermark_ms: i64) -> serde_json::Value {
+    return json!({
+        "message": format!(
+            "{} emitted {} events up to {}",
+            generator,
+            count,
+            watermark(watermark_ms)
+        ),
+        "service": "dynamo",
+        (FIELD): {
+            "generator": cluster::label(generator),
+            "count": count,
+            "watermark": watermark_ms,
+        },
+    });
+}
+
+/// Emits a heartbeat for every generator that has emitted events, every
+/// `interval_s` seconds. A zero interval disables heartbeats.
+pub fn start(tx: &Sender<Event>, interval_s: u64) {
+    if interval_s == 0 {
+        return;
+    }
+
+    let tx2 = tx.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_s));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            for (generator, count, watermark_ms) in sequence::snapshot() {
+                if generator == GENERATOR {
+                    continue;
+                }
+                let event = heartbeat(generator, count, watermark_ms);
+                if !crate::emit(&tx2, GENERATOR, event).await {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn heartbeats_carry_counts_and_watermarks() {
+        let event = super::heartbeat("vpc-accept", 42, 1_680_350_400_000);
+        assert_eq!(
+            event["message"],
+            "vpc-accept emitted 42 events up to 2023-04-01T12:00:00.000Z"
+        );
+        assert_eq!(event[super::FIELD]["count"], 42);
+        assert_eq!(event[super::FIELD]["watermark"], 1_680_350_400_000i64);
+    }
+}
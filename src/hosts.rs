@@ -0,0 +1,242 @@
+/*!
+The simulated storedog fleet.
+
+Every simulated host has a fixed private IP, and every generator that refers to
+a host uses the same one: the web servers that serve storedog's HTTP logs are
+the destinations of flow log traffic, the backend services log from their own
+hosts, and the host compromised in the crypto mining incident is part of the
+fleet too. An enrichment table mapping hostnames to IPs built from one stream
+therefore resolves in the others. The full mapping goes into the answer key.
+
+Hosts also have a platform. Most of the fleet runs Linux, the domain
+controller runs Windows under an upper-case NetBIOS-style name, and
+employees' laptops are MacBooks. Events from a fleet host carry its
+platform's `os` metadata.
+*/
+use serde_json::{self, json};
+
+use crate::{mining, random};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    Linux,
+    Windows,
+    MacOs,
+}
+
+impl Platform {
+    /// The `os` attributes of events from hosts on the platform.
+    pub fn metadata(&self) -> serde_json::Value {
+        return match self {
+            Platform::Linux => json!({
+                "type": "linux",
+                "name": "Ubuntu",
+                "version": "22.04.3 LTS (Jammy Jellyfish)",
+                "kernel": "5.15.0-91-generic",
+            }),
+            Platform::Windows => json!({
+                "type": "windows",
+                "name": "Windows Server 2022 Datacenter",
+                "version": "10.0.20348",
+                "kernel": "10.0.20348.2113",
+            }),
+            Platform::MacOs => json!({
+                "type": "macos",
+                "name": "macOS",
+                "version": "14.1.1",
+                "kernel": "23.1.0",
+            }),
+        };
+    }
+}
+
+pub struct Host {
+    pub hostname: &'static str,
+    pub ip: &'static str,
+    /// The service the host runs.
+    pub service: &'static str,
+    /// The team that operates the host.
+    pub team: &'static str,
+    pub platform: Platform,
+}
+
+pub const WEB: [Host; 2] = [
+    Host {
+        hostname: "storedog-web-1",
+        ip: "10.0.1.21",
+        service: "storedog",
+        team: "storefront",
+        platform: Platform::Linux,
+    },
+    Host {
+        hostname: "storedog-web-2",
+        ip: "10.0.1.22",
+        service: "storedog",
+        team: "storefront",
+        platform: Platform::Linux,
+    },
+];
+
+pub const BACKEND: [Host; 3] = [
+    Host {
+        hostname: "storedog-cart-1",
+        ip: "10.0.2.31",
+        service: "storedog-cart",
+        team: "commerce",
+        platform: Platform::Linux,
+    },
+    Host {
+        hostname: "storedog-catalog-1",
+        ip: "10.0.2.32",
+        service: "storedog-catalog",
+        team: "commerce",
+        platform: Platform::Linux,
+    },
+    Host {
+        hostname: "storedog-payments-1",
+        ip: "10.0.2.33",
+        service: "storedog-payments",
+        team: "payments",
+        platform: Platform::Linux,
+    },
+];
+
+pub const COMPROMISED: Host = Host {
+    hostname: "storedog-worker-1",
+    ip: mining::COMPROMISED_HOST_IP,
+    service: "storedog-worker",
+    team: "platform",
+    platform: Platform::Linux,
+};
+
+/// The domain controller, whose DNS server the fleet resolves names with.
+pub const DNS: Host = Host {
+    hostname: "STOREDOG-DC01",
+    ip: "10.0.0.10",
+    service: "windows-dns",
+    team: "platform",
+    platform: Platform::Windows,
+};
+
+/// The FTP server partners exchange files with.
+pub const FTP: Host = Host {
+    hostname: "storedog-ftp-1",
+    ip: "10.0.4.41",
+    service: "vsftpd",
+    team: "platform",
+    platform: Platform::Linux,
+};
+
+/// The VPN server employees connect to.
+pub const VPN: Host = Host {
+    hostname: "storedog-vpn-1",
+    ip: "10.0.0.20",
+    service: "openvpn",
+    team: "platform",
+    platform: Platform::Linux,
+};
+
+/// The Zeek sensor watching traffic at the edge of the network.
+pub const SENSOR: Host = Host {
+    hostname: "storedog-zeek-1",
+    ip: "10.0.0.30",
+    service: "zeek",
+    team: "security",
+    platform: Platform::Linux,
+};
+
+/// Employees' MacBooks, one for each VPN user, named the way macOS names
+/// them by default.
+pub const LAPTOPS: [Host; 4] = [
+    Host {
+        hostname: "akims-MacBook-Pro",
+        ip: "10.0.5.51",
+        service: "macos",
+        team: "it",
+        platform: Platform::MacOs,
+    },
+    Host {
+        hostname: "bnovaks-MacBook-Air",
+        ip: "10.0.5.52",
+        service: "macos",
+        team: "it",
+        platform: Platform::MacOs,
+    },
+    Host {
+        hostname: "cschulzs-MacBook-Pro",
+        ip: "10.0.5.53",
+        service: "macos",
+        team: "it",
+        platform: Platform::MacOs,
+    },
+    Host {
+        hostname: "dokafors-MacBook-Pro",
+        ip: "10.0.5.54",
+        service: "macos",
+        team: "it",
+        platform: Platform::MacOs,
+    },
+];
+
+/// The team that owns each service, including the AWS and platform services
+/// that aren't tied to a single host.
+pub const OWNERS: [(&str, &str); 22] = [
+    ("storedog", "storefront"),
+    ("storedog-orders", "commerce"),
+    ("storedog-cart", "commerce"),
+    ("storedog-catalog", "commerce"),
+    ("storedog-payments", "payments"),
+    ("payment-gateway", "payments"),
+    ("storedog-worker", "platform"),
+    ("pos-terminal", "commerce"),
+    ("windows-dns", "platform"),
+    ("vsftpd", "platform"),
+    ("openvpn", "platform"),
+    ("zeek", "security"),
+    ("okta", "security"),
+    ("falco", "security"),
+    ("cron", "platform"),
+    ("logrotate", "platform"),
+    ("kubelet", "platform"),
+    ("macos", "it"),
+    ("feature-flags", "platform"),
+    ("aws.vpc_flow_logs", "networking"),
+    ("aws.billing", "finops"),
+    ("aws.billing_export", "finops"),
+];
+
+/// Every host in the fleet.
+pub fn all() -> impl Iterator<Item = &'static Host> {
+    return WEB
+        .iter()
+        .chain(BACKEND.iter())
+        .chain([&COMPROMISED, &DNS, &FTP, &VPN, &SENSOR])
+        .chain(LAPTOPS.iter());
+}
+
+/// The fleet host named `hostname`, if there is one.
+pub fn find(hostname: &str) -> Option<&'static Host> {
+    return all().find(|h| h.hostname == hostname);
+}
+
+/// A random web server.
+pub fn web() -> &'static Host {
+    return random::pick(&WEB);
+}
+
+/// The host running a backend service.
+pub fn backend(service: &str) -> &'static Host {
+    return BACKEND
+        .iter()
+        .find(|h| h.service == service)
+        .expect("every backend service should have a host");
+}
+
+/// Hostname to IP for the whole fleet.
+pub fn mapping() -> serde_json::Value {
+    let mut mapping = serde_json::Map::new();
+    for host in all() {
+        mapping.insert(host.hostname.to_owned(), json!(host.ip));
+    }
+    return serde_json::Value::Object(mapping);
+}
@@ -0,0 +1,112 @@
+/*!
+Sender identity: the hostname and `ddsource` attached to every event.
+
+By default events carry the hostname of the simulated host they come from, or
+the real machine hostname if they don't come from one, and a `ddsource` of
+`dynamo`. Both can be overridden globally or per generator, so instructors can
+simulate a named fleet and avoid leaking their own hostname into shared demo
+orgs. A generator given several hostnames spreads its events across them.
+*/
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use gethostname::gethostname;
+
+use crate::random;
+
+#[derive(Default)]
+pub struct Identity {
+    pub hostnames: Vec<String>,
+    pub ddsource: Option<String>,
+    pub generator_hostnames: HashMap<String, Vec<String>>,
+    pub generator_ddsources: HashMap<String, String>,
+}
+
+static IDENTITY: OnceLock<Identity> = OnceLock::new();
+static REAL_HOSTNAME: OnceLock<String> = OnceLock::new();
+
+/// Parses a `generator=value` override. The value may be a comma-separated
+/// list.
+pub fn parse_override(s: &str) -> Result<(String, Vec<String>), String> {
+    let (generator, values) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected generator=value, got `{}`", s))?;
+    let values: Vec<String> = values
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_owned)
+        .collect();
+    if values.is_empty() {
+        return Err(format!("no value given for generator `{}`", generator));
+    }
+    return Ok((generator.trim().to_owned(), values));
+}
+
+/// Parses a `generator=value` override that takes a single value.
+pub fn parse_single_override(s: &str) -> Result<(String, String), String> {
+    let (generator, values) = parse_override(s)?;
+    if values.len() > 1 {
+        return Err(format!(
+            "only one value may be given for generator `{}`",
+            generator
+        ));
+    }
+    return Ok((generator, values[0].clone()));
+}
+
+/// Sets the identity for the run, rejecting overrides for generators that
+/// don't exist.
+pub fn configure(identity: Identity, generators: &[&str]) -> Result<(), String> {
+    let overridden = identity
+        .generator_hostnames
+        .keys()
+        .chain(identity.generator_ddsources.keys());
+    for generator in overridden {
+        if !generators.contains(&generator.as_str()) {
+            return Err(format!(
+                "unknown generator `{}` (known generators: {})",
+                generator,
+                generators.join(", ")
+            ));
+        }
+    }
+
+    IDENTITY
+        .set(identity)
+        .map_err(|_| "identity should only be configured once".to_owned())?;
+    return Ok(());
+}
+
+fn identity() -> &'static Identity {
+    return IDENTITY.get_or_init(Identity::default);
+}
+
+/// The hostname for an event from `generator`. Overrides take precedence over
+/// the `simulated` host the event says it comes from.
+pub fn hostname(generator: &str, simulated: Option<&str>) -> String {
+    let identity = identity();
+    let hostnames = identity
+        .generator_hostnames
+        .get(generator)
+        .unwrap_or(&identity.hostnames);
+    if hostnames.is_empty() {
+        if let Some(simulated) = simulated {
+            return simulated.to_owned();
+        }
+        return REAL_HOSTNAME
+            .get_or_init(|| gethostname().into_string().expect("could not get hostname"))
+            .clone();
+    }
+    return random::pick(hostnames).clone();
+}
+
+pub fn ddsource(generator: &str) -> String {
+    let identity = identity();
+    return identity
+        .generator_ddsources
+        .get(generator)
+        .or(identity.ddsource.as_ref())
+        .cloned()
+        .unwrap_or_else(|| "dynamo".to_owned());
+}
@@ -0,0 +1,93 @@
+/*!
+Timed incidents that switch part of the simulation into an anomalous mode.
+
+Generators check whether an incident is active when building each event. Start
+and stop times are recorded in the answer key under the incident's name,
+alongside any details students are expected to find, and sent to the progress
+webhook.
+*/
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::prelude::*;
+use serde_json::json;
+
+use crate::{answer_key, clock, cluster, trigger, webhook};
+
+pub struct Incident {
+    name: &'static str,
+    details: fn() -> serde_json::Value,
+    active: AtomicBool,
+    started: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl Incident {
+    pub const fn new(name: &'static str, details: fn() -> serde_json::Value) -> Incident {
+        return Incident {
+            name,
+            details,
+            active: AtomicBool::new(false),
+            started: Mutex::new(None),
+        };
+    }
+
+    pub fn active(&self) -> bool {
+        return self.active.load(Ordering::Relaxed);
+    }
+
+    /// How long the incident has been running, in generated time, if it is
+    /// active.
+    pub fn active_for(&self) -> Option<chrono::Duration> {
+        if !self.active() {
+            return None;
+        }
+        let started = *self
+            .started
+            .lock()
+            .expect("incident lock should not be poisoned");
+        return started.map(|started| clock::now() - started);
+    }
+
+    pub fn set(&self, active: bool) {
+        *self
+            .started
+            .lock()
+            .expect("incident lock should not be poisoned") = active.then(clock::now);
+        self.active.store(active, Ordering::Relaxed);
+
+        let field = if active { "started_at" } else { "stopped_at" };
+        let mut record = (self.details)();
+        record[field] = json!(answer_key::now());
+        answer_key::record(json!({ (self.name): record.clone() }));
+        webhook::phase(self.name, active, record);
+    }
+
+    /// Starts the incident `at_s` seconds after startup, and stops it
+    /// `duration_s` seconds later, here and on any coordinated workers. A zero
+    /// `at_s` disables the incident, and a zero `duration_s` leaves it running
+    /// for the rest of the run.
+    pub fn schedule(&'static self, at_s: u64, duration_s: u64) {
+        if at_s == 0 {
+            return;
+        }
+
+        let scenario = trigger::scenario_of(self);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(at_s)).await;
+            self.set(true);
+            if let Some(scenario) = scenario {
+                cluster::forward(scenario, false);
+            }
+            if duration_s == 0 {
+                return;
+            }
+
+            tokio::time::sleep(Duration::from_secs(duration_s)).await;
+            self.set(false);
+            if let Some(scenario) = scenario {
+                cluster::forward(scenario, true);
+            }
+        });
+    }
+}
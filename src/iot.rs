@@ -0,0 +1,177 @@
+/*!
+Device telemetry from storedog's warehouse, published over MQTT.
+
+Temperature sensors in the cold rooms and the server room report readings, and
+door sensors report when doors open and close. Each payload is a JSON object
+published with QoS 0 to `storedog/<site>/<device>/<type>`, for lessons on
+Vector's MQTT source and IoT pipelines. dynamo speaks just enough MQTT 3.1.1
+to connect and publish, so any broker will do.
+
+During the stuck sensor incident, the walk-in cooler's sensor keeps reporting
+the same reading no matter what the room is doing, which students are expected
+to detect from the lack of variance.
+*/
+use serde_json::{self, json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::incident::Incident;
+use crate::{clock, proto, publisher, random};
+
+pub const GENERATOR: &str = "iot-telemetry";
+const SITE: &str = "storedog-warehouse-1";
+/// Temperature sensors and the temperature, in Celsius, of what they measure.
+const SENSORS: [(&str, f64); 3] = [
+    ("walk-in-freezer-1", -18.0),
+    ("walk-in-cooler-1", 3.5),
+    ("server-room-1", 21.0),
+];
+const DOORS: [&str; 2] = ["loading-dock-1", "back-office-1"];
+pub const STUCK_SENSOR: &str = "walk-in-cooler-1";
+/// The reading the stuck sensor repeats.
+const STUCK_CELSIUS: f64 = 3.8;
+
+pub static INCIDENT: Incident = Incident::new("stuck_sensor", details);
+
+fn details() -> serde_json::Value {
+    return json!({
+        "site": SITE,
+        "device_id": STUCK_SENSOR,
+        "stuck_celsius": STUCK_CELSIUS,
+    });
+}
+
+fn temperature() -> serde_json::Value {
+    let (device, baseline) = random::pick(&SENSORS);
+    let celsius = if *device == STUCK_SENSOR && INCIDENT.active() {
+        STUCK_CELSIUS
+    } else {
+        ((baseline + random::range(-0.8..0.8)) * 10.0).round() / 10.0
+    };
+    return json!({
+        "device_id": device,
+        "type": "temperature",
+        "celsius": celsius,
+        "battery_pct": random::range(60..100),
+    });
+}
+
+fn door() -> serde_json::Value {
+    return json!({
+        "device_id": random::pick(&DOORS),
+        "type": "door",
+        "state": if random::chance(0.5) { "open" } else { "closed" },
+    });
+}
+
+/// A telemetry message, with the topic it is published to.
+pub fn generate() -> serde_json::Value {
+    let mut payload = if random::chance(0.8) {
+        temperature()
+    } else {
+        door()
+    };
+    payload["site"] = json!(SITE);
+    payload["ts"] = json!(clock::now().timestamp_millis());
+
+    let topic = format!(
+        "storedog/{}/{}/{}",
+        SITE,
+        payload["device_id"].as_str().unwrap_or_default(),
+        payload["type"].as_str().unwrap_or_default()
+    );
+    return json!({ "topic": topic, "payload": payload });
+}
+
+/// Appends a string with its 2-byte length, as MQTT encodes them.
+fn mqtt_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// A control packet with its fixed header. The remaining length uses the same
+/// base-128 encoding as protobuf varints.
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut buf = vec![kind];
+    proto::varint(&mut buf, body.len() as u64);
+    buf.extend_from_slice(body);
+    return buf;
+}
+
+/// A CONNECT packet for a clean session with keepalive disabled.
+fn connect_packet(client_id: &str) -> Vec<u8> {
+    let mut body = vec![];
+    mqtt_string(&mut body, "MQTT");
+    body.push(4); // protocol level 3.1.1
+    body.push(0x02); // clean session
+    body.extend_from_slice(&0u16.to_be_bytes());
+    mqtt_string(&mut body, client_id);
+    return packet(0x10, &body);
+}
+
+/// A QoS 0 PUBLISH packet.
+fn publish_packet(topic: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = vec![];
+    mqtt_string(&mut body, topic);
+    body.extend_from_slice(payload);
+    return packet(0x30, &body);
+}
+
+/// Connects to the broker at `target` and waits for it to accept the
+/// connection.
+async fn connect(target: String) -> Result<TcpStream, String> {
+    let mut stream = publisher::connect(target.clone()).await?;
+    let client_id = format!("dynamo-{}", std::process::id());
+    stream
+        .write_all(&connect_packet(&client_id))
+        .await
+        .map_err(|e| format!("Could not send to {}: {}", target, e))?;
+
+    let mut connack = [0u8; 4];
+    stream
+        .read_exact(&mut connack)
+        .await
+        .map_err(|e| format!("No CONNACK from {}: {}", target, e))?;
+    if connack[0] != 0x20 || connack[3] != 0 {
+        return Err(format!(
+            "{} refused the connection: return code {}",
+            target, connack[3]
+        ));
+    }
+    return Ok(stream);
+}
+
+/// Publishes telemetry to the MQTT broker at `target` at `rate_limit_per_s`,
+/// reconnecting whenever the connection fails. Messages generated while
+/// disconnected are dropped. A zero rate disables the generator.
+pub fn start(target: String, rate_limit_per_s: usize) {
+    publisher::start(GENERATOR, target, rate_limit_per_s, connect, || {
+        let message = generate();
+        return publish_packet(
+            message["topic"].as_str().unwrap_or_default(),
+            message["payload"].to_string().as_bytes(),
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_connect_and_publish_packets() {
+        let mut connect = vec![0x10, 14, 0, 4];
+        connect.extend(b"MQTT");
+        connect.extend([4, 0x02, 0, 0, 0, 2]);
+        connect.extend(b"id");
+        assert_eq!(connect_packet("id"), connect);
+
+        let mut publish = vec![0x30, 6, 0, 1, b't'];
+        publish.extend(b"{}\n");
+        assert_eq!(publish_packet("t", b"{}\n"), publish);
+
+        // Lengths over 127 take a second byte.
+        let long = publish_packet("t", &[0; 200]);
+        assert_eq!(&long[..3], &[0x30, 0xcb, 0x01]);
+    }
+}
@@ -0,0 +1,243 @@
+/*!
+End-to-end pipeline latency.
+
+Every event is stamped with the time dynamo sent it, in microseconds since the
+Unix epoch, just before its batch is encoded. The clock is read from a
+monotonic timer anchored to the wall clock at startup, so send times never go
+backwards within a run even if the system clock is adjusted.
+
+`dynamo latency-probe` reads Vector's output, from a `console` sink piped to
+its stdin or by following a `file` sink's file, and reports percentiles of the
+time between dynamo sending each event and the probe reading it. The probe has
+to run on the same machine as dynamo for the two clocks to agree. Latencies
+are kept in a fixed-size histogram, so the probe can follow a sink for as long
+as a class runs.
+*/
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+/// Field holding the send time on every event.
+pub const FIELD: &str = "dynamo_sent_at_us";
+
+static ANCHOR: OnceLock<(Instant, Duration)> = OnceLock::new();
+
+fn wall_clock() -> Duration {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock should be after the Unix epoch");
+}
+
+/// Monotonic microseconds since the Unix epoch.
+fn now_us() -> u64 {
+    let (instant, wall) = ANCHOR.get_or_init(|| (Instant::now(), wall_clock()));
+    return (*wall + instant.elapsed()).as_micros() as u64;
+}
+
+/// Stamps a batch of events with the current send time.
+pub fn stamp(events: &mut [serde_json::Value]) {
+    let now = now_us();
+    for event in events.iter_mut() {
+        event[FIELD] = json!(now);
+    }
+}
+
+/// How much wider each histogram bucket is than the one before it, which
+/// bounds the error of a percentile.
+const GROWTH: f64 = 1.01;
+/// Enough buckets for latencies of over a day, in microseconds. Longer ones
+/// are counted in the last bucket.
+const BUCKETS: usize = 2600;
+
+/// Latencies counted in buckets that grow by 1% each, so the probe's memory
+/// stays the same however long it follows a sink, and percentiles are within
+/// 1% of the exact ones.
+struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn new() -> Histogram {
+        return Histogram {
+            counts: vec![0; BUCKETS],
+            total: 0,
+            max: 0,
+        };
+    }
+
+    /// Bucket 0 holds zero, and bucket `b` everything up to `GROWTH^b`.
+    fn bucket(us: u64) -> usize {
+        if us == 0 {
+            return 0;
+        }
+        let bucket = ((us as f64).ln() / GROWTH.ln()).ceil() as usize;
+        return bucket.clamp(1, BUCKETS - 1);
+    }
+
+    fn add(&mut self, us: u64) {
+        self.counts[Histogram::bucket(us)] += 1;
+        self.total += 1;
+        self.max = self.max.max(us);
+    }
+
+    /// The latency at percentile `p`, by the nearest-rank method, as the upper
+    /// bound of the bucket it falls in.
+    fn percentile(&self, p: f64) -> u64 {
+        if self.total == 0 {
+            return 0;
+        }
+        let rank = ((p / 100.0) * self.total as f64).ceil() as u64;
+        let rank = rank.clamp(1, self.total);
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = GROWTH.powi(bucket as i32).floor() as u64;
+                return upper.min(self.max);
+            }
+        }
+        return self.max;
+    }
+}
+
+fn report(latencies: &Histogram, unstamped: u64) {
+    let ms = |us: u64| us as f64 / 1000.0;
+    println!(
+        "events={} p50={:.1}ms p90={:.1}ms p99={:.1}ms max={:.1}ms unstamped={}",
+        latencies.total,
+        ms(latencies.percentile(50.0)),
+        ms(latencies.percentile(90.0)),
+        ms(latencies.percentile(99.0)),
+        ms(latencies.max),
+        unstamped,
+    );
+}
+
+/// Reads the next line of `input`, or returns `None` at its end. With
+/// `follow`, the end of the input may fall in the middle of a line that is
+/// still being written, so what was read is kept in `partial` until the rest
+/// of the line arrives.
+fn next_line(
+    input: &mut impl BufRead,
+    partial: &mut String,
+    follow: bool,
+) -> Result<Option<String>, String> {
+    let read = input
+        .read_line(partial)
+        .map_err(|e| format!("could not read input: {}", e))?;
+    if (read == 0 && partial.is_empty()) || (follow && !partial.ends_with('\n')) {
+        return Ok(None);
+    }
+    return Ok(Some(std::mem::take(partial)));
+}
+
+/// Reads newline-delimited JSON events and prints latency percentiles for
+/// everything read so far every `interval`, and once more at the end of the
+/// input. With `follow`, reaching the end of the input waits for more instead
+/// of ending.
+fn probe(mut input: impl BufRead, follow: bool, interval: Duration) -> Result<(), String> {
+    let mut latencies = Histogram::new();
+    let mut unstamped = 0;
+    let mut reported = Instant::now();
+    let mut partial = String::new();
+    loop {
+        let line = next_line(&mut input, &mut partial, follow)?;
+        if reported.elapsed() >= interval {
+            report(&latencies, unstamped);
+            reported = Instant::now();
+        }
+        let line = match line {
+            Some(line) => line,
+            None if follow => {
+                std::thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            None => break,
+        };
+
+        let received = now_us();
+        let sent = serde_json::from_str::<serde_json::Value>(&line)
+            .ok()
+            .and_then(|event| event[FIELD].as_u64());
+        match sent {
+            Some(sent) => latencies.add(received.saturating_sub(sent)),
+            None => unstamped += 1,
+        }
+    }
+
+    report(&latencies, unstamped);
+    return Ok(());
+}
+
+/// Runs the probe against `file`, following new events appended to it, or
+/// against stdin until it ends.
+pub fn run(file: Option<&str>, interval_s: u64) -> Result<(), String> {
+    let interval = Duration::from_secs(interval_s);
+    return match file {
+        Some(path) => {
+            let mut file =
+                File::open(path).map_err(|e| format!("could not open {}: {}", path, e))?;
+            // Events already in the file were read by nobody at an unknown
+            // time, so only new ones are measured.
+            file.seek(SeekFrom::End(0))
+                .map_err(|e| format!("could not seek in {}: {}", path, e))?;
+            probe(BufReader::new(file), true, interval)
+        }
+        None => probe(std::io::stdin().lock(), false, interval),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let mut latencies = Histogram::new();
+        assert_eq!(latencies.percentile(50.0), 0);
+        latencies.add(7);
+        assert_eq!(latencies.percentile(0.0), 7);
+
+        let mut latencies = Histogram::new();
+        for us in 1..=100_000 {
+            latencies.add(us);
+        }
+        for (p, exact) in [(50.0, 50_000.0), (90.0, 90_000.0), (99.0, 99_000.0)] {
+            let found = latencies.percentile(p) as f64;
+            assert!(
+                (found - exact).abs() <= exact * 0.01,
+                "p{} was {}",
+                p,
+                found
+            );
+        }
+        assert_eq!(latencies.percentile(100.0), 100_000);
+        assert_eq!(latencies.max, 100_000);
+    }
+
+    #[test]
+    fn waits_for_whole_lines_when_following() {
+        let mut partial = String::new();
+        let mut first = "{\"a\":".as_bytes();
+        assert_eq!(next_line(&mut first, &mut partial, true).unwrap(), None);
+        let mut rest = "1}\n".as_bytes();
+        assert_eq!(
+            next_line(&mut rest, &mut partial, true).unwrap().as_deref(),
+            Some("{\"a\":1}\n")
+        );
+
+        let mut last = "{}".as_bytes();
+        assert_eq!(
+            next_line(&mut last, &mut partial, false)
+                .unwrap()
+                .as_deref(),
+            Some("{}")
+        );
+        assert_eq!(next_line(&mut last, &mut partial, false).unwrap(), None);
+    }
+}
@@ -0,0 +1,106 @@
+/*!
+macOS unified logs from employees' laptops.
+
+Cross-platform fleet lessons need more than Linux syslog and Windows DNS.
+Each line is an entry as `log show --style default` prints it: timestamp,
+thread, type, activity, PID and TTL columns, then the process, the library
+that logged the entry and, for most entries, its subsystem and category.
+Entries are screen unlocks, DNS lookups, software update scans, Gatekeeper
+assessments and failed authorizations, from one of the laptops in the fleet.
+*/
+use serde_json::{self, json};
+
+use crate::{clock, hosts, random};
+
+pub const GENERATOR: &str = "macos-unified";
+pub const SERVICE: &str = "macos";
+
+struct Entry {
+    process: &'static str,
+    /// The library or executable that logged the entry.
+    sender: &'static str,
+    /// Subsystem and category. Kernel entries have neither.
+    subsystem: Option<(&'static str, &'static str)>,
+    /// The entry's type: Default, Info, Debug, Error or Fault.
+    kind: &'static str,
+    message: &'static str,
+}
+
+const ENTRIES: [Entry; 6] = [
+    Entry {
+        process: "loginwindow",
+        sender: "loginwindow",
+        subsystem: Some(("com.apple.loginwindow.logging", "Standard")),
+        kind: "Default",
+        message: "-[SessionAgentNotificationCenter sendDistributedNotification:forUserID:] | sending distributed notification: com.apple.screenIsUnlocked",
+    },
+    Entry {
+        process: "mDNSResponder",
+        sender: "mDNSResponder",
+        subsystem: Some(("com.apple.mDNSResponder", "Default")),
+        kind: "Default",
+        message: "DNSServiceQueryRecord(15000, 0, <private>, Addr) START PID[512](Slack)",
+    },
+    Entry {
+        process: "softwareupdated",
+        sender: "SoftwareUpdate",
+        subsystem: Some(("com.apple.SoftwareUpdate", "SU")),
+        kind: "Info",
+        message: "SUOSUScanController: Scan for client pid 812 (/System/Library/PrivateFrameworks/SoftwareUpdate.framework/Resources/softwareupdated) completed",
+    },
+    Entry {
+        process: "syspolicyd",
+        sender: "syspolicyd",
+        subsystem: Some(("com.apple.syspolicy.exec", "default")),
+        kind: "Default",
+        message: "GK performScan: PST: (path: /Applications/zoom.us.app), (team: BJ4HAAB9B3), (id: us.zoom.xos), (bundle_id: us.zoom.xos)",
+    },
+    Entry {
+        process: "authd",
+        sender: "Security",
+        subsystem: Some(("com.apple.Authorization", "authd")),
+        kind: "Error",
+        message: "Failed to authorize right 'system.preferences.security' by client '/System/Library/PrivateFrameworks/SystemAdministration.framework/Versions/A/Resources/writeconfig.xpc' [914] (-60005)",
+    },
+    Entry {
+        process: "kernel",
+        sender: "AppleACPIPlatform",
+        subsystem: None,
+        kind: "Default",
+        message: "Wake reason: EC.LidOpen (User)",
+    },
+];
+
+pub fn generate() -> serde_json::Value {
+    let entry = random::pick(&ENTRIES);
+    let subsystem = match entry.subsystem {
+        Some((subsystem, category)) => format!("[{}:{}] ", subsystem, category),
+        None => String::new(),
+    };
+    // The kernel always logs as PID 0.
+    let pid = match entry.process {
+        "kernel" => 0,
+        _ => random::range(100..2000),
+    };
+    return json!({
+        "message": format!(
+            "{} {:<#10x} {:<11} {:<#20x} {:<6} {:<4} {}: ({}) {}{}",
+            clock::now().format("%Y-%m-%d %H:%M:%S%.6f%z"),
+            random::range(0x1000..0xfffff),
+            entry.kind,
+            0,
+            pid,
+            0,
+            entry.process,
+            entry.sender,
+            subsystem,
+            entry.message,
+        ),
+        "service": SERVICE,
+        "hostname": random::pick(&hosts::LAPTOPS).hostname,
+        "status": match entry.kind {
+            "Error" | "Fault" => "ERROR",
+            _ => "INFO",
+        },
+    });
+}
@@ -1,3 +1,6 @@
+// Explicit returns are the house style.
+#![allow(clippy::needless_return)]
+
 /**
 dynamo is a small program that will emit logs at a specified pace, intended
 as an instructional tool for people using Vector.
@@ -16,36 +19,307 @@ Those educational goals are:
 
 To this end, Dynamo supports the following outputs, which are intended to
 be directed at a listening Vector instance with the `datadog_agent` source
-configured:
+configured (or the `opentelemetry` source, with `--sink otlp`, or any HTTP
+endpoint or local file in JSON, logfmt, raw, CEF, protobuf or Avro encoding,
+with `--sink http` or `--sink file`):
 
  - HTTP logs coming from a sample e-commerce store, including a data leak
-   of customer credit card information; and
- - VPC flow logs, including evidence of an SSH brute-force attack.
+   of customer credit card information and a cardinality explosion from a
+   per-request tag, and business events for the orders placed through it;
+ - Application logs from the store's backend services with a mix of log
+   levels, including a DEBUG logging flood and a failure that cascades
+   through the services' dependencies;
+ - VPC flow logs, including evidence of an SSH brute-force attack and a
+   crypto mining incident;
+ - Feature flag audit events, one of which precedes a latency regression in
+   the HTTP logs;
+ - AWS Cost and Usage Report billing logs, as JSON or a CSV export, showing
+   the cost spike caused by the crypto mining incident;
+ - Point-of-sale transaction logs from the store's tills, with a
+   configurable delimiter or fixed-width columns;
+ - XML access records from the SOAP gateway to the card processor;
+ - Windows DNS Server debug logs, including lookups of algorithmically
+   generated domains from the compromised host;
+ - FTP transfer logs in the xferlog format, including one user's bulk
+   download of the customer export;
+ - OpenVPN connection logs and Zeek TLS handshake records, including one
+   account connecting from two distant countries within minutes;
+ - Okta single sign-on events from the same employees and addresses, so the
+   impossible travel shows up in both;
+ - macOS unified logs from employees' laptops;
+ - Falco process and network events from the Linux fleet, including the
+   crypto miner and the DGA malware on the compromised host; and
+ - Background noise from cron, logrotate, the kubelet and load balancer
+   health checks, on by default at a low rate.
 */
-use std::time;
+use std::net::SocketAddr;
 use std::time::Duration;
 
 use async_stream::stream;
-use chrono::prelude::*;
-use clap::Parser;
-use fakeit::company;
-use fakeit::internet;
-use fakeit::payment;
-use gethostname::gethostname;
+use clap::{Parser, Subcommand};
 use json_patch::merge;
-use leaky_bucket::RateLimiter;
-use rand::Rng;
 use serde_json::{self, json};
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 
+use crate::encoding::Encoding;
+use crate::incident::Incident;
+use crate::sink::{Compression, Event, Sink};
+
+mod admin;
+mod agents;
+mod answer_key;
+mod applog;
+mod attackers;
+mod avro;
+mod billing;
+mod buffer;
+mod bundle;
+mod cardinality;
+mod cascade;
+mod clock;
+mod cluster;
+mod config;
+mod dns;
+mod embed;
+mod encoding;
+mod enrichment;
+mod exercises;
+mod failures;
+mod falco;
+mod flags;
+mod ftp;
+mod grpc;
+mod heartbeat;
+mod hosts;
+mod identity;
+mod incident;
+mod iot;
+mod latency;
+mod macos;
+mod mining;
+mod netflow;
+mod noise;
+mod orders;
+mod otlp;
+mod pacing;
+mod pack;
+mod payments;
+mod percent;
+mod placement;
+mod pos;
+mod preflight;
+mod proto;
+mod publisher;
+mod queue;
+mod random;
+mod replay;
+mod sampling;
+mod schema;
+mod selftest;
+mod sequence;
+mod severity;
+mod shape;
+mod sink;
+mod snmp;
+mod soap;
+mod sso;
+mod stats;
+mod storedog;
+mod trigger;
+mod validate;
+mod vpc;
+mod vpn;
+mod warmup;
+mod webhook;
+
+fn trigger_names() -> clap::builder::PossibleValuesParser {
+    return trigger::SCENARIOS
+        .iter()
+        .map(|s| s.name)
+        .collect::<Vec<_>>()
+        .into();
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Check a scenario pack for unknown keys and options and for problems
+    /// that would stop or spoil a run, exiting non-zero if there are any.
+    Validate {
+        /// The pack's directory, tarball or pack.json, or a built-in pack's
+        /// name.
+        pack: String,
+    },
+    /// Print a JSON Schema for pack.json, for editors and CI.
+    Schema,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write CSV lookup tables for Vector's `enrichment_tables` that match
+    /// the simulated fleet.
+    ExportEnrichment {
+        /// Directory to write the tables to.
+        #[arg(long, default_value = ".")]
+        dir: String,
+    },
+    /// Check scenario packs, or print a JSON Schema for their manifests.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Write a starter VRL program with failing assertions and a sample
+    /// event for each enabled generator, as parsing exercises. Rates and
+    /// other options go before `exercises`.
+    Exercises {
+        /// Directory to write the exercises to.
+        #[arg(long, default_value = "exercises")]
+        dir: String,
+    },
+    /// Read Vector's output and report percentiles of the end-to-end latency
+    /// between dynamo sending each event and it coming out of the pipeline.
+    /// Reads newline-delimited JSON, such as from a `console` sink, from
+    /// stdin unless given a file to follow.
+    LatencyProbe {
+        /// File written by a Vector `file` sink to follow. Only events written
+        /// after the probe starts are measured.
+        #[arg(long)]
+        file: Option<String>,
+
+        /// Seconds between reports.
+        #[arg(long, default_value_t = 10)]
+        interval_s: u64,
+    },
+    /// Print the JSON Schema for a generator's events, or list the available
+    /// schemas.
+    Schema {
+        generator: Option<String>,
+
+        /// Schema version to print. Defaults to the latest.
+        #[arg(long)]
+        version: Option<u32>,
+    },
+    /// Render a whole run to per-source log files and its answer key,
+    /// without a live target. Rates, incident timings and the encoding come
+    /// from the other options, which go before `export`.
+    Export {
+        /// Simulated length of the run, in seconds or with an `s`, `m` or `h`
+        /// suffix.
+        #[arg(long, value_parser = bundle::parse_duration)]
+        duration: u64,
+
+        /// Directory to write the bundle to.
+        #[arg(long)]
+        out: String,
+
+        /// Render the run as history that ends now instead of starting now,
+        /// shaped by the weekday rates and diurnal curve.
+        #[arg(long)]
+        backfill: bool,
+    },
+    /// Replay a bundle written by `export` through the configured sink, then
+    /// exit.
+    Serve {
+        /// Directory of the bundle.
+        dir: String,
+
+        /// How many times faster than the original run to replay it.
+        #[arg(long, default_value_t = 1.0, value_parser = replay::parse_speed)]
+        speed: f64,
+    },
+    /// Render every generator with a fixed seed and compare the output
+    /// against the committed golden files.
+    Selftest {
+        /// Rewrite the golden files in this directory instead of checking
+        /// them.
+        #[arg(long, num_args = 0..=1, default_missing_value = "golden")]
+        update: Option<String>,
+    },
+    /// Fire an anomaly scenario in a running dynamo through its admin API.
+    Trigger {
+        #[arg(value_parser = trigger_names())]
+        scenario: String,
+
+        /// End the scenario instead of starting it.
+        #[arg(long)]
+        stop: bool,
+
+        /// Admin API address of the running dynamo.
+        #[arg(long, default_value = "127.0.0.1:8686")]
+        admin_address: SocketAddr,
+    },
+    /// Check newline-delimited JSON events on stdin, such as the output of a
+    /// Vector `console` sink, for gaps and duplicates in each generator's
+    /// sequence numbers and for batches that arrived incomplete.
+    VerifySequence,
+    /// Check newline-delimited JSON events on stdin, such as the output of a
+    /// Vector `console` sink, against the reference grammar for each format.
+    ValidateOutput,
+}
+
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, args_override_self = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Where to send logs.
+    #[arg(long, value_enum, default_value_t = Sink::DatadogAgent)]
+    sink: Sink,
+
+    /// How events are encoded. Only the generic HTTP and file sinks support
+    /// encodings other than their default: `json-array` for `datadog_agent`,
+    /// `protobuf` for `otlp`, and newline-delimited `json` for `http` and
+    /// `file`. Vector's `http_server` source frames and decodes `json` and
+    /// `json-array` bodies differently, so the HTTP sink can send either.
+    #[arg(long, value_enum)]
+    encoding: Option<Encoding>,
+
+    /// Compression for request bodies sent to the `datadog_agent` source or
+    /// the generic HTTP sink.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    compression: Compression,
+
     /// Vector `datadog_agent` source address to send to.
     #[arg(long, default_value = "http://localhost:8282")]
     datadog_agent_target: String,
 
+    /// URL the generic HTTP sink posts batches to, such as a Vector
+    /// `http_server` source.
+    #[arg(long, default_value = "http://localhost:8080")]
+    http_target: String,
+
+    /// File the file sink appends events to.
+    #[arg(long, default_value = "dynamo.log")]
+    file_path: String,
+
+    /// Event field to send as an HTTP header with the generic HTTP sink, as
+    /// `header=field`, such as `X-Service=service`. Nested fields are given
+    /// as dotted paths. Batches are split so that every request has a single
+    /// value per header. May be repeated.
+    #[arg(long, value_parser = sink::parse_header_field)]
+    http_header_field: Vec<(String, String)>,
+
+    /// Send each batch as one of several log shipping agents (current and
+    /// legacy Datadog Agents, Fluent Bit, Logstash and Vector), each with its
+    /// own `ddsource` and request headers.
+    #[arg(long)]
+    mimic_agents: bool,
+
+    /// Vector `opentelemetry` source gRPC address to send to.
+    #[arg(long, default_value = "http://localhost:4317")]
+    otlp_target: String,
+
+    /// Pack every OTLP request with as many resource and scope combinations as
+    /// fit, to load test the receiver.
+    #[arg(long)]
+    otlp_stress: bool,
+
+    /// Maximum encoded size of an OTLP request in stress mode. The default
+    /// stays just under gRPC's usual 4MiB message limit.
+    #[arg(long, default_value_t = 4_000_000)]
+    otlp_stress_max_bytes: usize,
+
     /// Total rate limit for normal HTTP logs.
     #[arg(long, default_value_t = 100)]
     http_log_rate_limit_per_s: usize,
@@ -66,6 +340,355 @@ struct Args {
     #[arg(long, default_value_t = 0)]
     vpc_log_attack_rate_limit_per_s: usize,
 
+    /// Country-specific IPv4 ranges the SSH brute-force attack comes from,
+    /// as a comma-separated list of `country=cidr`.
+    #[arg(long, value_parser = attackers::parse_ranges, default_value = attackers::DEFAULT_RANGES)]
+    attacker_ranges: attackers::Ranges,
+
+    /// Hostname to put on every event, instead of this machine's. May be a
+    /// comma-separated list to spread events over several hosts.
+    #[arg(long, value_delimiter = ',')]
+    hostname: Vec<String>,
+
+    /// `ddsource` to put on every event.
+    #[arg(long)]
+    ddsource: Option<String>,
+
+    /// Hostname override for a single generator, as
+    /// `generator=host1,host2`. May be repeated.
+    #[arg(long, value_parser = identity::parse_override)]
+    generator_hostname: Vec<(String, Vec<String>)>,
+
+    /// `ddsource` override for a single generator, as `generator=source`. May
+    /// be repeated.
+    #[arg(long, value_parser = identity::parse_single_override)]
+    generator_ddsource: Vec<(String, String)>,
+
+    /// Seconds after startup at which the `storedog.checkout-v2` feature flag
+    /// is enabled, causing a latency regression in the HTTP logs. Disabled by
+    /// default.
+    #[arg(long, default_value_t = 0)]
+    feature_flag_regression_at_s: u64,
+
+    /// Seconds the regressing feature flag stays enabled before it is rolled
+    /// back. 0 leaves it enabled for the rest of the run.
+    #[arg(long, default_value_t = 0)]
+    feature_flag_rollback_after_s: u64,
+
+    /// Seconds after startup at which a host is compromised and starts crypto
+    /// mining, showing up in VPC flow logs and as a cost spike in billing
+    /// logs. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    crypto_mining_at_s: u64,
+
+    /// Seconds the crypto mining incident lasts. 0 leaves it running for the
+    /// rest of the run.
+    #[arg(long, default_value_t = 0)]
+    crypto_mining_duration_s: u64,
+
+    /// Rate limit for VPC flow logs to the mining pool while the crypto mining
+    /// incident is active.
+    #[arg(long, default_value_t = 5)]
+    vpc_log_mining_rate_limit_per_s: usize,
+
+    /// Rate limit for Cost and Usage Report billing logs. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    billing_log_rate_limit_per_s: usize,
+
+    /// Rate limit for point-of-sale transaction logs from storedog's stores.
+    /// Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    pos_log_rate_limit_per_s: usize,
+
+    /// Delimiter for point-of-sale logs: pipe, tab, comma, w3c, fixed-width,
+    /// or a single punctuation character.
+    #[arg(long, default_value = pos::DEFAULT_DELIMITER, value_parser = pos::parse_delimiter)]
+    pos_log_delimiter: pos::Delimiter,
+
+    /// Rate limit for XML access records from the SOAP payment gateway.
+    /// Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    soap_log_rate_limit_per_s: usize,
+
+    /// Rate limit for card authorizations from the payments service, sent as
+    /// length-prefixed protobuf over TCP. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    protobuf_log_rate_limit_per_s: usize,
+
+    /// Address of the Vector `socket` source to send protobuf authorizations
+    /// to.
+    #[arg(long, default_value = "localhost:9000")]
+    protobuf_tcp_target: String,
+
+    /// Rate limit for warehouse device telemetry, published over MQTT.
+    /// Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    iot_log_rate_limit_per_s: usize,
+
+    /// Address of the MQTT broker to publish device telemetry to.
+    #[arg(long, default_value = "localhost:1883")]
+    mqtt_target: String,
+
+    /// Address of a NetFlow collector to also export the VPC's flows to over
+    /// UDP. Off by default.
+    #[arg(long)]
+    netflow_target: Option<String>,
+
+    /// Record format for exported flows.
+    #[arg(long, value_enum, default_value_t = netflow::Format::V5)]
+    netflow_format: netflow::Format,
+
+    /// Rate limit for SNMPv2c traps from the fleet: link flaps, and high CPU
+    /// on hosts loaded by an incident. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    snmp_trap_rate_limit_per_s: usize,
+
+    /// Address of the SNMP trap receiver to send traps to over UDP.
+    #[arg(long, default_value = "localhost:162")]
+    snmp_trap_target: String,
+
+    /// Rate limit for Windows DNS Server debug log lookups, each logged as a
+    /// query and a response. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    dns_log_rate_limit_per_s: usize,
+
+    /// Rate limit for FTP transfer logs in the xferlog format. Disabled by
+    /// default.
+    #[arg(long, default_value_t = 0)]
+    ftp_log_rate_limit_per_s: usize,
+
+    /// Rate limit for VPN activity: OpenVPN connection logs and Zeek TLS
+    /// handshake records. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    vpn_log_rate_limit_per_s: usize,
+
+    /// Rate limit for Okta single sign-on events from the employees who use
+    /// the VPN. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    sso_log_rate_limit_per_s: usize,
+
+    /// Rate limit for background noise: cron jobs, logrotate runs, kubelet
+    /// chatter and load balancer health checks. Set to 0 for a clean stream.
+    #[arg(long, default_value_t = noise::DEFAULT_RATE)]
+    noise_log_rate_limit_per_s: usize,
+
+    /// Rate limit for macOS unified logs from employees' laptops. Disabled by
+    /// default.
+    #[arg(long, default_value_t = 0)]
+    macos_log_rate_limit_per_s: usize,
+
+    /// Rate limit for Falco process execution and network connection events
+    /// from the Linux fleet. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    falco_log_rate_limit_per_s: usize,
+
+    /// Rate limit for billing line items as a CSV export. Disabled by
+    /// default.
+    #[arg(long, default_value_t = 0)]
+    billing_csv_rate_limit_per_s: usize,
+
+    /// Rate limit for storedog application logs. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    app_log_rate_limit_per_s: usize,
+
+    /// Relative weights of each level in application logs.
+    #[arg(long, default_value = applog::DEFAULT_LEVEL_MIX, value_parser = applog::parse_level_mix)]
+    app_log_level_mix: applog::LevelMix,
+
+    /// Rate multipliers per day of the week (UTC) applied to every generator,
+    /// such as `mon=1.4,sat=0.5,sun=0.4`. Days left out are unchanged.
+    #[arg(long, value_parser = shape::parse_weekday_rates)]
+    weekday_rates: Option<shape::WeekdayRates>,
+
+    /// How far a diurnal curve swings every generator's rate above and below
+    /// the day's rate, as a fraction from 0 to 1, peaking at 15:00 UTC and
+    /// lowest at 03:00 UTC. Zero, the default, leaves rates flat over the day.
+    #[arg(long, value_parser = shape::parse_diurnal_amplitude)]
+    diurnal_amplitude: Option<f64>,
+
+    /// Seconds after startup at which one service accidentally enables DEBUG
+    /// logging, multiplying application log volume by 20. Disabled by
+    /// default.
+    #[arg(long, default_value_t = 0)]
+    debug_flood_at_s: u64,
+
+    /// Seconds the DEBUG flood lasts. 0 leaves it running for the rest of the
+    /// run.
+    #[arg(long, default_value_t = 0)]
+    debug_flood_duration_s: u64,
+
+    /// Seconds after startup at which storedog starts tagging every request
+    /// with a unique `request_id`, causing a cardinality explosion. Disabled by
+    /// default.
+    #[arg(long, default_value_t = 0)]
+    cardinality_explosion_at_s: u64,
+
+    /// Seconds the cardinality explosion lasts. 0 leaves it running for the
+    /// rest of the run.
+    #[arg(long, default_value_t = 0)]
+    cardinality_explosion_duration_s: u64,
+
+    /// Seconds after startup at which storedog-payments slows down and the
+    /// failure cascades to the cart service and then the storefront. Disabled
+    /// by default.
+    #[arg(long, default_value_t = 0)]
+    dependency_cascade_at_s: u64,
+
+    /// Seconds the dependency cascade lasts. 0 leaves it running for the rest
+    /// of the run.
+    #[arg(long, default_value_t = 0)]
+    dependency_cascade_duration_s: u64,
+
+    /// Gigabytes of uncompressed events the `buffer-stress` scenario sends
+    /// when triggered.
+    #[arg(long, default_value_t = 1.0)]
+    buffer_stress_gb: f64,
+
+    /// Seconds after startup at which the compromised worker host starts
+    /// looking up algorithmically generated domains, showing up in the DNS
+    /// logs. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    dga_lookups_at_s: u64,
+
+    /// Seconds the DGA lookups last. 0 leaves them running for the rest of
+    /// the run.
+    #[arg(long, default_value_t = 0)]
+    dga_lookups_duration_s: u64,
+
+    /// Seconds after startup at which one employee account starts
+    /// downloading the customer export over FTP from outside the network.
+    /// Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    bulk_download_at_s: u64,
+
+    /// Seconds the bulk download lasts. 0 leaves it running for the rest of
+    /// the run.
+    #[arg(long, default_value_t = 0)]
+    bulk_download_duration_s: u64,
+
+    /// Seconds after startup at which one employee's VPN account starts
+    /// connecting from two distant countries within minutes. Disabled by
+    /// default.
+    #[arg(long, default_value_t = 0)]
+    impossible_travel_at_s: u64,
+
+    /// Seconds the impossible travel lasts. 0 leaves it running for the rest
+    /// of the run.
+    #[arg(long, default_value_t = 0)]
+    impossible_travel_duration_s: u64,
+
+    /// Seconds after startup at which the walk-in cooler's temperature sensor
+    /// gets stuck on one reading. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    stuck_sensor_at_s: u64,
+
+    /// Seconds the sensor stays stuck. 0 leaves it stuck for the rest of the
+    /// run.
+    #[arg(long, default_value_t = 0)]
+    stuck_sensor_duration_s: u64,
+
+    /// Percentage of events to tag with `sampled:true`, for lessons on
+    /// Vector's `sample` transform. Disabled by default.
+    #[arg(long, default_value_t = 0.0, value_parser = percent::parse)]
+    sample_tag_percent: f64,
+
+    /// Percentage of events to give a deliberately wrong `status`, as a
+    /// normalization exercise. Disabled by default.
+    #[arg(long, default_value_t = 0.0, value_parser = percent::parse)]
+    status_mismatch_percent: f64,
+
+    /// URL to POST to whenever a scenario phase begins or ends, with the
+    /// ground truth for that phase. Disabled by default.
+    #[arg(long)]
+    progress_webhook_url: Option<String>,
+
+    /// Seed for generated content, to make runs reproducible. Random by
+    /// default.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Directory or tarball of a scenario pack to load settings and a trigger
+    /// timeline from, or the name of a built-in pack such as
+    /// `storedog-incident-day`.
+    /// Options given on the command line override the pack's, and repeatable
+    /// options add to them.
+    #[arg(long)]
+    scenario_pack: Option<String>,
+
+    /// Path to write the answer key to, recording incident timings and exact
+    /// event counts for graders.
+    #[arg(long)]
+    answer_key_path: Option<String>,
+
+    /// Read single-key anomaly triggers from stdin.
+    #[arg(long)]
+    keyboard_triggers: bool,
+
+    /// Address to serve the admin API on, e.g. `127.0.0.1:8686`. Disabled by
+    /// default.
+    #[arg(long)]
+    admin_address: Option<SocketAddr>,
+
+    /// Address to serve the admin API over gRPC on, e.g. `127.0.0.1:8687`,
+    /// for orchestrators controlling many instances. The service is defined
+    /// in `proto/admin.proto`. Disabled by default.
+    #[arg(long)]
+    admin_grpc_address: Option<SocketAddr>,
+
+    /// Embed an anomaly in another generator's stream, as
+    /// `<generator>=<anomaly generator>:<one in>`: with
+    /// `storedog-ok=storedog-leak:10000`, one in 10,000 storedog-ok events is
+    /// a leak instead. May be repeated.
+    #[arg(long, value_parser = embed::parse)]
+    embed_anomaly: Vec<embed::Spec>,
+
+    /// Start a scenario at a random time within a window, as
+    /// `<scenario>=<from>-<to>`: with `card-leak=10m-40m`, the card leak
+    /// happens somewhere between minutes 10 and 40. Overrides the scenario's
+    /// start option, and the time is recorded in the answer key. May be
+    /// repeated.
+    #[arg(long, value_parser = placement::parse)]
+    scenario_window: Vec<placement::Window>,
+
+    /// Admin API address of a worker instance to coordinate. Every scenario
+    /// this instance starts or stops is forwarded to its workers. May be
+    /// repeated.
+    #[arg(long)]
+    worker: Vec<SocketAddr>,
+
+    /// Generate one slice of the configured rates as a worker, such as `2/3`
+    /// for the second of three workers.
+    #[arg(long, value_parser = cluster::parse_slice)]
+    slice: Option<cluster::Slice>,
+
+    /// Seconds over which generators ramp up from 5% of their configured
+    /// rates, after which a steady state marker event is emitted. Disabled by
+    /// default.
+    #[arg(long, default_value_t = 0)]
+    warmup_s: u64,
+
+    /// Seconds between heartbeat events, one per generator, with how many
+    /// events it has emitted and the timestamp of the last, for completeness
+    /// checks. Disabled by default.
+    #[arg(long, default_value_t = 0)]
+    heartbeat_interval_s: u64,
+
+    /// How many events a generator may send at once to catch up after falling
+    /// behind its rate. Defaults to 10ms worth of events at each generator's
+    /// rate, and at least one.
+    #[arg(long)]
+    rate_limit_burst: Option<usize>,
+
+    /// How many events the queue between the generators and the sender holds.
+    #[arg(long, default_value_t = queue::DEFAULT_DEPTH)]
+    queue_depth: usize,
+
+    /// What happens when the queue is full: generators wait for room and fall
+    /// behind their rates, or the queue drops its oldest event or the new one.
+    /// Dropped events are counted in the stats.
+    #[arg(long, value_enum, default_value_t = queue::Policy::Block)]
+    queue_policy: queue::Policy,
+
     /// Batch size for sending to Vector.
     #[arg(long, default_value_t = 5)]
     sender_batch_size: usize,
@@ -75,40 +698,245 @@ struct Args {
     sender_batch_timeout_s: u64,
 }
 
+/// Builds one or more events; an array return value is sent as separate
+/// events.
+type Generator = fn() -> serde_json::Value;
+
+/// Names of every generator, as used for stats and per-generator overrides.
+const GENERATORS: [&str; 26] = [
+    "storedog-ok",
+    "storedog-error",
+    "storedog-leak",
+    "applog",
+    "vpc-accept",
+    "vpc-ssh-attack",
+    "vpc-mining",
+    "billing",
+    "billing-csv",
+    "pos",
+    "soap-gateway",
+    "windows-dns",
+    "ftp-xfer",
+    "vpn",
+    sso::GENERATOR,
+    payments::GENERATOR,
+    iot::GENERATOR,
+    snmp::GENERATOR,
+    netflow::GENERATOR,
+    noise::GENERATOR,
+    macos::GENERATOR,
+    falco::GENERATOR,
+    "feature-flags",
+    "markers",
+    heartbeat::GENERATOR,
+    buffer::GENERATOR,
+];
+
+/// These simple attributes are needed for the Datadog API as implemented by
+/// Vector, so we add them to every message. The hostname may differ between
+/// calls if the generator simulates several hosts.
+fn required_attributes(generator: &str, simulated_host: Option<&str>) -> serde_json::Value {
+    return json!({
+        "ddsource": identity::ddsource(generator),
+        "hostname": identity::hostname(generator, simulated_host),
+        "status": "INFO",
+        "ddtags": "kube_namespace:test",
+    });
+}
+
+/// Merges the required attributes into an event from `generator` and
+/// timestamps it. Tags set by the generator are kept alongside the required
+/// ones, and so is its status.
+fn stamp(generator: &str, val: &mut serde_json::Value, needed: &serde_json::Value) {
+    let own_tags = val["ddtags"].as_str().map(str::to_owned);
+    let own_status = val["status"].take();
+    merge(val, needed);
+    if let (Some(own), Some(required)) = (own_tags, needed["ddtags"].as_str()) {
+        val["ddtags"] = json!(format!("{},{}", required, own));
+    }
+    if !own_status.is_null() {
+        val["status"] = own_status;
+    }
+    severity::mismatch(generator, val);
+    sampling::label(val);
+    val["timestamp"] = json!(clock::now().timestamp_micros() / 1000);
+}
+
+/// Stamps an event from `generator` with the attributes every event needs,
+/// its timestamp and its sequence number. Events from a simulated host get
+/// its platform's `os` metadata.
+fn prepare(generator: &'static str, value: &mut serde_json::Value) {
+    let simulated_host = value["hostname"].as_str().map(str::to_owned);
+    if let Some(host) = simulated_host.as_deref().and_then(hosts::find) {
+        if value["os"].is_null() {
+            value["os"] = host.platform.metadata();
+        }
+    }
+    stamp(
+        generator,
+        value,
+        &required_attributes(generator, simulated_host.as_deref()),
+    );
+    sequence::number(generator, value);
+}
+
+/// Stamps and queues a single event from `generator`, counting it in the
+/// generator's stats. Returns false if the sender has gone away.
+async fn emit(
+    tx: &tokio::sync::mpsc::Sender<Event>,
+    generator: &'static str,
+    mut value: serde_json::Value,
+) -> bool {
+    prepare(generator, &mut value);
+    let counters = stats::counters(generator);
+    stats::add(&counters.generated, 1);
+    return queue::send(tx, Event { counters, value }).await;
+}
+
+/// Every generator sent through the queue, with its rate.
+fn sources(args: &Args) -> Vec<(&'static str, usize, Generator)> {
+    return vec![
+        ("storedog-ok", args.http_log_rate_limit_per_s, storedog::ok),
+        (
+            "storedog-error",
+            args.http_log_error_rate_limit_per_s,
+            storedog::error,
+        ),
+        (
+            "storedog-leak",
+            args.http_log_leak_rate_limit_per_s,
+            storedog::leak,
+        ),
+        ("applog", args.app_log_rate_limit_per_s, applog::generate),
+        ("vpc-accept", args.vpc_log_rate_limit_per_s, vpc::accept),
+        (
+            "vpc-ssh-attack",
+            args.vpc_log_attack_rate_limit_per_s,
+            vpc::ssh_attack,
+        ),
+        (
+            "vpc-mining",
+            args.vpc_log_mining_rate_limit_per_s,
+            vpc::mining,
+        ),
+        (
+            "billing",
+            args.billing_log_rate_limit_per_s,
+            billing::generate,
+        ),
+        (
+            "billing-csv",
+            args.billing_csv_rate_limit_per_s,
+            billing::generate_csv,
+        ),
+        ("pos", args.pos_log_rate_limit_per_s, pos::generate),
+        (
+            "soap-gateway",
+            args.soap_log_rate_limit_per_s,
+            soap::generate,
+        ),
+        ("windows-dns", args.dns_log_rate_limit_per_s, dns::generate),
+        ("ftp-xfer", args.ftp_log_rate_limit_per_s, ftp::generate),
+        (
+            noise::GENERATOR,
+            args.noise_log_rate_limit_per_s,
+            noise::generate,
+        ),
+        ("vpn", args.vpn_log_rate_limit_per_s, vpn::generate),
+        (sso::GENERATOR, args.sso_log_rate_limit_per_s, sso::generate),
+        (
+            macos::GENERATOR,
+            args.macos_log_rate_limit_per_s,
+            macos::generate,
+        ),
+        (
+            falco::GENERATOR,
+            args.falco_log_rate_limit_per_s,
+            falco::generate,
+        ),
+    ];
+}
+
+/// Every scheduled incident, with when it starts and how long it lasts.
+fn incidents(args: &Args) -> Vec<(&'static Incident, u64, u64)> {
+    let incidents = vec![
+        (
+            &mining::INCIDENT,
+            args.crypto_mining_at_s,
+            args.crypto_mining_duration_s,
+        ),
+        (
+            &applog::FLOOD,
+            args.debug_flood_at_s,
+            args.debug_flood_duration_s,
+        ),
+        (
+            &cardinality::INCIDENT,
+            args.cardinality_explosion_at_s,
+            args.cardinality_explosion_duration_s,
+        ),
+        (
+            &cascade::INCIDENT,
+            args.dependency_cascade_at_s,
+            args.dependency_cascade_duration_s,
+        ),
+        (
+            &dns::INCIDENT,
+            args.dga_lookups_at_s,
+            args.dga_lookups_duration_s,
+        ),
+        (
+            &ftp::INCIDENT,
+            args.bulk_download_at_s,
+            args.bulk_download_duration_s,
+        ),
+        (
+            &vpn::INCIDENT,
+            args.impossible_travel_at_s,
+            args.impossible_travel_duration_s,
+        ),
+        (
+            &iot::INCIDENT,
+            args.stuck_sensor_at_s,
+            args.stuck_sensor_duration_s,
+        ),
+    ];
+    // Placed scenarios start at their drawn time instead.
+    return incidents
+        .into_iter()
+        .map(|(incident, at_s, duration_s)| {
+            let at_s = match trigger::scenario_of(incident) {
+                Some(scenario) => placement::at_s(scenario, at_s),
+                None => at_s,
+            };
+            return (incident, at_s, duration_s);
+        })
+        .collect();
+}
+
 fn send_log(
-    tx: &tokio::sync::mpsc::Sender<serde_json::Value>,
+    tx: &tokio::sync::mpsc::Sender<Event>,
+    name: &'static str,
     rate_limit_per_s: usize,
-    generator: fn() -> serde_json::Value,
+    generator: Generator,
 ) {
-    // The rate limiters don't support 0-values, so we just don't create the
-    // logger if a zero is specified.
+    // A zero rate disables the generator.
+    let rate_limit_per_s = cluster::share(rate_limit_per_s);
     if rate_limit_per_s == 0 {
         return;
     }
 
-    let rate_limiter = RateLimiter::builder()
-        .max(rate_limit_per_s * 100)
-        .initial(0)
-        .refill(rate_limit_per_s * 1.01 as usize)
-        .interval(time::Duration::from_millis(1000))
-        .build();
+    let mut pacer = pacing::for_rate(rate_limit_per_s);
     let tx2 = tx.clone();
 
-    // These simple attributes are needed for the Datadog API as
-    // implemented by Vector, so we add them to every message.
-    let hostname = gethostname().into_string().expect("could not get hostname");
-    let needed = json!({
-        "ddsource": "dynamo",
-        "hostname": hostname,
-        "status": "INFO",
-        "ddtags": "kube_namespace:test",
-    });
-
     tokio::spawn(async move {
         loop {
-            rate_limiter.acquire_one().await;
+            pacer.acquire().await;
+            if !warmup::admit() {
+                continue;
+            }
 
-            let mut v = generator();
+            let mut v = embed::generate(name, generator);
             if !v.is_array() {
                 v = json!([v]);
             }
@@ -116,129 +944,243 @@ fn send_log(
             let vs = v
                 .as_array_mut()
                 .expect("JSON returned from generator should be an array");
-            for mut val in vs {
-                merge(&mut val, &needed);
-
-                val["timestamp"] = json!(Utc::now().timestamp_micros() / 1000);
-                match tx2.send(val.to_owned()).await {
-                    Ok(_) => {}
-                    Err(_) => {
-                        break;
-                    }
+            for val in vs {
+                if !emit(&tx2, name, val.take()).await {
+                    break;
                 }
             }
         }
     });
 }
 
-fn generate_apache_log_line(method: &str, status: usize) -> String {
-    let addr = internet::ipv4_address();
-    let username = internet::username();
-
-    let ts = Utc::now().format("%d/%b/%G:%H:%M:%S %z");
-
-    // TODO: handle time generation
-    return format!(
-        "{} - {} [{}] \"{} /{} {}\" {} {}",
-        addr,
-        username,
-        ts,
-        method,
-        company::buzzword(),
-        "HTTP/1.1",
-        status,
-        1024
-    );
-}
+/// Applies the options that shape generated content.
+fn configure(args: &Args) {
+    let identity = identity::Identity {
+        hostnames: args.hostname.clone(),
+        ddsource: args.ddsource.clone(),
+        generator_hostnames: args.generator_hostname.iter().cloned().collect(),
+        generator_ddsources: args.generator_ddsource.iter().cloned().collect(),
+    };
+    if let Err(e) = identity::configure(identity, &GENERATORS) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 
-fn generate_vpc_flow_line(action: &str, status: &str, port: usize) -> String {
-    let mut rng = rand::thread_rng();
-
-    let start = Utc::now()
-        .checked_sub_signed(chrono::Duration::seconds(rng.gen_range(5..30)))
-        .expect("could not create start time for log");
-    let end = Utc::now();
-
-    let client_ip = internet::ipv4_address();
-    let server_ip = internet::ipv4_address();
-    let client_port = rng.gen_range(30000..78000);
-    let request_bytes = rng.gen_range(230..9000);
-    let request_packets = rng.gen_range(5..1000);
-
-    return format!(
-        "{} {} {} {} {} {} {} {} {} {} {} {} {} {}",
-        2,
-        "1234567890",
-        "eni-sdvu4NphZxGvp1MDz",
-        client_ip,
-        server_ip,
-        client_port,
-        port,
-        6,
-        request_packets,
-        request_bytes,
-        start.timestamp(),
-        end.timestamp(),
-        action,
-        status,
-    );
+    if let Some(slice) = args.slice {
+        cluster::set_slice(slice);
+    }
+    if !args.worker.is_empty() {
+        cluster::set_workers(args.worker.clone());
+    }
+    if let Some(seed) = args.seed {
+        random::set_seed(cluster::seed(seed));
+    }
+    // Every slice places scenarios from the same seed, so they agree.
+    if let Err(e) = placement::configure(&args.scenario_window, args.seed) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let generators: Vec<(&'static str, Generator)> = sources(args)
+        .into_iter()
+        .map(|(name, _, generator)| (name, generator))
+        .collect();
+    if let Err(e) = embed::configure(&args.embed_anomaly, &generators) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    sampling::set_percent(args.sample_tag_percent);
+    severity::set_mismatch_percent(args.status_mismatch_percent);
+    if let Some(url) = args.progress_webhook_url.clone() {
+        webhook::set_url(url);
+    }
+    applog::set_level_mix(args.app_log_level_mix.clone());
+    buffer::set_target_gb(args.buffer_stress_gb);
+    attackers::set_ranges(args.attacker_ranges.clone());
+    pos::set_delimiter(args.pos_log_delimiter);
+    // Files, whether written by the file sink or by an export, repeat the CSV
+    // header periodically.
+    billing::set_csv_periodic_headers(args.sink == Sink::File || args.command.is_some());
+    if let Some(burst) = args.rate_limit_burst {
+        pacing::set_burst(burst);
+    }
+    if let Some(rates) = args.weekday_rates.clone() {
+        shape::set_weekday_rates(rates);
+    }
+    if let Some(amplitude) = args.diurnal_amplitude {
+        shape::set_diurnal_amplitude(amplitude);
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
-
-    let logs_client_address = format!("{}/api/v2/logs", args.datadog_agent_target);
-    let logs_client = reqwest::Client::builder()
-        .gzip(true)
-        .build()
-        .expect("could not initialize client");
-    let (tx, mut rx) = mpsc::channel(32);
-
-    send_log(&tx, args.http_log_rate_limit_per_s, || {
-        return json!({
-            "message": generate_apache_log_line("GET", 200),
-            "service": "storedog",
-        });
-    });
+    let command_line = match pack::expand(std::env::args().collect()) {
+        Ok(command_line) => command_line,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let args = Args::parse_from(command_line);
+    // Replays run through the sender like a live run.
+    let command = args
+        .command
+        .as_ref()
+        .filter(|c| !matches!(c, Command::Serve { .. }));
+    if let Some(command) = command {
+        let result = match command {
+            Command::ExportEnrichment { dir } => enrichment::export(dir),
+            Command::Export {
+                duration,
+                out,
+                backfill,
+            } => {
+                configure(&args);
+                bundle::export(&bundle::Run {
+                    duration_s: *duration,
+                    backfill: *backfill,
+                    encoding: args.encoding.unwrap_or(Encoding::Raw),
+                    sources: sources(&args),
+                    incidents: incidents(&args),
+                    one_offs: placement::one_offs(),
+                    flag_regression: (
+                        placement::at_s("latency-regression", args.feature_flag_regression_at_s),
+                        args.feature_flag_rollback_after_s,
+                    ),
+                    out: out.clone(),
+                })
+            }
+            Command::Config { command } => match command {
+                ConfigCommand::Validate { pack } => config::validate(pack),
+                ConfigCommand::Schema => config::print_schema(),
+            },
+            Command::Exercises { dir } => {
+                configure(&args);
+                exercises::write(dir, &sources(&args))
+            }
+            Command::Schema { generator, version } => schema::print(generator.as_deref(), *version),
+            Command::Selftest { update } => selftest::run(update.as_deref()),
+            Command::LatencyProbe { file, interval_s } => {
+                latency::run(file.as_deref(), *interval_s)
+            }
+            Command::VerifySequence => sequence::verify(std::io::stdin().lock()),
+            Command::ValidateOutput => validate::run(std::io::stdin().lock()),
+            Command::Trigger {
+                scenario,
+                stop,
+                admin_address,
+            } => trigger::remote(*admin_address, scenario, *stop).await,
+            Command::Serve { .. } => unreachable!("replays are run below"),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    send_log(&tx, args.http_log_error_rate_limit_per_s, || {
-        return json!({
-            "message": generate_apache_log_line("GET", 500),
-            "service": "storedog",
-        });
-    });
+    let problems = preflight::check(&args);
+    if !problems.is_empty() {
+        for problem in problems {
+            eprintln!("{}", problem);
+        }
+        std::process::exit(1);
+    }
 
-    send_log(&tx, args.http_log_leak_rate_limit_per_s, || {
-        return json!([
-            {
-                "message": generate_apache_log_line("POST", 504),
-                "service": "storedog",
-            },
-            {
-                "message": format!("ERROR could not charge card {}!", payment::credit_card_number()),
-                "service": "storedog",
-            },
-        ]);
-    });
+    configure(&args);
 
-    send_log(&tx, args.vpc_log_rate_limit_per_s, || {
-        return json!([{
-            "message": generate_vpc_flow_line("ACCEPT", "OK", 443),
-            "service": "aws.vpc_flow_logs",
-        }]);
+    if let Some(path) = &args.answer_key_path {
+        answer_key::spawn_writer(path);
+    }
+    let answer_key_path = args.answer_key_path.clone();
+    tokio::spawn(async move {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("could not listen for interrupts");
+        println!("{}", stats::summary());
+        if let Some(path) = answer_key_path {
+            answer_key::write(&path);
+        }
+        std::process::exit(0);
     });
 
-    send_log(&tx, args.vpc_log_attack_rate_limit_per_s, || {
-        return json!({
-            "message": generate_vpc_flow_line("REJECT", "OK", 22),
-            "service": "aws.vpc_flow_logs",
-        });
-    });
+    let encoding = args.encoding.unwrap_or(args.sink.default_encoding());
+    if let Err(e) = args.sink.check_encoding(encoding) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    let sender = sink::Sender {
+        sink: args.sink,
+        encoding,
+        compression: args.compression,
+        datadog_agent_address: format!("{}/api/v2/logs", args.datadog_agent_target),
+        http_target: args.http_target.clone(),
+        file_path: args.file_path.clone(),
+        mimic_agents: args.mimic_agents,
+        header_fields: args.http_header_field.clone(),
+        otlp_target: args.otlp_target.clone(),
+        otlp_stress: match args.otlp_stress {
+            true => Some(otlp::StressMode {
+                max_bytes: args.otlp_stress_max_bytes,
+            }),
+            false => None,
+        },
+        logs_client: reqwest::Client::builder()
+            .gzip(true)
+            .build()
+            .expect("could not initialize client"),
+        otlp_client: reqwest::Client::builder()
+            .http2_prior_knowledge()
+            .build()
+            .expect("could not initialize OTLP client"),
+    };
+    let (tx, mut rx) = queue::new(args.queue_depth, args.queue_policy);
+
+    if let Some(address) = args.admin_address {
+        admin::serve(address, &tx);
+    }
+    if let Some(address) = args.admin_grpc_address {
+        grpc::serve(address, &tx);
+    }
+    let mut replayed = None;
+    if let Some(Command::Serve { dir, speed }) = &args.command {
+        match replay::start(tx, dir, *speed) {
+            Ok(done) => replayed = Some(done),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        start(&args, &tx);
+    }
 
     let stream = stream! {
-        while let Some(message) = rx.recv().await {
-            yield message;
+        // A replay ends once it has queued every event, even though the
+        // admin APIs may still hold senders: the queue is closed to new
+        // events and drained.
+        let replayed = async move {
+            match replayed {
+                Some(done) => {
+                    let _ = done.await;
+                }
+                None => std::future::pending().await,
+            }
+        };
+        tokio::pin!(replayed);
+        let mut closed = false;
+        loop {
+            let message = tokio::select! {
+                message = rx.recv() => message,
+                _ = &mut replayed, if !closed => {
+                    rx.close();
+                    closed = true;
+                    continue;
+                }
+            };
+            match message {
+                Some(message) => yield message,
+                None => break,
+            }
         }
     };
 
@@ -246,18 +1188,50 @@ async fn main() {
         args.sender_batch_size,
         Duration::from_secs(args.sender_batch_timeout_s),
     ));
-    while let Some(message) = pinned.next().await {
-        let m = json!(message);
-        match logs_client
-            .post(&logs_client_address)
-            .body(m.to_string())
-            .send()
-            .await
-        {
-            Ok(_) => {}
-            Err(e) => {
-                println!("Could not connect to Vector: {}", e);
-            }
-        };
+    while let Some(batch) = pinned.next().await {
+        sender.send(batch).await;
+    }
+    println!("{}", stats::summary());
+    if let Some(path) = &args.answer_key_path {
+        answer_key::write(path);
+    }
+}
+
+/// Starts every generator and schedules every incident of a live run.
+fn start(args: &Args, tx: &mpsc::Sender<Event>) {
+    if args.keyboard_triggers {
+        trigger::listen_for_keys(tx);
+    }
+    warmup::start(tx, args.warmup_s);
+    heartbeat::start(tx, args.heartbeat_interval_s);
+    pack::start(tx);
+
+    // Before the VPC generators start, so that their first flows are exported.
+    netflow::start(args.netflow_target.clone(), args.netflow_format);
+    for (name, rate_limit_per_s, generator) in sources(args) {
+        send_log(tx, name, rate_limit_per_s, generator);
+    }
+    payments::start(
+        args.protobuf_tcp_target.clone(),
+        cluster::share(args.protobuf_log_rate_limit_per_s),
+    );
+    iot::start(
+        args.mqtt_target.clone(),
+        cluster::share(args.iot_log_rate_limit_per_s),
+    );
+    snmp::start(
+        args.snmp_trap_target.clone(),
+        cluster::share(args.snmp_trap_rate_limit_per_s),
+    );
+
+    flags::schedule_regression(
+        tx,
+        placement::at_s("latency-regression", args.feature_flag_regression_at_s),
+        args.feature_flag_rollback_after_s,
+    );
+    placement::schedule(tx);
+
+    for (incident, at_s, duration_s) in incidents(args) {
+        incident.schedule(at_s, duration_s);
     }
 }
@@ -0,0 +1,36 @@
+/*!
+The crypto mining incident.
+
+At a configured point in the run a single host is compromised and starts
+talking to a mining pool over the Stratum port. The incident shows up in two
+places: as flow logs in the VPC stream, and as a cost spike for GPU compute in
+the billing stream, tying the security and FinOps storylines together. The
+miner's process and its connections also show up in the Falco stream.
+*/
+use serde_json::json;
+
+use crate::hosts;
+use crate::incident::Incident;
+
+pub const COMPROMISED_HOST_IP: &str = "10.0.3.17";
+pub const COMPROMISED_INSTANCE_ID: &str = "i-0b7c1e94d2f3a5860";
+pub const POOL_IP: &str = "45.9.148.125";
+pub const POOL_PORT: usize = 3333;
+/// The miner, disguised as a kernel thread.
+pub const MINER_PATH: &str = "/var/tmp/.cache/kswapd0";
+
+pub static INCIDENT: Incident = Incident::new("crypto_mining", details);
+
+pub fn active() -> bool {
+    return INCIDENT.active();
+}
+
+fn details() -> serde_json::Value {
+    return json!({
+        "compromised_host_ip": COMPROMISED_HOST_IP,
+        "compromised_hostname": hosts::COMPROMISED.hostname,
+        "compromised_instance_id": COMPROMISED_INSTANCE_ID,
+        "pool": format!("{}:{}", POOL_IP, POOL_PORT),
+        "miner_path": MINER_PATH,
+    });
+}
@@ -0,0 +1,289 @@
+/*!
+NetFlow v5 and IPFIX export of the VPC's flows.
+
+Network observability labs often include a flow collector alongside the log
+pipeline. With `--netflow-target`, every flow the VPC flow log generators
+produce is also exported to a collector over UDP, as NetFlow v5 records or,
+with `--netflow-format ipfix`, IPFIX data records. The same connections show
+up in both places, so students can match a flow log line to its record.
+
+Flows are batched into a packet of up to 30 records, or whatever has arrived
+after a second. NetFlow v5 has no field for the VPC's action, so rejected
+flows only show as a lone SYN in their TCP flags; IPFIX records carry
+`forwardingStatus` as well. IPFIX packets repeat the template every time, so
+a collector started mid-run decodes the next packet it receives.
+*/
+use std::net::Ipv4Addr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::{clock, stats};
+
+pub const GENERATOR: &str = "netflow";
+/// Records per packet, the most NetFlow v5 allows.
+const MAX_RECORDS: usize = 30;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Flows waiting to be exported. More than this and new flows are dropped.
+const QUEUE_DEPTH: usize = 1024;
+/// How long the simulated router has been up when dynamo starts, so that
+/// flows that began just before aren't before boot.
+const UPTIME_AT_START_MS: i64 = 3_600_000;
+const IPFIX_TEMPLATE_ID: u16 = 256;
+/// IPFIX information elements in each data record, with their lengths.
+const IPFIX_FIELDS: [(u16, u16); 11] = [
+    (8, 4),   // sourceIPv4Address
+    (12, 4),  // destinationIPv4Address
+    (7, 2),   // sourceTransportPort
+    (11, 2),  // destinationTransportPort
+    (4, 1),   // protocolIdentifier
+    (6, 1),   // tcpControlBits
+    (2, 8),   // packetDeltaCount
+    (1, 8),   // octetDeltaCount
+    (150, 4), // flowStartSeconds
+    (151, 4), // flowEndSeconds
+    (89, 1),  // forwardingStatus
+];
+/// TCP flags of a completed connection, and of one that was never answered.
+const FLAGS_ACCEPTED: u8 = 0x1b; // FIN, SYN, PSH, ACK
+const FLAGS_REJECTED: u8 = 0x02; // SYN
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    V5,
+    Ipfix,
+}
+
+pub struct Flow {
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub protocol: u8,
+    pub packets: u32,
+    pub bytes: u32,
+    pub start_s: i64,
+    pub end_s: i64,
+    pub accepted: bool,
+}
+
+static FLOWS: OnceLock<Sender<Flow>> = OnceLock::new();
+
+/// Whether flows are being exported, so generators can skip building them.
+pub fn enabled() -> bool {
+    return FLOWS.get().is_some();
+}
+
+/// Queues a flow for export.
+pub fn observe(flow: Flow) {
+    let Some(tx) = FLOWS.get() else {
+        return;
+    };
+    let counters = stats::counters(GENERATOR);
+    stats::add(&counters.generated, 1);
+    if tx.try_send(flow).is_err() {
+        stats::add(&counters.dropped, 1);
+    }
+}
+
+fn flags(flow: &Flow) -> u8 {
+    return if flow.accepted {
+        FLAGS_ACCEPTED
+    } else {
+        FLAGS_REJECTED
+    };
+}
+
+/// A NetFlow v5 packet. `boot_ms` is when the simulated router started, for
+/// the uptimes that flow times are given in.
+fn v5(flows: &[Flow], sequence: u32, boot_ms: i64) -> Vec<u8> {
+    let now = clock::now();
+    let uptime = |ms: i64| (ms - boot_ms).max(0) as u32;
+
+    let mut buf = vec![];
+    buf.extend_from_slice(&5u16.to_be_bytes());
+    buf.extend_from_slice(&(flows.len() as u16).to_be_bytes());
+    buf.extend_from_slice(&uptime(now.timestamp_millis()).to_be_bytes());
+    buf.extend_from_slice(&(now.timestamp() as u32).to_be_bytes());
+    buf.extend_from_slice(&now.timestamp_subsec_nanos().to_be_bytes());
+    buf.extend_from_slice(&sequence.to_be_bytes());
+    buf.extend_from_slice(&[0, 0]); // engine type and ID
+    buf.extend_from_slice(&0u16.to_be_bytes()); // not sampled
+
+    for flow in flows {
+        buf.extend_from_slice(&flow.src.octets());
+        buf.extend_from_slice(&flow.dst.octets());
+        buf.extend_from_slice(&[0; 4]); // next hop
+        buf.extend_from_slice(&[0; 4]); // input and output interfaces
+        buf.extend_from_slice(&flow.packets.to_be_bytes());
+        buf.extend_from_slice(&flow.bytes.to_be_bytes());
+        buf.extend_from_slice(&uptime(flow.start_s * 1000).to_be_bytes());
+        buf.extend_from_slice(&uptime(flow.end_s * 1000).to_be_bytes());
+        buf.extend_from_slice(&flow.src_port.to_be_bytes());
+        buf.extend_from_slice(&flow.dst_port.to_be_bytes());
+        buf.extend_from_slice(&[0, flags(flow), flow.protocol, 0]);
+        buf.extend_from_slice(&[0; 8]); // AS numbers, masks and padding
+    }
+    return buf;
+}
+
+/// Appends an IPFIX set with its header.
+fn set(buf: &mut Vec<u8>, id: u16, contents: &[u8]) {
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf.extend_from_slice(&(contents.len() as u16 + 4).to_be_bytes());
+    buf.extend_from_slice(contents);
+}
+
+/// An IPFIX message with the template and a data set.
+fn ipfix(flows: &[Flow], sequence: u32) -> Vec<u8> {
+    let mut template = vec![];
+    template.extend_from_slice(&IPFIX_TEMPLATE_ID.to_be_bytes());
+    template.extend_from_slice(&(IPFIX_FIELDS.len() as u16).to_be_bytes());
+    for (id, len) in IPFIX_FIELDS {
+        template.extend_from_slice(&id.to_be_bytes());
+        template.extend_from_slice(&len.to_be_bytes());
+    }
+
+    let mut records = vec![];
+    for flow in flows {
+        records.extend_from_slice(&flow.src.octets());
+        records.extend_from_slice(&flow.dst.octets());
+        records.extend_from_slice(&flow.src_port.to_be_bytes());
+        records.extend_from_slice(&flow.dst_port.to_be_bytes());
+        records.extend_from_slice(&[flow.protocol, flags(flow)]);
+        records.extend_from_slice(&u64::from(flow.packets).to_be_bytes());
+        records.extend_from_slice(&u64::from(flow.bytes).to_be_bytes());
+        records.extend_from_slice(&(flow.start_s as u32).to_be_bytes());
+        records.extend_from_slice(&(flow.end_s as u32).to_be_bytes());
+        // Forwarded or dropped, with an unknown reason.
+        records.push(if flow.accepted { 0x40 } else { 0x80 });
+    }
+
+    let mut sets = vec![];
+    set(&mut sets, 2, &template);
+    set(&mut sets, IPFIX_TEMPLATE_ID, &records);
+
+    let mut buf = vec![];
+    buf.extend_from_slice(&10u16.to_be_bytes());
+    buf.extend_from_slice(&(sets.len() as u16 + 16).to_be_bytes());
+    buf.extend_from_slice(&(clock::now().timestamp() as u32).to_be_bytes());
+    buf.extend_from_slice(&sequence.to_be_bytes());
+    buf.extend_from_slice(&0u32.to_be_bytes()); // observation domain
+    buf.extend_from_slice(&sets);
+    return buf;
+}
+
+/// Exports flows to the collector at `target`, if there is one.
+pub fn start(target: Option<String>, format: Format) {
+    let Some(target) = target else {
+        return;
+    };
+    let (tx, mut rx) = mpsc::channel(QUEUE_DEPTH);
+    FLOWS
+        .set(tx)
+        .expect("the flow exporter should only be started once");
+
+    let counters = stats::counters(GENERATOR);
+    let boot_ms = clock::now().timestamp_millis() - UPTIME_AT_START_MS;
+    tokio::spawn(async move {
+        let socket = match UdpSocket::bind("0.0.0.0:0").await {
+            Ok(socket) => socket,
+            Err(e) => {
+                println!("Could not open a socket for flow export: {}", e);
+                return;
+            }
+        };
+        // Both formats count records, not packets.
+        let mut sequence: u32 = 0;
+        let mut batch = vec![];
+        let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tokio::select! {
+                flow = rx.recv() => match flow {
+                    Some(flow) => {
+                        batch.push(flow);
+                        if batch.len() < MAX_RECORDS {
+                            continue;
+                        }
+                    }
+                    None => return,
+                },
+                _ = flush.tick() => {
+                    if batch.is_empty() {
+                        continue;
+                    }
+                }
+            }
+
+            let packet = match format {
+                Format::V5 => v5(&batch, sequence, boot_ms),
+                Format::Ipfix => ipfix(&batch, sequence),
+            };
+            let records = batch.len() as u64;
+            sequence = sequence.wrapping_add(records as u32);
+            batch.clear();
+            stats::add(&counters.bytes_uncompressed, packet.len() as u64);
+            stats::add(&counters.bytes_wire, packet.len() as u64);
+            match socket.send_to(&packet, &target).await {
+                Ok(_) => stats::add(&counters.sent, records),
+                Err(e) => {
+                    println!("Could not export flows to {}: {}", target, e);
+                    stats::add(&counters.dropped, records);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Flow;
+
+    fn flow(accepted: bool) -> Flow {
+        return Flow {
+            src: "203.0.113.7".parse().unwrap(),
+            dst: "10.0.1.11".parse().unwrap(),
+            src_port: 40000,
+            dst_port: 22,
+            protocol: 6,
+            packets: 5,
+            bytes: 300,
+            start_s: 1_680_350_390,
+            end_s: 1_680_350_400,
+            accepted,
+        };
+    }
+
+    #[test]
+    fn encodes_v5_records() {
+        let boot_ms = 1_680_350_000_000;
+        let packet = super::v5(&[flow(true), flow(false)], 7, boot_ms);
+        assert_eq!(packet.len(), 24 + 2 * 48);
+        assert_eq!(packet[..4], [0, 5, 0, 2]);
+        assert_eq!(packet[16..20], 7u32.to_be_bytes());
+
+        let record = &packet[24..72];
+        assert_eq!(record[..8], [203, 0, 113, 7, 10, 0, 1, 11]);
+        assert_eq!(record[24..28], 390_000u32.to_be_bytes());
+        assert_eq!(record[28..32], 400_000u32.to_be_bytes());
+        assert_eq!(record[32..36], [0x9c, 0x40, 0, 22]);
+        assert_eq!(record[37..39], [super::FLAGS_ACCEPTED, 6]);
+        assert_eq!(packet[72 + 37], super::FLAGS_REJECTED);
+    }
+
+    #[test]
+    fn encodes_ipfix_messages() {
+        let packet = super::ipfix(&[flow(false)], 0);
+        let template_set = 4 + 4 + 4 * super::IPFIX_FIELDS.len();
+        let record: u16 = super::IPFIX_FIELDS.iter().map(|(_, len)| len).sum();
+        assert_eq!(record, 39);
+        assert_eq!(packet.len(), 16 + template_set + 4 + record as usize);
+        assert_eq!(packet[..4], [0, 10, 0, packet.len() as u8]);
+        assert_eq!(packet[16..18], [0, 2]);
+        assert_eq!(packet[16 + template_set..][..2], [1, 0]);
+        assert_eq!(packet.last(), Some(&0x80));
+    }
+}
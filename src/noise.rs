@@ -0,0 +1,117 @@
+/*!
+Background noise from the fleet.
+
+Real log streams are mostly housekeeping: cron jobs starting, logrotate runs,
+kubelet chatter and load balancer health checks. None of it matters, but
+detection exercises against a stream without it are too easy, since anything
+that isn't a storedog request stands out. This generator mixes the four at a
+low rate, on by default.
+*/
+use serde_json::{self, json};
+
+use crate::{clock, hosts, random};
+
+pub const GENERATOR: &str = "noise";
+pub const DEFAULT_RATE: usize = 1;
+/// The load balancer address health checks come from.
+const HEALTH_CHECKER_IP: &str = "10.0.0.5";
+
+const CRON_JOBS: [(&str, &str); 4] = [
+    ("root", "cd / && run-parts --report /etc/cron.hourly"),
+    (
+        "root",
+        "test -x /usr/sbin/anacron || ( cd / && run-parts --report /etc/cron.daily )",
+    ),
+    ("root", "/usr/lib/sysstat/debian-sa1 1 1"),
+    (
+        "storedog",
+        "/opt/storedog/bin/cleanup-sessions > /dev/null 2>&1",
+    ),
+];
+const LOGROTATE: [&str; 3] = [
+    "Starting Rotate log files...",
+    "logrotate.service: Deactivated successfully.",
+    "Finished Rotate log files.",
+];
+/// Kubelet messages, after the klog header, and whether they are warnings.
+const KUBELET: [(&str, bool); 4] = [
+    (
+        "reconciler_common.go:258] \"operationExecutor.VerifyControllerAttachedVolume started for volume\" volume=\"kube-api-access\"",
+        false,
+    ),
+    (
+        "kubelet_volumes.go:160] \"Cleaned up orphaned pod volumes dir\" path=\"/var/lib/kubelet/pods\"",
+        false,
+    ),
+    (
+        "scope.go:117] \"RemoveContainer\" containerID=\"containerd://sha256\"",
+        false,
+    ),
+    (
+        "container_manager_linux.go:514] \"Failed to get system container stats\" err=\"failed to get cgroup stats for \\\"/system.slice/containerd.service\\\"\"",
+        true,
+    ),
+];
+
+/// Any Linux host, which all run cron and logrotate.
+fn linux_host() -> &'static hosts::Host {
+    let linux: Vec<&hosts::Host> = hosts::all()
+        .filter(|h| h.platform == hosts::Platform::Linux)
+        .collect();
+    let host: &&hosts::Host = random::pick(&linux);
+    return host;
+}
+
+fn cron() -> serde_json::Value {
+    let (user, command) = random::pick(&CRON_JOBS);
+    return json!({
+        "message": format!("CRON[{}]: ({}) CMD ({})", random::range(1000..60000), user, command),
+        "service": "cron",
+        "hostname": linux_host().hostname,
+    });
+}
+
+fn logrotate() -> serde_json::Value {
+    return json!({
+        "message": format!("systemd[1]: {}", random::pick(&LOGROTATE)),
+        "service": "logrotate",
+        "hostname": linux_host().hostname,
+    });
+}
+
+/// A kubelet line in klog's format, from one of the backend nodes.
+fn kubelet() -> serde_json::Value {
+    let (message, warning) = *random::pick(&KUBELET);
+    let now = clock::now();
+    return json!({
+        "message": format!(
+            "{}{} {} {:>7} {}",
+            if warning { 'W' } else { 'I' },
+            now.format("%m%d"),
+            now.format("%H:%M:%S%.6f"),
+            random::range(800..1200),
+            message,
+        ),
+        "service": "kubelet",
+        "hostname": random::pick(&hosts::BACKEND).hostname,
+        "status": if warning { "WARNING" } else { "INFO" },
+    });
+}
+
+/// A load balancer health check, in the storefront's own access log.
+fn health_check() -> serde_json::Value {
+    return json!({
+        "message": format!(
+            "{} - - [{}] \"GET /healthz HTTP/1.1\" 200 2",
+            HEALTH_CHECKER_IP,
+            clock::now().format("%d/%b/%Y:%H:%M:%S %z"),
+        ),
+        "service": "storedog",
+        "hostname": hosts::web().hostname,
+    });
+}
+
+pub fn generate() -> serde_json::Value {
+    let noise: [fn() -> serde_json::Value; 4] = [cron, logrotate, kubelet, health_check];
+    return random::pick(&noise)();
+}
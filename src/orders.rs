@@ -0,0 +1,115 @@
+/*!
+Business events from storedog's order pipeline.
+
+A share of successful storedog requests are checkouts, and every successful
+checkout produces an `order_placed` and a `payment_captured` event; now and
+then an earlier order is refunded. Because the events are generated together
+with the checkout requests, their volume tracks checkout traffic exactly.
+Totals go into the answer key, as the source of truth for lessons on deriving
+business metrics from logs.
+*/
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{self, json};
+
+use crate::{hosts, random};
+
+pub const CHECKOUT_PATH: &str = "/checkout";
+pub const SERVICE: &str = "storedog-orders";
+/// Chance that a successful storedog request is a checkout.
+const CHECKOUT_CHANCE: f64 = 0.1;
+/// Chance that a checkout is accompanied by a refund for an earlier order.
+const REFUND_CHANCE: f64 = 0.05;
+/// How many recent orders are remembered as candidates for refunds.
+const RECENT_ORDERS: usize = 100;
+
+static PLACED: AtomicU64 = AtomicU64::new(0);
+static CAPTURED_CENTS: AtomicU64 = AtomicU64::new(0);
+static REFUNDED: AtomicU64 = AtomicU64::new(0);
+static REFUNDED_CENTS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // Kept per thread so that seeded runs stay deterministic.
+    static RECENT: RefCell<VecDeque<Order>> = const { RefCell::new(VecDeque::new()) };
+}
+
+struct Order {
+    id: String,
+    customer: String,
+    cents: u64,
+}
+
+pub fn is_checkout() -> bool {
+    return random::chance(CHECKOUT_CHANCE);
+}
+
+fn amount(cents: u64) -> String {
+    return format!("{}.{:02}", cents / 100, cents % 100);
+}
+
+fn event(kind: &str, order: &Order, extra: &str, hostname: &str) -> serde_json::Value {
+    return json!({
+        "message": format!(
+            "{} order_id={} customer={} {}amount={} currency=USD",
+            kind,
+            order.id,
+            order.customer,
+            extra,
+            amount(order.cents),
+        ),
+        "service": SERVICE,
+        "hostname": hostname,
+    });
+}
+
+/// The business events for a checkout served by `hostname`.
+pub fn checkout_completed(hostname: &str) -> Vec<serde_json::Value> {
+    let order = Order {
+        id: random::uuid_v4(),
+        customer: random::username(),
+        cents: random::range(1500..25000),
+    };
+    let items = format!("items={} ", random::range(1..6));
+    let payments = hosts::backend("storedog-payments").hostname;
+    let mut events = vec![
+        event("order_placed", &order, &items, hostname),
+        event("payment_captured", &order, "", payments),
+    ];
+    PLACED.fetch_add(1, Ordering::Relaxed);
+    CAPTURED_CENTS.fetch_add(order.cents, Ordering::Relaxed);
+
+    let refund = RECENT.with(|recent| {
+        let mut recent = recent.borrow_mut();
+        let refund = if random::chance(REFUND_CHANCE) {
+            recent.pop_front()
+        } else {
+            None
+        };
+        recent.push_back(order);
+        if recent.len() > RECENT_ORDERS {
+            recent.pop_front();
+        }
+        return refund;
+    });
+    if let Some(refunded) = refund {
+        events.push(event("refund_issued", &refunded, "", payments));
+        REFUNDED.fetch_add(1, Ordering::Relaxed);
+        REFUNDED_CENTS.fetch_add(refunded.cents, Ordering::Relaxed);
+    }
+    return events;
+}
+
+pub fn summary() -> serde_json::Value {
+    let captured = CAPTURED_CENTS.load(Ordering::Relaxed);
+    let refunded = REFUNDED_CENTS.load(Ordering::Relaxed);
+    return json!({
+        "orders_placed": PLACED.load(Ordering::Relaxed),
+        "payments_captured": PLACED.load(Ordering::Relaxed),
+        "refunds_issued": REFUNDED.load(Ordering::Relaxed),
+        "captured_usd": amount(captured),
+        "refunded_usd": amount(refunded),
+        "net_revenue_usd": amount(captured - refunded),
+    });
+}
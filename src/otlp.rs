@@ -0,0 +1,332 @@
+/*!
+OpenTelemetry logs over gRPC, for Vector's `opentelemetry` source.
+
+Events are encoded as an `ExportLogsServiceRequest` by hand rather than through
+generated code. The service and hostname become resource attributes, the
+message becomes the log body, and every other field becomes a log attribute,
+with nested objects kept as key/value lists so students can practice
+flattening them.
+
+In stress mode each batch is spread over many resource and scope combinations
+and packed up to the size limit of a single gRPC message, to load test the
+source's decoding and attribute handling.
+*/
+use chrono::prelude::*;
+use serde_json::{self, json};
+
+use crate::failures::{self, Failure};
+use crate::proto;
+
+const EXPORT_PATH: &str = "/opentelemetry.proto.collector.logs.v1.LogsService/Export";
+
+/// Attributes that are mapped to dedicated OTLP fields instead of log
+/// attributes.
+const LIFTED: [&str; 5] = ["message", "service", "hostname", "status", "timestamp"];
+
+pub struct StressMode {
+    pub max_bytes: usize,
+}
+
+fn any_value(value: &serde_json::Value) -> Vec<u8> {
+    let mut buf = vec![];
+    match value {
+        serde_json::Value::String(s) => proto::string(&mut buf, 1, s),
+        serde_json::Value::Bool(b) => proto::boolean(&mut buf, 2, *b),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => proto::int64(&mut buf, 3, i),
+            None => proto::double(&mut buf, 4, n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::Array(values) => {
+            let mut array = vec![];
+            for v in values {
+                proto::message(&mut array, 1, &any_value(v));
+            }
+            proto::message(&mut buf, 5, &array);
+        }
+        serde_json::Value::Object(map) => {
+            let mut kvlist = vec![];
+            for (k, v) in map {
+                proto::message(&mut kvlist, 1, &key_value(k, v));
+            }
+            proto::message(&mut buf, 6, &kvlist);
+        }
+        serde_json::Value::Null => {}
+    }
+    return buf;
+}
+
+fn key_value(key: &str, value: &serde_json::Value) -> Vec<u8> {
+    let mut buf = vec![];
+    proto::string(&mut buf, 1, key);
+    proto::message(&mut buf, 2, &any_value(value));
+    return buf;
+}
+
+fn severity_number(status: &str) -> u64 {
+    return match status.to_ascii_uppercase().as_str() {
+        "DEBUG" => 5,
+        "WARN" | "WARNING" => 13,
+        "ERROR" => 17,
+        "CRITICAL" | "FATAL" => 21,
+        _ => 9,
+    };
+}
+
+fn log_record(event: &serde_json::Value) -> Vec<u8> {
+    let timestamp_ms = event["timestamp"].as_i64().unwrap_or_default();
+    let status = event["status"].as_str().unwrap_or("INFO");
+
+    let mut buf = vec![];
+    proto::fixed64(&mut buf, 1, (timestamp_ms * 1_000_000) as u64);
+    proto::uint64(&mut buf, 2, severity_number(status));
+    proto::string(&mut buf, 3, status);
+    proto::message(&mut buf, 5, &any_value(&event["message"]));
+    if let Some(map) = event.as_object() {
+        for (k, v) in map.iter().filter(|(k, _)| !LIFTED.contains(&k.as_str())) {
+            proto::message(&mut buf, 6, &key_value(k, v));
+        }
+    }
+    proto::fixed64(&mut buf, 11, Utc::now().timestamp_nanos() as u64);
+    return buf;
+}
+
+fn resource(attributes: &serde_json::Value) -> Vec<u8> {
+    let mut buf = vec![];
+    if let Some(map) = attributes.as_object() {
+        for (k, v) in map {
+            proto::message(&mut buf, 1, &key_value(k, v));
+        }
+    }
+    return buf;
+}
+
+fn scope(name: &str) -> Vec<u8> {
+    let mut buf = vec![];
+    proto::string(&mut buf, 1, name);
+    proto::string(&mut buf, 2, env!("CARGO_PKG_VERSION"));
+    return buf;
+}
+
+fn resource_logs(
+    resource_attributes: &serde_json::Value,
+    scopes: &[(String, Vec<Vec<u8>>)],
+) -> Vec<u8> {
+    let mut buf = vec![];
+    proto::message(&mut buf, 1, &resource(resource_attributes));
+    for (name, records) in scopes {
+        let mut scope_logs = vec![];
+        proto::message(&mut scope_logs, 1, &scope(name));
+        for record in records {
+            proto::message(&mut scope_logs, 2, record);
+        }
+        proto::message(&mut buf, 2, &scope_logs);
+    }
+    return buf;
+}
+
+/// Groups events into one `ResourceLogs` per service and host.
+pub fn export_request(events: &[serde_json::Value]) -> Vec<u8> {
+    let mut groups: Vec<(serde_json::Value, Vec<Vec<u8>>)> = vec![];
+    for event in events {
+        let attributes = json!({
+            "service.name": event["service"],
+            "host.name": event["hostname"],
+        });
+        let record = log_record(event);
+        match groups.iter_mut().find(|(a, _)| *a == attributes) {
+            Some((_, records)) => records.push(record),
+            None => groups.push((attributes, vec![record])),
+        }
+    }
+
+    let mut buf = vec![];
+    for (attributes, records) in groups {
+        let scopes = [("dynamo".to_owned(), records)];
+        proto::message(&mut buf, 1, &resource_logs(&attributes, &scopes));
+    }
+    return buf;
+}
+
+/// Repeats the batch across as many resource and scope combinations as fit in
+/// `max_bytes`, each resource carrying a distinct set of attributes.
+fn stress_request(events: &[serde_json::Value], stress: &StressMode) -> Vec<u8> {
+    let records: Vec<Vec<u8>> = events.iter().map(log_record).collect();
+    let mut buf = vec![];
+    let mut n = 0;
+    loop {
+        let region = ["us-east-1", "eu-west-1", "ap-south-1"][n % 3];
+        let environment = ["production", "staging"][n % 2];
+        let attributes = json!({
+            "service.name": format!("stress-service-{}", n % 97),
+            "host.name": format!("stress-host-{}", n),
+            "k8s.namespace.name": format!("namespace-{}", n % 13),
+            "k8s.pod.name": format!("pod-{}-{}", n % 97, n),
+            "cloud.region": region,
+            "deployment.environment": environment,
+        });
+        let scopes: Vec<(String, Vec<Vec<u8>>)> = (0..(n % 7) + 1)
+            .map(|s| (format!("dynamo.stress.scope{}", s), records.clone()))
+            .collect();
+
+        let mut next = vec![];
+        proto::message(&mut next, 1, &resource_logs(&attributes, &scopes));
+        if !buf.is_empty() && buf.len() + next.len() > stress.max_bytes {
+            return buf;
+        }
+        buf.extend_from_slice(&next);
+        n += 1;
+    }
+}
+
+/// Encodes a batch of events as a gRPC-framed export request.
+pub fn encode(events: &[serde_json::Value], stress: Option<&StressMode>) -> Vec<u8> {
+    let request = match stress {
+        Some(stress) => stress_request(events, stress),
+        None => export_request(events),
+    };
+
+    // gRPC frames each message with an uncompressed flag and its length.
+    let mut body = vec![0];
+    body.extend_from_slice(&(request.len() as u32).to_be_bytes());
+    body.extend_from_slice(&request);
+    return body;
+}
+
+/// Sends an encoded export request to the OTLP gRPC `target`.
+pub async fn export(client: &reqwest::Client, target: &str, body: Vec<u8>) -> Result<(), Failure> {
+    let url = format!("{}{}", target, EXPORT_PATH);
+    let response = client
+        .post(&url)
+        .header("content-type", "application/grpc")
+        .header("te", "trailers")
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| failures::request(&url, &e))?;
+
+    if !response.status().is_success() {
+        return Err(failures::status(&url, response.status()));
+    }
+    // Errors are usually reported in trailers, which aren't exposed, but
+    // trailers-only responses put the status in the headers.
+    if let Some(status) = response.headers().get("grpc-status") {
+        if status != "0" {
+            let message = response
+                .headers()
+                .get("grpc-message")
+                .and_then(|m| m.to_str().ok())
+                .unwrap_or_default();
+            let detail = format!("gRPC status {:?}: {}", status, message);
+            return Err(failures::other(&url, detail));
+        }
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::Value;
+
+    #[test]
+    fn encodes_a_record() {
+        let event = json!({
+            "message": "hi",
+            "service": "s",
+            "hostname": "h",
+            "status": "ERROR",
+            "timestamp": 2,
+            "k": true,
+        });
+        let request = export_request(&[event]);
+        let version = env!("CARGO_PKG_VERSION").as_bytes();
+        let scope_len = 10 + version.len() as u8;
+        let scope_logs_len = 2 + scope_len + 2 + 42;
+
+        // ResourceLogs, then its Resource with host.name and service.name.
+        let mut expected = vec![0x0a, 2 + 39 + 2 + scope_logs_len, 0x0a, 39];
+        expected.extend([0x0a, 16, 0x0a, 9]);
+        expected.extend(b"host.name");
+        expected.extend([0x12, 3, 0x0a, 1, b'h', 0x0a, 19, 0x0a, 12]);
+        expected.extend(b"service.name");
+        expected.extend([0x12, 3, 0x0a, 1, b's']);
+        // ScopeLogs, then its InstrumentationScope.
+        expected.extend([0x12, scope_logs_len, 0x0a, scope_len, 0x0a, 6]);
+        expected.extend(b"dynamo");
+        expected.extend([0x12, version.len() as u8]);
+        expected.extend(version);
+        // LogRecord: time, severity number and text, body and the attribute.
+        expected.extend([0x12, 42, 0x09, 0x80, 0x84, 0x1e, 0, 0, 0, 0, 0]);
+        expected.extend([0x10, 17, 0x1a, 5]);
+        expected.extend(b"ERROR");
+        expected.extend([0x2a, 4, 0x0a, 2, b'h', b'i']);
+        expected.extend([0x32, 7, 0x0a, 1, b'k', 0x12, 2, 0x10, 1]);
+        // observed_time_unix_nano is the time of encoding.
+        expected.push(0x59);
+        assert_eq!(&request[..request.len() - 8], expected.as_slice());
+    }
+
+    fn bytes<'a>(value: &Value<'a>) -> &'a [u8] {
+        return match value {
+            Value::Bytes(bytes) => bytes,
+            _ => panic!("expected a message, got {:?}", value),
+        };
+    }
+
+    /// The host name and scope count of each `ResourceLogs` in a request,
+    /// with its encoded length.
+    fn resources(request: &[u8]) -> Vec<(String, usize, usize)> {
+        let mut resources = vec![];
+        for (field, value) in proto::decode(request).unwrap() {
+            assert_eq!(field, 1);
+            let fields = proto::decode(bytes(&value)).unwrap();
+            let mut host = String::new();
+            for (_, attribute) in proto::decode(bytes(&fields[0].1)).unwrap() {
+                let attribute = proto::decode(bytes(&attribute)).unwrap();
+                if bytes(&attribute[0].1) == b"host.name" {
+                    let any_value = proto::decode(bytes(&attribute[1].1)).unwrap();
+                    host = String::from_utf8(bytes(&any_value[0].1).to_vec()).unwrap();
+                }
+            }
+            let scopes = fields.iter().filter(|(field, _)| *field == 2).count();
+            let mut encoded = vec![];
+            proto::message(&mut encoded, 1, bytes(&value));
+            resources.push((host, scopes, encoded.len()));
+        }
+        return resources;
+    }
+
+    #[test]
+    fn packs_resources_and_scopes() {
+        let events = [json!({ "message": "m", "service": "s", "hostname": "h" })];
+        let stress = StressMode { max_bytes: 4096 };
+        let request = stress_request(&events, &stress);
+        assert!(request.len() <= stress.max_bytes);
+
+        let resources = resources(&request);
+        assert!(resources.len() > 3, "{:?}", resources);
+        for (n, (host, scopes, _)) in resources.iter().enumerate() {
+            assert_eq!(*host, format!("stress-host-{}", n));
+            assert_eq!(*scopes, n % 7 + 1);
+        }
+
+        // The same layout continues in a bigger request, and its next
+        // resource would not have fitted in the smaller one.
+        let bigger = stress_request(
+            &events,
+            &StressMode {
+                max_bytes: 2 * 4096,
+            },
+        );
+        let bigger = super::tests::resources(&bigger);
+        let layout = |resources: &[(String, usize, usize)]| {
+            return resources
+                .iter()
+                .map(|(host, scopes, _)| (host.clone(), *scopes))
+                .collect::<Vec<_>>();
+        };
+        assert_eq!(layout(&bigger[..resources.len()]), layout(&resources));
+        let (_, _, next) = bigger[resources.len()];
+        assert!(request.len() + next > stress.max_bytes);
+    }
+}
@@ -0,0 +1,163 @@
+/*!
+Pacing for generators.
+
+Each generator gets a pacer that hands out permits at its configured rate,
+evenly spaced over each second. Up to `burst` permits may be taken at once to
+catch up after the generator falls behind, for example because the timer woke
+up late, but unused permits never accumulate beyond that, so a generator that
+was blocked on a full channel doesn't flood Vector when it is unblocked.
+
+Traffic shaping scales the rate by stretching or shrinking the interval
+between permits, so shaped traffic stays evenly spaced. A multiplier of zero
+pauses the generator until the shape lets traffic through again.
+*/
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::time::Instant;
+
+use crate::shape;
+
+static BURST: OnceLock<usize> = OnceLock::new();
+
+/// How much timer slop the default burst absorbs. Timers have millisecond
+/// resolution, so high rates need several permits per wakeup.
+const DEFAULT_BURST_WINDOW: Duration = Duration::from_millis(10);
+/// How often a paused generator checks whether its traffic has resumed.
+const PAUSED_CHECK: Duration = Duration::from_secs(1);
+
+pub struct Pacer {
+    /// The interval between permits at the configured rate, before shaping.
+    interval: Duration,
+    burst: u32,
+    /// When the next permit is due.
+    next: Instant,
+}
+
+/// The default burst size for a rate: enough permits to cover timer slop, and
+/// at least one.
+pub fn default_burst(rate_per_s: usize) -> usize {
+    let burst = rate_per_s as f64 * DEFAULT_BURST_WINDOW.as_secs_f64();
+    return (burst.ceil() as usize).max(1);
+}
+
+/// Sets the burst size for every generator, instead of the default for its
+/// rate.
+pub fn set_burst(burst: usize) {
+    BURST
+        .set(burst)
+        .expect("burst size should only be set once");
+}
+
+/// A pacer for a generator running at `rate_per_s`.
+pub fn for_rate(rate_per_s: usize) -> Pacer {
+    let burst = BURST
+        .get()
+        .copied()
+        .unwrap_or_else(|| default_burst(rate_per_s));
+    return Pacer::new(rate_per_s, burst);
+}
+
+impl Pacer {
+    pub fn new(rate_per_s: usize, burst: usize) -> Pacer {
+        assert!(rate_per_s > 0, "pacer rate should be positive");
+        return Pacer {
+            interval: Duration::from_secs_f64(1.0 / rate_per_s as f64),
+            burst: burst.max(1) as u32,
+            next: Instant::now(),
+        };
+    }
+
+    /// Waits until the next permit is due.
+    pub async fn acquire(&mut self) {
+        let mut multiplier = shape::multiplier(Utc::now());
+        while multiplier <= 0.0 {
+            tokio::time::sleep(PAUSED_CHECK).await;
+            multiplier = shape::multiplier(Utc::now());
+        }
+        let interval = self.interval.div_f64(multiplier);
+
+        let now = Instant::now();
+        // Permits that were due more than a burst ago are forfeited.
+        let window = interval * (self.burst - 1);
+        if let Some(earliest) = now.checked_sub(window) {
+            self.next = self.next.max(earliest);
+        }
+
+        if self.next > now {
+            tokio::time::sleep_until(self.next).await;
+        }
+        self.next += interval;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::{default_burst, Pacer};
+
+    /// Permits handed out in `duration` of simulated time.
+    async fn achieved(rate_per_s: usize, burst: usize, duration: Duration) -> usize {
+        let mut pacer = Pacer::new(rate_per_s, burst);
+        let end = Instant::now() + duration;
+        let mut permits = 0;
+        loop {
+            pacer.acquire().await;
+            if Instant::now() >= end {
+                return permits;
+            }
+            permits += 1;
+        }
+    }
+
+    fn assert_within(achieved: usize, expected: usize, tolerance: f64) {
+        let error = (achieved as f64 - expected as f64).abs() / expected as f64;
+        assert!(
+            error <= tolerance,
+            "achieved {} permits, expected {} within {}%",
+            achieved,
+            expected,
+            tolerance * 100.0
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn honors_configured_rates() {
+        for rate in [1, 3, 10, 100, 1_000, 25_000] {
+            let permits = achieved(rate, default_burst(rate), Duration::from_secs(10)).await;
+            assert_within(permits, rate * 10, 0.01);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spaces_permits_evenly() {
+        let mut pacer = Pacer::new(10, 1);
+        pacer.acquire().await;
+        let start = Instant::now();
+        for n in 1..=20 {
+            pacer.acquire().await;
+            let expected = Duration::from_millis(100 * n);
+            assert_eq!(Instant::now() - start, expected);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_catch_up_to_the_burst_size() {
+        let mut pacer = Pacer::new(100, 5);
+        pacer.acquire().await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+
+        // Only the burst is available immediately after a long stall.
+        let start = Instant::now();
+        for _ in 0..5 {
+            pacer.acquire().await;
+        }
+        assert_eq!(Instant::now(), start);
+        pacer.acquire().await;
+        assert!(Instant::now() > start);
+    }
+}
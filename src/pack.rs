@@ -0,0 +1,411 @@
+/*!
+Scenario packs: training content distributed separately from dynamo.
+
+A scenario pack is a directory holding a `pack.json` manifest, or a tarball
+(`.tar`, `.tar.gz` or `.tgz`) of one, so a pack can be handed out as a
+single file:
+
+```json
+{
+  "name": "incident-response-101",
+  "version": "1.2.0",
+  "args": { "app-log-rate-limit-per-s": 20, "keyboard-triggers": true },
+  "timeline": [
+    { "at_s": 60, "scenario": "dependency-cascade" },
+    { "at_s": 240, "scenario": "dependency-cascade", "stop": true }
+  ]
+}
+```
+
+`args` sets command line options by their long name, without the dashes.
+`true` passes a flag, `false` leaves it out, and arrays repeat the option.
+Options given on the command line take precedence over the pack's, except
+for options that may be repeated, whose values are added to the pack's. The
+timeline fires trigger scenarios at the given number of seconds after
+startup, as if an instructor had pressed the key. A step may instead, or as
+well, print a line of `narration` for the instructor to read out, or emit a
+`deploy` marker event for a `{ "service", "version" }`. The pack's name and
+version go into the answer key, so graders know which content a run used,
+along with when each step of the timeline happened and the pack's `grading`
+manifest, if it has one: a list of the questions students should be able to
+answer, each naming the answer key incident that holds the answer.
+
+Packs that ship with dynamo are built into the binary and can be loaded by
+name instead of by directory, e.g. `--scenario-pack storedog-incident-day`.
+
+Only the manifest is read from a pack. Generators' entities and message
+formats are built into dynamo, so packs can't carry their own templates or
+entity data.
+*/
+use std::io::Read;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+
+use crate::sink::Event;
+use crate::{answer_key, trigger};
+
+pub const FLAG: &str = "--scenario-pack";
+const MANIFEST: &str = "pack.json";
+/// Keys a manifest, and a step of its timeline, may have.
+pub const KEYS: [&str; 5] = ["name", "version", "args", "timeline", "grading"];
+pub const STEP_KEYS: [&str; 5] = ["at_s", "scenario", "stop", "narration", "deploy"];
+
+/// Packs built into the binary, by name.
+const BUILT_IN: [(&str, &str); 2] = [
+    (
+        "storedog-incident-day",
+        include_str!("../packs/storedog-incident-day/pack.json"),
+    ),
+    (
+        "soc-analyst-shift",
+        include_str!("../packs/soc-analyst-shift/pack.json"),
+    ),
+];
+
+struct Step {
+    at_s: u64,
+    scenario: Option<String>,
+    stop: bool,
+    narration: Option<String>,
+    deploy: Option<(String, String)>,
+}
+
+struct Pack {
+    name: String,
+    version: String,
+    timeline: Vec<Step>,
+    grading: serde_json::Value,
+}
+
+static PACK: OnceLock<Pack> = OnceLock::new();
+
+/// Turns the manifest's `args` into command line arguments.
+pub fn args(manifest: &serde_json::Value) -> Result<Vec<String>, String> {
+    let options = match &manifest["args"] {
+        serde_json::Value::Null => return Ok(vec![]),
+        serde_json::Value::Object(options) => options,
+        _ => return Err("pack args should be an object".to_owned()),
+    };
+
+    let mut args = vec![];
+    for (name, value) in options {
+        let values = match value {
+            serde_json::Value::Array(values) => values.clone(),
+            value => vec![value.clone()],
+        };
+        for value in values {
+            match value {
+                serde_json::Value::Bool(true) => args.push(format!("--{}", name)),
+                serde_json::Value::Bool(false) => {}
+                serde_json::Value::String(s) => args.extend([format!("--{}", name), s]),
+                serde_json::Value::Number(n) => args.extend([format!("--{}", name), n.to_string()]),
+                _ => return Err(format!("unsupported value for pack arg `{}`", name)),
+            }
+        }
+    }
+    return Ok(args);
+}
+
+fn timeline(manifest: &serde_json::Value) -> Result<Vec<Step>, String> {
+    let mut steps = vec![];
+    for step in manifest["timeline"].as_array().into_iter().flatten() {
+        let scenario = step["scenario"].as_str();
+        if let Some(scenario) = scenario {
+            if !trigger::SCENARIOS.iter().any(|s| s.name == scenario) {
+                return Err(format!("unknown scenario `{}` in timeline", scenario));
+            }
+        }
+        let deploy = match &step["deploy"] {
+            serde_json::Value::Null => None,
+            deploy => match (deploy["service"].as_str(), deploy["version"].as_str()) {
+                (Some(service), Some(version)) => Some((service.to_owned(), version.to_owned())),
+                _ => return Err(format!("deploy needs a service and version: {}", step)),
+            },
+        };
+        let narration = step["narration"].as_str();
+        if scenario.is_none() && deploy.is_none() && narration.is_none() {
+            return Err(format!("timeline step does nothing: {}", step));
+        }
+
+        steps.push(Step {
+            at_s: step["at_s"]
+                .as_u64()
+                .ok_or_else(|| format!("timeline step has no at_s: {}", step))?,
+            scenario: scenario.map(str::to_owned),
+            stop: step["stop"].as_bool().unwrap_or(false),
+            narration: narration.map(str::to_owned),
+            deploy,
+        });
+    }
+    return Ok(steps);
+}
+
+/// Whether `path` names a tarball rather than a directory.
+pub fn is_tarball(path: &str) -> bool {
+    return [".tar", ".tar.gz", ".tgz"]
+        .iter()
+        .any(|extension| path.ends_with(extension));
+}
+
+/// The contents of the manifest in a tar archive, either at its top level or
+/// in the directory the archive was made from.
+fn manifest_in_tar(archive: &[u8]) -> Option<&[u8]> {
+    let mut offset = 0;
+    while offset + 512 <= archive.len() {
+        let header = &archive[offset..offset + 512];
+        // The archive ends with empty blocks.
+        if header.iter().all(|b| *b == 0) {
+            return None;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let field = &header[range];
+            let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+            return String::from_utf8_lossy(&field[..end]).into_owned();
+        };
+        let size = usize::from_str_radix(field(124..136).trim(), 8).ok()?;
+        let name = match field(345..500) {
+            prefix if !prefix.is_empty() => format!("{}/{}", prefix, field(0..100)),
+            _ => field(0..100),
+        };
+        let start = offset + 512;
+        let regular = matches!(header[156], b'0' | 0);
+        let name = name.trim_start_matches("./");
+        let top_level = name == MANIFEST;
+        let in_dir = name
+            .split_once('/')
+            .is_some_and(|(_, rest)| rest == MANIFEST);
+        if regular && (top_level || in_dir) {
+            return archive.get(start..start.checked_add(size)?);
+        }
+        offset = start.checked_add(size.div_ceil(512).checked_mul(512)?)?;
+    }
+    return None;
+}
+
+fn read_tarball(path: &str) -> Result<String, String> {
+    let file = std::fs::read(path).map_err(|e| format!("could not read {}: {}", path, e))?;
+    let archive = if path.ends_with(".tar") {
+        file
+    } else {
+        let mut archive = vec![];
+        flate2::read::GzDecoder::new(file.as_slice())
+            .read_to_end(&mut archive)
+            .map_err(|e| format!("could not decompress {}: {}", path, e))?;
+        archive
+    };
+    let contents =
+        manifest_in_tar(&archive).ok_or_else(|| format!("{} has no {}", path, MANIFEST))?;
+    return String::from_utf8(contents.to_vec())
+        .map_err(|_| format!("{} in {} is not UTF-8", MANIFEST, path));
+}
+
+/// Reads `pack.json` from the pack directory or tarball, falling back to the
+/// built-in pack of that name when there is no such directory.
+pub fn manifest(dir: &str) -> Result<serde_json::Value, String> {
+    let path = Path::new(dir).join(MANIFEST);
+    let contents = match BUILT_IN.iter().find(|(name, _)| *name == dir) {
+        Some((_, contents)) if !Path::new(dir).exists() => contents.to_string(),
+        _ if is_tarball(dir) => read_tarball(dir)?,
+        _ => std::fs::read_to_string(&path)
+            .map_err(|e| format!("could not read {}: {}", path.display(), e))?,
+    };
+    return serde_json::from_str(&contents)
+        .map_err(|e| format!("{} is not valid JSON: {}", path.display(), e));
+}
+
+fn grading(manifest: &serde_json::Value) -> Result<serde_json::Value, String> {
+    return match &manifest["grading"] {
+        grading @ (serde_json::Value::Null | serde_json::Value::Array(_)) => Ok(grading.clone()),
+        _ => Err("pack grading should be a list".to_owned()),
+    };
+}
+
+/// Checks a manifest's timeline and grading the way loading it would.
+pub fn check(manifest: &serde_json::Value) -> Result<(), String> {
+    timeline(manifest)?;
+    grading(manifest)?;
+    return Ok(());
+}
+
+fn deploy_event(service: &str, version: &str) -> serde_json::Value {
+    return json!({
+        "message": format!("Deployed {} {}", service, version),
+        "service": service,
+        "marker": "deploy",
+        "deploy": { "service": service, "version": version },
+    });
+}
+
+/// Loads the pack named on the command line, if any, and returns the command
+/// line with the pack's arguments inserted before the user's own so that
+/// those take precedence.
+pub fn expand(command_line: Vec<String>) -> Result<Vec<String>, String> {
+    let dir = command_line.iter().enumerate().find_map(|(i, arg)| {
+        if arg == FLAG {
+            return command_line.get(i + 1).cloned();
+        }
+        return arg.strip_prefix(&format!("{}=", FLAG)).map(str::to_owned);
+    });
+    let dir = match dir {
+        Some(dir) => dir,
+        None => return Ok(command_line),
+    };
+
+    let manifest = manifest(&dir)?;
+    let pack = Pack {
+        name: manifest["name"].as_str().unwrap_or(&dir).to_owned(),
+        version: manifest["version"]
+            .as_str()
+            .unwrap_or("unversioned")
+            .to_owned(),
+        timeline: timeline(&manifest)?,
+        grading: grading(&manifest)?,
+    };
+    let mut expanded = command_line[..1].to_vec();
+    expanded.extend(args(&manifest)?);
+    expanded.extend_from_slice(&command_line[1..]);
+    PACK.set(pack)
+        .map_err(|_| "only one scenario pack can be loaded".to_owned())?;
+    return Ok(expanded);
+}
+
+/// Records the loaded pack in the answer key and schedules its timeline.
+pub fn start(tx: &Sender<Event>) {
+    let pack = match PACK.get() {
+        Some(pack) => pack,
+        None => return,
+    };
+    answer_key::record(json!({
+        "scenario_pack": {
+            "name": pack.name,
+            "version": pack.version,
+            "grading": pack.grading,
+        },
+    }));
+
+    for (i, step) in pack.timeline.iter().enumerate() {
+        let tx2 = tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(step.at_s)).await;
+            if let Some(narration) = &step.narration {
+                println!(
+                    "[{:02}:{:02}] {}",
+                    step.at_s / 60,
+                    step.at_s % 60,
+                    narration
+                );
+            }
+            if let Some((service, version)) = &step.deploy {
+                crate::emit(&tx2, "markers", deploy_event(service, version)).await;
+            }
+            if let Some(scenario) = &step.scenario {
+                match trigger::fire(&tx2, scenario, step.stop).await {
+                    Ok(message) | Err(message) => println!("{}", message),
+                }
+            }
+
+            answer_key::record(json!({
+                "scenario_pack": { "timeline": { (i.to_string()): {
+                    "at_s": step.at_s,
+                    "scenario": step.scenario,
+                    "stop": step.stop,
+                    "deploy": step.deploy.as_ref().map(|(service, version)| {
+                        json!({ "service": service, "version": version })
+                    }),
+                    "narration": step.narration,
+                    "happened_at": answer_key::now(),
+                } } },
+            }));
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn converts_pack_args_to_options() {
+        let manifest = json!({
+            "args": {
+                "hostname": ["web-1", "web-2"],
+                "keyboard-triggers": true,
+                "otlp-stress": false,
+                "seed": 7,
+            },
+        });
+        assert_eq!(
+            super::args(&manifest).unwrap(),
+            [
+                "--hostname",
+                "web-1",
+                "--hostname",
+                "web-2",
+                "--keyboard-triggers",
+                "--seed",
+                "7"
+            ]
+        );
+        assert!(super::args(&json!({ "args": { "seed": null } })).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_timeline_scenarios() {
+        let manifest = json!({ "timeline": [{ "at_s": 1, "scenario": "nope" }] });
+        assert!(super::timeline(&manifest).is_err());
+        let manifest = json!({ "timeline": [{ "at_s": 1, "scenario": "card-leak" }] });
+        assert_eq!(super::timeline(&manifest).unwrap().len(), 1);
+        let manifest = json!({ "timeline": [{ "at_s": 1 }] });
+        assert!(super::timeline(&manifest).is_err());
+    }
+
+    /// A tar archive of the given files.
+    fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut archive = vec![];
+        for (name, contents) in files {
+            let mut header = [0u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            header[124..136].copy_from_slice(format!("{:011o}\0", contents.len()).as_bytes());
+            header[156] = b'0';
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(contents.as_bytes());
+            archive.resize(archive.len().div_ceil(512) * 512, 0);
+        }
+        archive.resize(archive.len() + 1024, 0);
+        return archive;
+    }
+
+    #[test]
+    fn reads_tarballs() {
+        use std::io::Write;
+
+        let archive = tar(&[
+            ("shift/README", "Hand this out before class."),
+            ("shift/pack.json", r#"{ "name": "shift" }"#),
+        ]);
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&archive).unwrap();
+        let path = std::env::temp_dir().join(format!("dynamo-pack-{}.tgz", std::process::id()));
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+        let manifest = super::manifest(&path.to_string_lossy());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(manifest.unwrap(), json!({ "name": "shift" }));
+
+        assert!(super::manifest_in_tar(&tar(&[("pack.json", "{}")])).is_some());
+        assert!(super::manifest_in_tar(&tar(&[("a/b/pack.json", "{}")])).is_none());
+    }
+
+    #[test]
+    fn built_in_packs_are_valid() {
+        for (name, _) in super::BUILT_IN.iter() {
+            let manifest = super::manifest(name).unwrap();
+            assert_eq!(manifest["name"], *name);
+            assert!(super::args(&manifest).is_ok());
+            assert!(!super::timeline(&manifest).unwrap().is_empty());
+        }
+    }
+}
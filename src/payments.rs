@@ -0,0 +1,148 @@
+/*!
+Card authorizations from storedog's payments service, as binary protobuf over
+TCP.
+
+Each authorization is encoded as the `dynamo.payments.v1.Authorization`
+message from `proto/payments.proto` and written to a TCP connection with a
+4-byte big-endian length prefix, for lessons on Vector's `socket` source with
+`length_delimited` framing and protobuf decoding. While the dependency cascade
+has reached the payments service, authorizations slow down and some end in
+processor errors.
+
+These events don't go through the sink, so they carry none of the required
+Datadog attributes; only their hostname follows the identity overrides.
+*/
+use serde_json::{self, json};
+
+use crate::{cascade, clock, hosts, identity, proto, publisher, random};
+
+pub const GENERATOR: &str = "payments-protobuf";
+const SERVICE: &str = "storedog-payments";
+const CARD_BRANDS: [&str; 4] = ["visa", "mastercard", "amex", "discover"];
+/// Values of the `Authorization.Result` enum.
+const RESULTS: [&str; 4] = [
+    "RESULT_UNSPECIFIED",
+    "APPROVED",
+    "DECLINED",
+    "PROCESSOR_ERROR",
+];
+
+/// An authorization, with the same field names as the protobuf message.
+pub fn generate() -> serde_json::Value {
+    let slow = cascade::impacted(cascade::ORIGIN);
+    let result = if slow && random::chance(0.3) {
+        "PROCESSOR_ERROR"
+    } else if random::chance(0.05) {
+        "DECLINED"
+    } else {
+        "APPROVED"
+    };
+    let latency_ms: u64 = if slow {
+        random::range(2500..6000)
+    } else {
+        random::range(40..300)
+    };
+
+    return json!({
+        "transaction_id": random::uuid_v4(),
+        "timestamp_ms": clock::now().timestamp_millis(),
+        "hostname": hosts::backend(SERVICE).hostname,
+        "service": SERVICE,
+        "amount_cents": random::range(500i64..40_000),
+        "currency": "USD",
+        "card_brand": random::pick(&CARD_BRANDS),
+        "result": result,
+        "latency_ms": latency_ms,
+    });
+}
+
+/// Encodes an authorization as a protobuf message.
+pub fn encode(event: &serde_json::Value) -> Vec<u8> {
+    let str_field = |name: &str| event[name].as_str().unwrap_or_default();
+    let result = RESULTS
+        .iter()
+        .position(|r| *r == str_field("result"))
+        .unwrap_or_default();
+
+    let mut buf = vec![];
+    proto::string(&mut buf, 1, str_field("transaction_id"));
+    proto::int64(
+        &mut buf,
+        2,
+        event["timestamp_ms"].as_i64().unwrap_or_default(),
+    );
+    proto::string(&mut buf, 3, str_field("hostname"));
+    proto::string(&mut buf, 4, str_field("service"));
+    proto::int64(
+        &mut buf,
+        5,
+        event["amount_cents"].as_i64().unwrap_or_default(),
+    );
+    proto::string(&mut buf, 6, str_field("currency"));
+    proto::string(&mut buf, 7, str_field("card_brand"));
+    proto::uint64(&mut buf, 8, result as u64);
+    proto::uint64(
+        &mut buf,
+        9,
+        event["latency_ms"].as_u64().unwrap_or_default(),
+    );
+    return buf;
+}
+
+/// Prefixes a message with its length, for `length_delimited` framing.
+fn frame(message: &[u8]) -> Vec<u8> {
+    let mut framed = (message.len() as u32).to_be_bytes().to_vec();
+    framed.extend_from_slice(message);
+    return framed;
+}
+
+/// Sends authorizations to `target` at `rate_limit_per_s`, reconnecting
+/// whenever the connection fails. Events generated while disconnected are
+/// dropped. A zero rate disables the generator.
+pub fn start(target: String, rate_limit_per_s: usize) {
+    publisher::start(
+        GENERATOR,
+        target,
+        rate_limit_per_s,
+        publisher::connect,
+        || {
+            let mut event = generate();
+            let simulated_host = event["hostname"].as_str().map(str::to_owned);
+            event["hostname"] = json!(identity::hostname(GENERATOR, simulated_host.as_deref()));
+            return frame(&encode(&event));
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_encoded_authorizations() {
+        let event = json!({
+            "transaction_id": "t",
+            "timestamp_ms": 1,
+            "hostname": "h",
+            "service": "s",
+            "amount_cents": 150,
+            "currency": "USD",
+            "card_brand": "visa",
+            "result": "DECLINED",
+            "latency_ms": 42,
+        });
+        let expected: Vec<u8> = vec![
+            0, 0, 0, 29, // length prefix
+            0x0a, 1, b't', // transaction_id
+            0x10, 1, // timestamp_ms
+            0x1a, 1, b'h', // hostname
+            0x22, 1, b's', // service
+            0x28, 0x96, 0x01, // amount_cents
+            0x32, 3, b'U', b'S', b'D', // currency
+            0x3a, 4, b'v', b'i', b's', b'a', // card_brand
+            0x40, 2, // result
+            0x48, 42, // latency_ms
+        ];
+        assert_eq!(frame(&encode(&event)), expected);
+    }
+}
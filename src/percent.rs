@@ -0,0 +1,53 @@
+/*!
+Percentages set from the command line and read by every generator thread.
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::random;
+
+/// A percentage that can be shared between threads. It is stored as the bits
+/// of an f64, since there is no atomic float.
+pub struct Percent(AtomicU64);
+
+impl Percent {
+    pub const fn new() -> Percent {
+        // 0.0 is all zero bits.
+        return Percent(AtomicU64::new(0));
+    }
+
+    pub fn set(&self, percent: f64) {
+        self.0.store(percent.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> f64 {
+        return f64::from_bits(self.0.load(Ordering::Relaxed));
+    }
+
+    /// Whether an event falls in the percentage.
+    pub fn chance(&self) -> bool {
+        let percent = self.get();
+        return percent > 0.0 && random::range(0.0..100.0) < percent;
+    }
+}
+
+/// Parses a percentage, which must be between 0 and 100.
+pub fn parse(s: &str) -> Result<f64, String> {
+    return match s.parse::<f64>() {
+        Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent),
+        _ => Err(format!("`{}` is not a percentage between 0 and 100", s)),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parses_percentages() {
+        assert_eq!(super::parse("0").unwrap(), 0.0);
+        assert_eq!(super::parse("12.5").unwrap(), 12.5);
+        assert_eq!(super::parse("100").unwrap(), 100.0);
+        assert!(super::parse("-1").is_err());
+        assert!(super::parse("101").is_err());
+        assert!(super::parse("NaN").is_err());
+        assert!(super::parse("inf").is_err());
+    }
+}
@@ -0,0 +1,127 @@
+/*!
+Random placement of scenarios within a window.
+
+For graded labs, a scenario at a fixed offset lets students watch the clock
+instead of the data. `--scenario-window card-leak=10m-40m` starts the card
+leak at a random second between minutes 10 and 40 instead, and records where
+it landed in the answer key. Placement draws from its own RNG, seeded from
+`--seed` if given, so the same seed places scenarios at the same times
+regardless of what else the run generates. In a cluster, only the coordinator
+needs windows: it forwards placed scenarios to its workers as they start.
+
+A placed incident starts at its drawn time, in place of its `--<scenario>-at-s`
+option, and lasts as long as its duration option says. The latency regression
+is placed the same way. One-off scenarios, the card leak and the buffer stress
+burst, are fired at their drawn time.
+*/
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use rand::Rng;
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+
+use crate::sink::Event;
+use crate::{answer_key, bundle, random, trigger};
+
+/// A scenario and the first and last second it may start at.
+pub type Window = (String, u64, u64);
+
+static PLACED: OnceLock<Vec<(&'static str, u64)>> = OnceLock::new();
+
+/// Parses `<scenario>=<from>-<to>`, with durations such as `90s`, `10m` or
+/// `1h`.
+pub fn parse(s: &str) -> Result<Window, String> {
+    let invalid = || format!("`{}` should look like card-leak=10m-40m", s);
+    let (scenario, window) = s.split_once('=').ok_or_else(invalid)?;
+    let (from, to) = window.split_once('-').ok_or_else(invalid)?;
+    let (from, to) = (bundle::parse_duration(from)?, bundle::parse_duration(to)?);
+    if from == 0 || from > to {
+        return Err(format!(
+            "the window in `{}` should start at least 1s into the run and end after it starts",
+            s
+        ));
+    }
+    return Ok((scenario.to_owned(), from, to));
+}
+
+/// Draws a start time within each window, and records them in the answer
+/// key.
+pub fn configure(windows: &[Window], seed: Option<u64>) -> Result<(), String> {
+    let mut rng = random::independent(seed);
+    let mut placed = vec![];
+    for (scenario, from, to) in windows {
+        let name = trigger::SCENARIOS
+            .iter()
+            .map(|s| s.name)
+            .find(|name| name == scenario)
+            .ok_or_else(|| format!("unknown scenario `{}` to place", scenario))?;
+        if placed.iter().any(|(n, _)| *n == name) {
+            return Err(format!("scenario `{}` is placed more than once", name));
+        }
+        let at_s = rng.gen_range(*from..=*to);
+        answer_key::record(json!({
+            "placements": { (name): { "window_s": [from, to], "at_s": at_s } },
+        }));
+        placed.push((name, at_s));
+    }
+    PLACED
+        .set(placed)
+        .map_err(|_| "placements should only be configured once".to_owned())?;
+    return Ok(());
+}
+
+/// When `scenario` starts: its drawn time if it was placed, and `at_s`
+/// otherwise.
+pub fn at_s(scenario: &str, at_s: u64) -> u64 {
+    return PLACED
+        .get()
+        .into_iter()
+        .flatten()
+        .find(|(name, _)| *name == scenario)
+        .map(|(_, placed)| *placed)
+        .unwrap_or(at_s);
+}
+
+/// One-off scenarios that were placed, with their drawn times.
+pub fn one_offs() -> Vec<(&'static str, u64)> {
+    return PLACED
+        .get()
+        .into_iter()
+        .flatten()
+        .filter(|(name, _)| trigger::ONE_OFFS.contains(name))
+        .copied()
+        .collect();
+}
+
+/// Fires every placed one-off scenario at its time.
+pub fn schedule(tx: &Sender<Event>) {
+    for (name, at_s) in one_offs() {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(at_s)).await;
+            if let Err(e) = trigger::fire(&tx, name, false).await {
+                println!("Could not start {}: {}", name, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parses_windows() {
+        assert_eq!(
+            super::parse("card-leak=10m-40m").unwrap(),
+            ("card-leak".to_owned(), 600, 2400)
+        );
+        assert_eq!(
+            super::parse("crypto-mining=90-120s").unwrap(),
+            ("crypto-mining".to_owned(), 90, 120)
+        );
+        assert!(super::parse("card-leak=0-40m").is_err());
+        assert!(super::parse("card-leak=40m-10m").is_err());
+        assert!(super::parse("card-leak=10m").is_err());
+        assert!(super::parse("card-leak").is_err());
+    }
+}
@@ -0,0 +1,157 @@
+/*!
+Point-of-sale transaction logs from storedog's physical stores.
+
+The tills run legacy software that writes delimited records instead of
+anything structured. The delimiter is configurable per run (pipe, tab, comma,
+any other punctuation character, W3C extended style space-separated fields,
+or fixed-width columns), so the same parsing lesson can be re-run with a
+twist. In W3C mode a `#Fields:` directive precedes the records periodically,
+as it does at the top of each log file.
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use serde_json::{self, json};
+
+use crate::{clock, random};
+
+pub const SERVICE: &str = "pos-terminal";
+pub const DEFAULT_DELIMITER: &str = "pipe";
+pub const FIELDS: [&str; 9] = [
+    "date", "time", "store", "terminal", "txn", "cashier", "tender", "amount", "result",
+];
+/// Column widths in fixed-width mode. Amounts are right-aligned.
+const WIDTHS: [usize; 9] = [10, 8, 10, 3, 9, 16, 4, 9, 8];
+const TENDERS: [&str; 3] = ["CASH", "CARD", "GIFT"];
+/// Records between `#Fields:` directives in W3C mode.
+const DIRECTIVE_EVERY: u64 = 100;
+
+static DELIMITER: OnceLock<Delimiter> = OnceLock::new();
+static RECORDS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delimiter {
+    Char(char),
+    W3c,
+    FixedWidth,
+}
+
+/// Parses `pipe`, `tab`, `comma`, `w3c`, `fixed-width`, or a single
+/// punctuation character to use as the delimiter.
+pub fn parse_delimiter(s: &str) -> Result<Delimiter, String> {
+    return match s {
+        "pipe" => Ok(Delimiter::Char('|')),
+        "tab" => Ok(Delimiter::Char('\t')),
+        "comma" => Ok(Delimiter::Char(',')),
+        "w3c" => Ok(Delimiter::W3c),
+        "fixed-width" => Ok(Delimiter::FixedWidth),
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_punctuation() => Ok(Delimiter::Char(c)),
+                _ => Err(format!(
+                    "expected pipe, tab, comma, w3c, fixed-width or a punctuation character, got `{}`",
+                    s
+                )),
+            }
+        }
+    };
+}
+
+pub fn set_delimiter(delimiter: Delimiter) {
+    DELIMITER
+        .set(delimiter)
+        .expect("POS delimiter should only be set once");
+}
+
+fn delimiter() -> Delimiter {
+    return *DELIMITER.get_or_init(|| {
+        parse_delimiter(DEFAULT_DELIMITER).expect("default delimiter should parse")
+    });
+}
+
+fn record() -> [String; 9] {
+    let now = clock::now();
+    let result = if random::chance(0.05) {
+        "DECLINED"
+    } else if random::chance(0.02) {
+        "VOID"
+    } else {
+        "APPROVED"
+    };
+    return [
+        now.format("%Y-%m-%d").to_string(),
+        now.format("%H:%M:%S").to_string(),
+        format!("STORE-{:04}", random::range(1..60)),
+        format!("T{:02}", random::range(1..13)),
+        format!("{:09}", random::range(0..1_000_000_000)),
+        random::username(),
+        random::pick(&TENDERS).to_string(),
+        format!("{:.2}", random::range(100..30000) as f64 / 100.0),
+        result.to_owned(),
+    ];
+}
+
+fn line(fields: &[String], delimiter: Delimiter) -> String {
+    return match delimiter {
+        Delimiter::Char(c) => fields.join(&c.to_string()),
+        Delimiter::W3c => fields.join(" "),
+        Delimiter::FixedWidth => fields
+            .iter()
+            .zip(WIDTHS)
+            .enumerate()
+            .map(|(i, (field, width))| match FIELDS[i] {
+                "amount" => format!("{:>width$}", field, width = width),
+                _ => format!("{:<width$}", field, width = width),
+            })
+            .collect::<Vec<String>>()
+            .join(" "),
+    };
+}
+
+fn event(message: String, status: &str) -> serde_json::Value {
+    return json!({ "message": message, "service": SERVICE, "status": status });
+}
+
+pub fn generate() -> serde_json::Value {
+    let delimiter = delimiter();
+    let fields = record();
+    let status = if fields[8] == "DECLINED" {
+        "WARNING"
+    } else {
+        "INFO"
+    };
+    let record = event(line(&fields, delimiter), status);
+    let record_number = RECORDS.fetch_add(1, Ordering::Relaxed);
+    if delimiter != Delimiter::W3c || !record_number.is_multiple_of(DIRECTIVE_EVERY) {
+        return record;
+    }
+    return json!([
+        event(format!("#Fields: {}", FIELDS.join(" ")), "INFO"),
+        record
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{line, parse_delimiter, record, SERVICE};
+    use crate::validate;
+
+    #[test]
+    fn every_delimiter_matches_the_reference_grammar() {
+        for name in ["pipe", "tab", "comma", ";", "w3c", "fixed-width"] {
+            let delimiter = parse_delimiter(name).expect("delimiter should parse");
+            let message = line(&record(), delimiter);
+            let result = validate::event(&json!({ "message": message, "service": SERVICE }));
+            assert!(result.is_ok(), "{}: {:?}", name, result);
+        }
+    }
+
+    #[test]
+    fn rejects_alphanumeric_delimiters() {
+        assert!(parse_delimiter("x").is_err());
+        assert!(parse_delimiter("||").is_err());
+    }
+}
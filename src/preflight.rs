@@ -0,0 +1,126 @@
+/*!
+Startup checks for option combinations that would otherwise fail silently.
+
+A zero batch size or timeout, or rates far beyond what one sender can deliver,
+don't crash dynamo: it stalls, or falls further and further behind its rates
+while generators wait on the event queue. These are caught before anything
+starts, with a suggested value for each problem.
+*/
+use crate::{iot, payments, snmp, Args};
+
+/// The fastest a single generator can run.
+const MAX_RATE_PER_S: usize = 1_000_000;
+/// Roughly how many batches a second one sender keeps up with.
+const MAX_BATCHES_PER_S: usize = 1_000;
+/// Batches a second to aim for when suggesting a batch size.
+const SUGGESTED_BATCHES_PER_S: usize = 100;
+
+/// Returns every problem found with the options, each saying what to change.
+pub fn check(args: &Args) -> Vec<String> {
+    let mut problems = vec![];
+    if args.sender_batch_size == 0 {
+        problems.push(
+            "--sender-batch-size 0 would never fill a batch; use at least 1 (the default is 5)"
+                .to_owned(),
+        );
+    }
+    if args.queue_depth == 0 {
+        problems.push(
+            "--queue-depth 0 leaves no room for a single event; use at least 1 (the default \
+             is 32)"
+                .to_owned(),
+        );
+    }
+    if args.sender_batch_timeout_s == 0 {
+        problems.push(
+            "--sender-batch-timeout-s 0 would send partial batches continuously; use at least 1 \
+             (the default is 5)"
+                .to_owned(),
+        );
+    }
+
+    // Rates sent directly over TCP, MQTT or UDP don't go through the queue, but
+    // are paced the same way.
+    let queued: Vec<(&str, usize)> = crate::sources(args)
+        .into_iter()
+        .map(|(name, rate, _)| (name, rate))
+        .collect();
+    let direct = [
+        (payments::GENERATOR, args.protobuf_log_rate_limit_per_s),
+        (iot::GENERATOR, args.iot_log_rate_limit_per_s),
+        (snmp::GENERATOR, args.snmp_trap_rate_limit_per_s),
+    ];
+    for (name, rate) in queued.iter().chain(direct.iter()) {
+        if *rate > MAX_RATE_PER_S {
+            problems.push(format!(
+                "{} at {} events/s is more than one generator can produce; rates are events \
+                 per second, so set its rate to at most {}",
+                name, rate, MAX_RATE_PER_S
+            ));
+        }
+    }
+
+    // Saturating, since absurd rates are what this is here to catch.
+    let total = queued
+        .iter()
+        .fold(0usize, |total, (_, rate)| total.saturating_add(*rate));
+    let batch_size = args.sender_batch_size.max(1);
+    if total / batch_size > MAX_BATCHES_PER_S {
+        problems.push(format!(
+            "{} events/s in batches of {} is {} batches/s, more than the sender keeps up \
+             with, so the {}-event queue would fill and generators would fall behind \
+             their rates or drop events; try --sender-batch-size {}",
+            total,
+            batch_size,
+            total / batch_size,
+            args.queue_depth,
+            total.div_ceil(SUGGESTED_BATCHES_PER_S)
+        ));
+    }
+    return problems;
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::Args;
+
+    fn problems(options: &[&str]) -> Vec<String> {
+        let args = Args::parse_from(["dynamo"].iter().chain(options.iter()));
+        return super::check(&args);
+    }
+
+    #[test]
+    fn accepts_defaults() {
+        assert!(problems(&[]).is_empty());
+    }
+
+    #[test]
+    fn suggests_fixes() {
+        let found = problems(&["--sender-batch-size", "0", "--sender-batch-timeout-s", "0"]);
+        assert_eq!(found.len(), 2);
+
+        let found = problems(&["--http-log-rate-limit-per-s", "50000"]);
+        assert_eq!(found.len(), 1);
+        assert!(
+            found[0].ends_with("--sender-batch-size 501"),
+            "{}",
+            found[0]
+        );
+
+        let found = problems(&["--vpn-log-rate-limit-per-s", "5000000"]);
+        assert!(found[0].starts_with("vpn at 5000000 events/s"));
+        let found = problems(&["--snmp-trap-rate-limit-per-s", "5000000"]);
+        assert!(found[0].starts_with("snmp-traps at 5000000 events/s"));
+
+        let max = usize::MAX.to_string();
+        let found = problems(&[
+            "--http-log-rate-limit-per-s",
+            &max,
+            "--vpc-log-rate-limit-per-s",
+            &max,
+        ]);
+        assert!(found[0].starts_with("storedog-ok"));
+    }
+}
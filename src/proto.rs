@@ -0,0 +1,146 @@
+/*!
+A minimal protobuf wire-format encoder, and a decoder for small requests.
+
+Only the handful of field types dynamo's binary outputs need are supported.
+Messages are built by appending fields to a byte buffer, nesting by encoding
+the inner message first and appending it as a length-delimited field.
+*/
+
+const VARINT: u32 = 0;
+const FIXED64: u32 = 1;
+const LENGTH_DELIMITED: u32 = 2;
+const FIXED32: u32 = 5;
+
+pub fn varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn key(buf: &mut Vec<u8>, field: u32, wire_type: u32) {
+    varint(buf, ((field << 3) | wire_type) as u64);
+}
+
+pub fn uint64(buf: &mut Vec<u8>, field: u32, value: u64) {
+    key(buf, field, VARINT);
+    varint(buf, value);
+}
+
+pub fn int64(buf: &mut Vec<u8>, field: u32, value: i64) {
+    uint64(buf, field, value as u64);
+}
+
+pub fn boolean(buf: &mut Vec<u8>, field: u32, value: bool) {
+    uint64(buf, field, value as u64);
+}
+
+pub fn fixed64(buf: &mut Vec<u8>, field: u32, value: u64) {
+    key(buf, field, FIXED64);
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn double(buf: &mut Vec<u8>, field: u32, value: f64) {
+    fixed64(buf, field, value.to_bits());
+}
+
+pub fn bytes(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+    key(buf, field, LENGTH_DELIMITED);
+    varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+pub fn string(buf: &mut Vec<u8>, field: u32, value: &str) {
+    bytes(buf, field, value.as_bytes());
+}
+
+/// Appends a nested message that was encoded into its own buffer.
+pub fn message(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+    bytes(buf, field, value);
+}
+
+/// A decoded field's value.
+#[derive(Debug, PartialEq)]
+pub enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = buf.split_first().ok_or("truncated varint")?;
+        *buf = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    return Err("varint is too long".to_owned());
+}
+
+fn read_bytes<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if buf.len() < len {
+        return Err("truncated field".to_owned());
+    }
+    let (bytes, rest) = buf.split_at(len);
+    *buf = rest;
+    return Ok(bytes);
+}
+
+/// Decodes a message into its fields, in the order they appear.
+pub fn decode(mut buf: &[u8]) -> Result<Vec<(u32, Value<'_>)>, String> {
+    let mut fields = vec![];
+    while !buf.is_empty() {
+        let key = read_varint(&mut buf)?;
+        let value = match key as u32 & 0x7 {
+            VARINT => Value::Varint(read_varint(&mut buf)?),
+            FIXED64 => {
+                let bytes = read_bytes(&mut buf, 8)?;
+                Value::Fixed64(u64::from_le_bytes(
+                    bytes.try_into().expect("8 bytes were read"),
+                ))
+            }
+            LENGTH_DELIMITED => {
+                let len = read_varint(&mut buf)? as usize;
+                Value::Bytes(read_bytes(&mut buf, len)?)
+            }
+            FIXED32 => {
+                let bytes = read_bytes(&mut buf, 4)?;
+                Value::Fixed32(u32::from_le_bytes(
+                    bytes.try_into().expect("4 bytes were read"),
+                ))
+            }
+            wire_type => return Err(format!("unsupported wire type {}", wire_type)),
+        };
+        fields.push(((key >> 3) as u32, value));
+    }
+    return Ok(fields);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Value;
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let mut buf = vec![];
+        super::string(&mut buf, 1, "card-leak");
+        super::boolean(&mut buf, 2, true);
+        super::uint64(&mut buf, 3, 300);
+        super::double(&mut buf, 4, 1.5);
+        assert_eq!(
+            super::decode(&buf).unwrap(),
+            vec![
+                (1, Value::Bytes(b"card-leak")),
+                (2, Value::Varint(1)),
+                (3, Value::Varint(300)),
+                (4, Value::Fixed64(1.5f64.to_bits())),
+            ]
+        );
+        assert!(super::decode(&buf[..buf.len() - 1]).is_err());
+    }
+}
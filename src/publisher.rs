@@ -0,0 +1,88 @@
+/*!
+Publishing to a generator's own TCP target.
+
+Some generators, the protobuf payments stream and the MQTT telemetry, write
+straight to a TCP connection instead of going through the sink. A publisher
+paces a generator like any other and writes each frame it builds to the
+target, connecting on the first frame and reconnecting whenever the
+connection fails, at most once a second. Frames built while disconnected are
+dropped and counted as such.
+*/
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use crate::{pacing, stats, warmup};
+
+/// How long to wait after a failed connection before trying again.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Opens a plain TCP connection to `target`.
+pub async fn connect(target: String) -> Result<TcpStream, String> {
+    return TcpStream::connect(&target)
+        .await
+        .map_err(|e| format!("Could not connect to {}: {}", target, e));
+}
+
+/// Writes frames built by `frame` to `target` at `rate_limit_per_s`, opening
+/// connections with `connect`. A zero rate disables the generator.
+pub fn start<C, F, Frame>(
+    generator: &'static str,
+    target: String,
+    rate_limit_per_s: usize,
+    connect: C,
+    mut frame: Frame,
+) where
+    C: Fn(String) -> F + Send + 'static,
+    F: Future<Output = Result<TcpStream, String>> + Send,
+    Frame: FnMut() -> Vec<u8> + Send + 'static,
+{
+    if rate_limit_per_s == 0 {
+        return;
+    }
+
+    let mut pacer = pacing::for_rate(rate_limit_per_s);
+    let counters = stats::counters(generator);
+    tokio::spawn(async move {
+        let mut connection: Option<TcpStream> = None;
+        let mut retry_at = tokio::time::Instant::now();
+        loop {
+            pacer.acquire().await;
+            if !warmup::admit() {
+                continue;
+            }
+
+            let framed = frame();
+            stats::add(&counters.generated, 1);
+            stats::add(&counters.bytes_uncompressed, framed.len() as u64);
+            stats::add(&counters.bytes_wire, framed.len() as u64);
+
+            if connection.is_none() && tokio::time::Instant::now() >= retry_at {
+                match connect(target.clone()).await {
+                    Ok(stream) => connection = Some(stream),
+                    Err(e) => {
+                        println!("{}", e);
+                        retry_at = tokio::time::Instant::now() + RETRY_INTERVAL;
+                    }
+                }
+            }
+            let written = match connection.as_mut() {
+                Some(stream) => stream.write_all(&framed).await,
+                None => {
+                    stats::add(&counters.dropped, 1);
+                    continue;
+                }
+            };
+            match written {
+                Ok(_) => stats::add(&counters.sent, 1),
+                Err(e) => {
+                    println!("Could not send to {}: {}", target, e);
+                    stats::add(&counters.dropped, 1);
+                    connection = None;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,168 @@
+/*!
+The queue between the generators and the sender.
+
+When the sink can't keep up, the queue fills, and something has to give. By
+default generators wait for room and fall behind their rates, like a pipeline
+applying backpressure. With `--queue-policy drop-oldest` or `drop-newest` they
+keep their rates, and the queue sheds events instead, like a buffer set to
+drop when full. The queue's depth is set with `--queue-depth`, so a small
+queue and a slow sink show each trade-off in a few seconds. How many events
+were dropped is counted per generator and for the queue as a whole, in the
+stats and the summary printed on exit.
+*/
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use serde_json::{self, json};
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender};
+
+use crate::sink::Event;
+use crate::stats;
+
+pub const DEFAULT_DEPTH: usize = 32;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Generators wait for room, and fall behind their rates.
+    Block,
+    /// The oldest queued event is dropped to make room.
+    DropOldest,
+    /// The new event is dropped.
+    DropNewest,
+}
+
+impl Policy {
+    fn name(&self) -> &'static str {
+        return match self {
+            Policy::Block => "block",
+            Policy::DropOldest => "drop-oldest",
+            Policy::DropNewest => "drop-newest",
+        };
+    }
+}
+
+static POLICY: OnceLock<(Policy, usize)> = OnceLock::new();
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+fn policy() -> Policy {
+    return POLICY.get().map(|(p, _)| *p).unwrap_or(Policy::Block);
+}
+
+fn drop_event(event: &Event) {
+    stats::add(&event.counters.dropped, 1);
+    DROPPED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Creates the queue. With `drop-oldest`, queued events are held by a task
+/// that relays them to the receiver, so that it can discard the oldest.
+pub fn new(depth: usize, policy: Policy) -> (Sender<Event>, Receiver<Event>) {
+    POLICY
+        .set((policy, depth))
+        .expect("the queue should only be created once");
+    if policy != Policy::DropOldest {
+        return mpsc::channel(depth);
+    }
+
+    let (tx, mut inbound) = mpsc::channel::<Event>(depth);
+    let (outbound, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        let mut queued: VecDeque<Event> = VecDeque::with_capacity(depth + 1);
+        loop {
+            tokio::select! {
+                event = inbound.recv() => match event {
+                    Some(event) => {
+                        queued.push_back(event);
+                        if queued.len() > depth {
+                            let oldest = queued.pop_front().expect("the queue is not empty");
+                            drop_event(&oldest);
+                        }
+                    }
+                    None => break,
+                },
+                permit = outbound.reserve(), if !queued.is_empty() => match permit {
+                    Ok(permit) => permit.send(queued.pop_front().expect("the queue is not empty")),
+                    Err(_) => return,
+                },
+            }
+        }
+        // Generators have stopped; deliver what's left.
+        for event in queued {
+            if outbound.send(event).await.is_err() {
+                return;
+            }
+        }
+    });
+    return (tx, rx);
+}
+
+/// Queues an event according to the policy. Returns false if the receiver
+/// has gone away.
+pub async fn send(tx: &Sender<Event>, event: Event) -> bool {
+    if policy() == Policy::Block {
+        if let Err(e) = tx.send(event).await {
+            stats::add(&e.0.counters.dropped, 1);
+            return false;
+        }
+        return true;
+    }
+
+    return match tx.try_send(event) {
+        Ok(()) => true,
+        // Only possible when dropping the newest event: with drop-oldest,
+        // the relay keeps draining the channel, so senders wait at most
+        // briefly.
+        Err(TrySendError::Full(event)) if policy() == Policy::DropNewest => {
+            drop_event(&event);
+            true
+        }
+        Err(TrySendError::Full(event)) => tx.send(event).await.is_ok(),
+        Err(TrySendError::Closed(event)) => {
+            stats::add(&event.counters.dropped, 1);
+            false
+        }
+    };
+}
+
+pub fn snapshot() -> serde_json::Value {
+    let (policy, depth) = POLICY.get().copied().unwrap_or((Policy::Block, 0));
+    return json!({
+        "policy": policy.name(),
+        "depth": depth,
+        "dropped": DROPPED.load(Ordering::Relaxed),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Policy;
+    use crate::sink::Event;
+    use crate::stats;
+
+    #[tokio::test]
+    async fn drop_oldest_keeps_the_newest() {
+        let (tx, mut rx) = super::new(2, Policy::DropOldest);
+        let counters = stats::counters("queue-test");
+        for i in 0..5 {
+            let value = json!({ "i": i });
+            assert!(super::send(&tx, Event { counters, value }).await);
+            tokio::task::yield_now().await;
+        }
+        drop(tx);
+
+        let mut received = vec![];
+        while let Some(event) = rx.recv().await {
+            received.push(event.value["i"].as_u64().unwrap());
+        }
+        // The relay hands one event to the receiver's channel, and keeps the
+        // newest two.
+        assert_eq!(received, [0, 3, 4]);
+        assert_eq!(
+            super::snapshot()["dropped"],
+            json!(counters.dropped.load(std::sync::atomic::Ordering::Relaxed))
+        );
+    }
+}
@@ -0,0 +1,136 @@
+/*!
+Seedable randomness for generators.
+
+Every generator draws from a thread-local RNG instead of `rand::thread_rng` or
+fakeit's global one, so that runs can be reproduced from a seed. Each thread's
+RNG is derived from the base seed and the order in which threads first ask for
+randomness, which makes single-threaded rendering (as in `dynamo selftest`)
+fully deterministic.
+
+The fake data helpers mirror fakeit's, using its data tables.
+*/
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+use fakeit::data::{company, payment, person};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+static SEED: OnceLock<u64> = OnceLock::new();
+static THREADS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(new_rng());
+}
+
+fn new_rng() -> StdRng {
+    return match SEED.get() {
+        Some(seed) => {
+            let thread = THREADS.fetch_add(1, Ordering::Relaxed);
+            StdRng::seed_from_u64(seed.wrapping_add(thread))
+        }
+        None => StdRng::from_entropy(),
+    };
+}
+
+/// Sets the base seed for the run. Must be called before any randomness is
+/// used.
+pub fn set_seed(seed: u64) {
+    SEED.set(seed).expect("seed should only be set once");
+}
+
+/// Reseeds the current thread's RNG, regardless of the base seed.
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// An RNG of its own, for decisions that shouldn't depend on how much
+/// randomness generators have used, seeded from `seed` if given.
+pub fn independent(seed: Option<u64>) -> StdRng {
+    return match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+}
+
+pub fn with<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    return RNG.with(|rng| f(&mut rng.borrow_mut()));
+}
+
+pub fn range<T, R>(range: R) -> T
+where
+    T: rand::distributions::uniform::SampleUniform,
+    R: rand::distributions::uniform::SampleRange<T>,
+{
+    return with(|rng| rng.gen_range(range));
+}
+
+pub fn chance(probability: f64) -> bool {
+    return with(|rng| rng.gen_bool(probability));
+}
+
+pub fn pick<T>(items: &[T]) -> &T {
+    return with(|rng| items.choose(rng)).expect("there should be an item to pick");
+}
+
+/// Replaces every `#` in `pattern` with a random digit.
+fn digits(pattern: &str) -> String {
+    return pattern
+        .chars()
+        .map(|c| match c {
+            '#' => char::from(b'0' + range(0..10u8)),
+            c => c,
+        })
+        .collect();
+}
+
+pub fn ipv4_address() -> String {
+    return format!(
+        "{}.{}.{}.{}",
+        range(2..254),
+        range(2..254),
+        range(2..254),
+        range(2..254)
+    );
+}
+
+pub fn username() -> String {
+    return format!("{}{}", pick(person::LAST), digits("####"));
+}
+
+pub fn buzzword() -> String {
+    return pick(company::BUZZWORDS).to_string();
+}
+
+pub fn credit_card_number() -> String {
+    let pattern: &&str = pick(payment::NUMBER);
+    return digits(pattern);
+}
+
+pub fn uuid_v4() -> String {
+    let bytes: [u8; 16] = with(|rng| rng.gen());
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    // The version nibble is always 4, and the variant nibble one of 8-b.
+    return format!(
+        "{}-{}-4{}-{:x}{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[13..16],
+        8 + (bytes[8] & 0x3),
+        &hex[17..20],
+        &hex[20..32],
+    );
+}
+
+/// FNV-1a, for values that must only depend on their input and never on the
+/// seed.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
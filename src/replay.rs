@@ -0,0 +1,174 @@
+/*!
+Replay of exported bundles.
+
+`dynamo serve <bundle>` sends a bundle written by `dynamo export` through the
+configured sink, so a pre-baked dataset can still be delivered live to Vector.
+Events keep their spacing from the original run, divided by `--speed`.
+
+Bundles exported as JSON hold whole events, which are sent unchanged at the
+offset of their timestamp from the start of the run. Other encodings only
+hold each event's text, so every line is sent as the message of a new event
+from its generator, with the service the bundle lists for it, stamped as it
+is sent, with each file's lines spread evenly over the run.
+*/
+use std::path::Path;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::oneshot;
+
+use crate::bundle;
+use crate::encoding::Encoding;
+use crate::sink::Event;
+use crate::{queue, stats};
+
+struct Line {
+    /// Milliseconds into the run.
+    offset_ms: u64,
+    generator: &'static str,
+    text: String,
+    /// The event's service, for encodings that only hold its text.
+    service: Option<String>,
+}
+
+/// Parses a replay speed, which must be positive.
+pub fn parse_speed(s: &str) -> Result<f64, String> {
+    return match s.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("`{}` is not a positive speed, such as 1 or 10", s)),
+    };
+}
+
+fn read(dir: &str) -> Result<(Encoding, Vec<Line>), String> {
+    let path = Path::new(dir).join(bundle::MANIFEST);
+    let manifest: serde_json::Value = std::fs::read_to_string(&path)
+        .map_err(|e| format!("could not read {}: {}", path.display(), e))
+        .and_then(|contents| {
+            return serde_json::from_str(&contents)
+                .map_err(|e| format!("{} is not valid JSON: {}", path.display(), e));
+        })?;
+    let encoding = manifest["encoding"]
+        .as_str()
+        .and_then(|name| Encoding::from_str(name, false).ok())
+        .ok_or_else(|| format!("{} has no known encoding", path.display()))?;
+    let started_at = manifest["started_at"].as_i64().unwrap_or_default();
+    let duration_ms = manifest["duration_s"].as_u64().unwrap_or_default() * 1000;
+
+    let mut lines = vec![];
+    for (name, file) in manifest["files"].as_object().into_iter().flatten() {
+        let generator = *crate::GENERATORS
+            .iter()
+            .find(|g| *g == name)
+            .ok_or_else(|| format!("unknown generator `{}` in {}", name, path.display()))?;
+        let file = Path::new(dir).join(file.as_str().unwrap_or_default());
+        let contents = std::fs::read_to_string(&file)
+            .map_err(|e| format!("could not read {}: {}", file.display(), e))?;
+
+        // Bundles from before services were listed have none.
+        let services = match manifest["services"][name].as_str() {
+            Some(services) => {
+                let services = Path::new(dir).join(services);
+                std::fs::read_to_string(&services)
+                    .map_err(|e| format!("could not read {}: {}", services.display(), e))?
+            }
+            None => String::new(),
+        };
+        let mut services = services.lines();
+
+        let count = contents.lines().count() as u64;
+        for (i, text) in contents.lines().enumerate() {
+            let offset_ms = match encoding {
+                Encoding::Json => {
+                    let event: serde_json::Value = serde_json::from_str(text)
+                        .map_err(|e| format!("{} has an invalid event: {}", file.display(), e))?;
+                    let timestamp = event["timestamp"].as_i64().unwrap_or(started_at);
+                    (timestamp - started_at).max(0) as u64
+                }
+                _ => i as u64 * duration_ms / count,
+            };
+            lines.push(Line {
+                offset_ms,
+                generator,
+                text: text.to_owned(),
+                service: services.next().filter(|s| !s.is_empty()).map(str::to_owned),
+            });
+        }
+    }
+    lines.sort_by_key(|line| line.offset_ms);
+    return Ok((encoding, lines));
+}
+
+/// Loads the bundle in `dir` and starts replaying it at `speed` times its
+/// original pace. The returned receiver fires once every event has been
+/// queued.
+pub fn start(tx: Sender<Event>, dir: &str, speed: f64) -> Result<oneshot::Receiver<()>, String> {
+    let (encoding, lines) = read(dir)?;
+    let (finished, done) = oneshot::channel();
+    println!("Replaying {} events from {}", lines.len(), dir);
+
+    tokio::spawn(async move {
+        let started = tokio::time::Instant::now();
+        for line in lines {
+            let offset = Duration::from_secs_f64(line.offset_ms as f64 / 1000.0 / speed);
+            tokio::time::sleep_until(started + offset).await;
+
+            let sent = match encoding {
+                Encoding::Json => {
+                    let counters = stats::counters(line.generator);
+                    stats::add(&counters.generated, 1);
+                    let value = serde_json::from_str(&line.text)
+                        .expect("events were checked when the bundle was read");
+                    queue::send(&tx, Event { counters, value }).await
+                }
+                _ => {
+                    let mut value = serde_json::json!({ "message": line.text });
+                    if let Some(service) = line.service {
+                        value["service"] = serde_json::json!(service);
+                    }
+                    crate::emit(&tx, line.generator, value).await
+                }
+            };
+            if !sent {
+                return;
+            }
+        }
+        let _ = finished.send(());
+    });
+    return Ok(done);
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parses_speeds() {
+        assert_eq!(super::parse_speed("10").unwrap(), 10.0);
+        assert_eq!(super::parse_speed("0.5").unwrap(), 0.5);
+        assert!(super::parse_speed("0").is_err());
+        assert!(super::parse_speed("fast").is_err());
+    }
+
+    #[test]
+    fn restores_services() {
+        let dir = std::env::temp_dir().join(format!("dynamo-replay-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = serde_json::json!({
+            "encoding": "raw",
+            "duration_s": 2,
+            "files": { "vpn": "vpn.log" },
+            "services": { "vpn": "vpn.services" },
+        });
+        std::fs::write(dir.join(crate::bundle::MANIFEST), manifest.to_string()).unwrap();
+        std::fs::write(
+            dir.join("vpn.log"),
+            "{\"uid\":\"C1\"}\nPeer Connection Initiated\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("vpn.services"), "zeek\nopenvpn\n").unwrap();
+
+        let (_, lines) = super::read(&dir.to_string_lossy()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let services: Vec<Option<&str>> = lines.iter().map(|l| l.service.as_deref()).collect();
+        assert_eq!(services, [Some("zeek"), Some("openvpn")]);
+    }
+}
@@ -0,0 +1,45 @@
+/*!
+Sampling labels for teaching Vector's `sample` transform.
+
+A configured percentage of events is tagged with `sampled:true`. Exact counts
+of tagged and total events go into the answer key, so students can compare the
+rate they observe after sampling against the expected one.
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{self, json};
+
+use crate::percent::Percent;
+
+static PERCENT: Percent = Percent::new();
+static EVENTS: AtomicU64 = AtomicU64::new(0);
+static SAMPLED: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_percent(percent: f64) {
+    PERCENT.set(percent);
+}
+
+/// Counts an event and decides whether it should be tagged as sampled.
+pub fn label(val: &mut serde_json::Value) {
+    EVENTS.fetch_add(1, Ordering::Relaxed);
+
+    if !PERCENT.chance() {
+        return;
+    }
+
+    SAMPLED.fetch_add(1, Ordering::Relaxed);
+    let tags = val["ddtags"].as_str().unwrap_or_default();
+    val["ddtags"] = if tags.is_empty() {
+        json!("sampled:true")
+    } else {
+        json!(format!("{},sampled:true", tags))
+    };
+}
+
+pub fn summary() -> serde_json::Value {
+    return json!({
+        "percent": PERCENT.get(),
+        "events": EVENTS.load(Ordering::Relaxed),
+        "sampled": SAMPLED.load(Ordering::Relaxed),
+    });
+}
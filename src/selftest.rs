@@ -0,0 +1,473 @@
+/*!
+Golden-file snapshot tests for generators.
+
+Each case renders a generator a fixed number of times on one thread, with a
+fixed seed and a frozen clock, and compares the result against the output
+committed in `golden/`. Any change to a generator's format shows up as a
+golden mismatch, so changes that would break course material are caught
+before release rather than in class. Each golden event is also checked against
+the latest schema for its generator.
+
+The goldens are embedded in the binary so `dynamo selftest` can check a
+release build anywhere. After an intentional format change, regenerate them
+with `dynamo selftest --update` from the repository root.
+*/
+use chrono::prelude::*;
+use serde_json::{self, json};
+
+use crate::{
+    applog, billing, cardinality, cascade, clock, dns, falco, flags, ftp, iot, macos, mining,
+    noise, payments, pos, random, schema, snmp, soap, sso, storedog, vpc, vpn, Generator,
+};
+
+const SEED: u64 = 423;
+const RENDERS: usize = 5;
+
+struct Case {
+    name: &'static str,
+    /// The generator's name, as in `--stats`, which its schema is found by.
+    source: &'static str,
+    golden: &'static str,
+    generator: Generator,
+    /// Puts the simulation into the state the case covers, or back out of it.
+    scenario: fn(bool),
+}
+
+fn normal(_: bool) {}
+
+/// Starts the dependency cascade long enough ago that it has reached every
+/// service.
+fn full_cascade(active: bool) {
+    let now = clock::now();
+    clock::freeze(Some(now - chrono::Duration::minutes(5)));
+    cascade::INCIDENT.set(active);
+    clock::freeze(Some(now));
+}
+
+fn feature_flag() -> serde_json::Value {
+    return flags::flag_change_event(flags::CHECKOUT_V2, true);
+}
+
+const CASES: [Case; 37] = [
+    Case {
+        name: "storedog-ok",
+        source: "storedog-ok",
+        golden: include_str!("../golden/storedog-ok.jsonl"),
+        generator: storedog::ok,
+        scenario: normal,
+    },
+    Case {
+        name: "storedog-error",
+        source: "storedog-error",
+        golden: include_str!("../golden/storedog-error.jsonl"),
+        generator: storedog::error,
+        scenario: normal,
+    },
+    Case {
+        name: "storedog-leak",
+        source: "storedog-leak",
+        golden: include_str!("../golden/storedog-leak.jsonl"),
+        generator: storedog::leak,
+        scenario: normal,
+    },
+    Case {
+        name: "storedog-ok-regression",
+        source: "storedog-ok",
+        golden: include_str!("../golden/storedog-ok-regression.jsonl"),
+        generator: storedog::ok,
+        scenario: flags::force_checkout_v2,
+    },
+    Case {
+        name: "storedog-ok-cardinality",
+        source: "storedog-ok",
+        golden: include_str!("../golden/storedog-ok-cardinality.jsonl"),
+        generator: storedog::ok,
+        scenario: |active| cardinality::INCIDENT.set(active),
+    },
+    Case {
+        name: "storedog-ok-dependency-cascade",
+        source: "storedog-ok",
+        golden: include_str!("../golden/storedog-ok-dependency-cascade.jsonl"),
+        generator: storedog::ok,
+        scenario: full_cascade,
+    },
+    Case {
+        name: "applog",
+        source: "applog",
+        golden: include_str!("../golden/applog.jsonl"),
+        generator: applog::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "applog-debug-flood",
+        source: "applog",
+        golden: include_str!("../golden/applog-debug-flood.jsonl"),
+        generator: applog::generate,
+        scenario: |active| applog::FLOOD.set(active),
+    },
+    Case {
+        name: "applog-dependency-cascade",
+        source: "applog",
+        golden: include_str!("../golden/applog-dependency-cascade.jsonl"),
+        generator: applog::generate,
+        scenario: full_cascade,
+    },
+    Case {
+        name: "vpc-accept",
+        source: "vpc-accept",
+        golden: include_str!("../golden/vpc-accept.jsonl"),
+        generator: vpc::accept,
+        scenario: normal,
+    },
+    Case {
+        name: "vpc-ssh-attack",
+        source: "vpc-ssh-attack",
+        golden: include_str!("../golden/vpc-ssh-attack.jsonl"),
+        generator: vpc::ssh_attack,
+        scenario: normal,
+    },
+    Case {
+        name: "vpc-mining",
+        source: "vpc-mining",
+        golden: include_str!("../golden/vpc-mining.jsonl"),
+        generator: vpc::mining,
+        scenario: |active| mining::INCIDENT.set(active),
+    },
+    Case {
+        name: "feature-flags",
+        source: "feature-flags",
+        golden: include_str!("../golden/feature-flags.jsonl"),
+        generator: feature_flag,
+        scenario: normal,
+    },
+    Case {
+        name: "billing",
+        source: "billing",
+        golden: include_str!("../golden/billing.jsonl"),
+        generator: billing::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "billing-mining",
+        source: "billing",
+        golden: include_str!("../golden/billing-mining.jsonl"),
+        generator: billing::generate,
+        scenario: |active| mining::INCIDENT.set(active),
+    },
+    Case {
+        name: "billing-csv",
+        source: "billing-csv",
+        golden: include_str!("../golden/billing-csv.jsonl"),
+        generator: billing::generate_csv,
+        scenario: |_| billing::restart_csv_export(),
+    },
+    Case {
+        name: "pos",
+        source: "pos",
+        golden: include_str!("../golden/pos.jsonl"),
+        generator: pos::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "soap-gateway",
+        source: "soap-gateway",
+        golden: include_str!("../golden/soap-gateway.jsonl"),
+        generator: soap::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "soap-gateway-dependency-cascade",
+        source: "soap-gateway",
+        golden: include_str!("../golden/soap-gateway-dependency-cascade.jsonl"),
+        generator: soap::generate,
+        scenario: full_cascade,
+    },
+    Case {
+        name: "payments-protobuf",
+        source: payments::GENERATOR,
+        golden: include_str!("../golden/payments-protobuf.jsonl"),
+        generator: payments::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "windows-dns",
+        source: "windows-dns",
+        golden: include_str!("../golden/windows-dns.jsonl"),
+        generator: dns::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "windows-dns-dga",
+        source: "windows-dns",
+        golden: include_str!("../golden/windows-dns-dga.jsonl"),
+        generator: dns::generate,
+        scenario: |active| dns::INCIDENT.set(active),
+    },
+    Case {
+        name: "ftp",
+        source: "ftp-xfer",
+        golden: include_str!("../golden/ftp.jsonl"),
+        generator: ftp::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "ftp-bulk-download",
+        source: "ftp-xfer",
+        golden: include_str!("../golden/ftp-bulk-download.jsonl"),
+        generator: ftp::generate,
+        scenario: |active| ftp::INCIDENT.set(active),
+    },
+    Case {
+        name: "noise",
+        source: noise::GENERATOR,
+        golden: include_str!("../golden/noise.jsonl"),
+        generator: noise::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "macos-unified",
+        source: macos::GENERATOR,
+        golden: include_str!("../golden/macos-unified.jsonl"),
+        generator: macos::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "falco",
+        source: falco::GENERATOR,
+        golden: include_str!("../golden/falco.jsonl"),
+        generator: falco::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "falco-mining",
+        source: falco::GENERATOR,
+        golden: include_str!("../golden/falco-mining.jsonl"),
+        generator: falco::generate,
+        scenario: |active| mining::INCIDENT.set(active),
+    },
+    Case {
+        name: "falco-dga",
+        source: falco::GENERATOR,
+        golden: include_str!("../golden/falco-dga.jsonl"),
+        generator: falco::generate,
+        scenario: |active| dns::INCIDENT.set(active),
+    },
+    Case {
+        name: "vpn",
+        source: "vpn",
+        golden: include_str!("../golden/vpn.jsonl"),
+        generator: vpn::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "vpn-impossible-travel",
+        source: "vpn",
+        golden: include_str!("../golden/vpn-impossible-travel.jsonl"),
+        generator: vpn::generate,
+        scenario: |active| vpn::INCIDENT.set(active),
+    },
+    Case {
+        name: "sso",
+        source: sso::GENERATOR,
+        golden: include_str!("../golden/sso.jsonl"),
+        generator: sso::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "sso-impossible-travel",
+        source: sso::GENERATOR,
+        golden: include_str!("../golden/sso-impossible-travel.jsonl"),
+        generator: sso::generate,
+        scenario: |active| vpn::INCIDENT.set(active),
+    },
+    Case {
+        name: "iot",
+        source: iot::GENERATOR,
+        golden: include_str!("../golden/iot.jsonl"),
+        generator: iot::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "iot-stuck-sensor",
+        source: iot::GENERATOR,
+        golden: include_str!("../golden/iot-stuck-sensor.jsonl"),
+        generator: iot::generate,
+        scenario: |active| iot::INCIDENT.set(active),
+    },
+    Case {
+        name: "snmp",
+        source: snmp::GENERATOR,
+        golden: include_str!("../golden/snmp.jsonl"),
+        generator: snmp::generate,
+        scenario: normal,
+    },
+    Case {
+        name: "snmp-mining",
+        source: snmp::GENERATOR,
+        golden: include_str!("../golden/snmp-mining.jsonl"),
+        generator: snmp::generate,
+        scenario: |active| mining::INCIDENT.set(active),
+    },
+];
+
+/// Renders a case deterministically, one generator call per line.
+fn render(case: &Case) -> String {
+    random::reseed(SEED);
+    clock::freeze(Some(
+        Utc.with_ymd_and_hms(2023, 4, 1, 12, 0, 0)
+            .single()
+            .expect("golden time should be valid"),
+    ));
+    (case.scenario)(true);
+
+    let mut out = String::new();
+    for _ in 0..RENDERS {
+        out.push_str(&json!((case.generator)()).to_string());
+        out.push('\n');
+    }
+
+    (case.scenario)(false);
+    clock::freeze(None);
+    return out;
+}
+
+/// A golden event as its generator puts it on the wire. Events for the sink
+/// are stamped as they are sent, and MQTT messages publish only their payload.
+fn wire(source: &'static str, mut event: serde_json::Value) -> serde_json::Value {
+    return match source {
+        iot::GENERATOR => event["payload"].take(),
+        payments::GENERATOR | snmp::GENERATOR => event,
+        _ => {
+            crate::prepare(source, &mut event);
+            event
+        }
+    };
+}
+
+/// Checks a case's golden events against the latest schema for its generator.
+fn schema_failures(case: &Case) -> Vec<String> {
+    let schema = match schema::find(case.source, None) {
+        Some(schema) => schema,
+        None => return vec![format!("{}: no schema for {}", case.name, case.source)],
+    };
+    let mut failures = vec![];
+    for (line, golden) in case.golden.lines().enumerate() {
+        let events = match serde_json::from_str(golden) {
+            Ok(serde_json::Value::Array(events)) => events,
+            Ok(event) => vec![event],
+            Err(e) => {
+                failures.push(format!(
+                    "{}: line {} is not JSON: {}",
+                    case.name,
+                    line + 1,
+                    e
+                ));
+                continue;
+            }
+        };
+        for event in events {
+            let path = format!("{} line {}", case.name, line + 1);
+            failures.extend(schema.violations(&wire(case.source, event), &path));
+        }
+    }
+    return failures;
+}
+
+/// Compares every case against its golden output and its schema, returning a
+/// description of each mismatch.
+pub fn check() -> Vec<String> {
+    let mut failures = vec![];
+    for case in CASES.iter() {
+        let rendered = render(case);
+        let mismatch = rendered
+            .lines()
+            .zip(case.golden.lines())
+            .enumerate()
+            .find(|(_, (r, g))| r != g);
+
+        if let Some((line, (r, g))) = mismatch {
+            failures.push(format!(
+                "{}: line {} differs\n  golden:   {}\n  rendered: {}",
+                case.name,
+                line + 1,
+                g,
+                r
+            ));
+        } else if rendered.lines().count() != case.golden.lines().count() {
+            failures.push(format!(
+                "{}: golden has a different number of lines",
+                case.name
+            ));
+        }
+        failures.extend(schema_failures(case));
+    }
+    return failures;
+}
+
+/// Rewrites the golden files in `dir` from the current generators.
+pub fn update(dir: &str) -> Result<(), String> {
+    for case in CASES.iter() {
+        let path = format!("{}/{}.jsonl", dir, case.name);
+        std::fs::write(&path, render(case))
+            .map_err(|e| format!("could not write {}: {}", path, e))?;
+    }
+    return Ok(());
+}
+
+pub fn run(update_dir: Option<&str>) -> Result<(), String> {
+    if let Some(dir) = update_dir {
+        update(dir)?;
+        println!("Updated {} golden files in {}", CASES.len(), dir);
+        return Ok(());
+    }
+
+    let failures = check();
+    for failure in failures.iter() {
+        println!("FAIL {}", failure);
+    }
+    if !failures.is_empty() {
+        return Err(format!(
+            "{} of {} golden cases failed",
+            failures.len(),
+            CASES.len()
+        ));
+    }
+    println!("All {} golden cases passed", CASES.len());
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn goldens_match_their_schemas() {
+        for case in super::CASES.iter() {
+            let failures = super::schema_failures(case);
+            assert!(failures.is_empty(), "{}", failures.join("\n"));
+        }
+    }
+
+    #[test]
+    fn generators_match_goldens() {
+        let failures = super::check();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn golden_services_have_owners() {
+        for case in super::CASES.iter() {
+            for line in case.golden.lines() {
+                let events = match serde_json::from_str(line).unwrap() {
+                    serde_json::Value::Array(events) => events,
+                    event => vec![event],
+                };
+                for service in events.iter().filter_map(|e| e["service"].as_str()) {
+                    assert!(
+                        crate::hosts::OWNERS.iter().any(|(s, _)| *s == service),
+                        "{} has no owner",
+                        service
+                    );
+                }
+            }
+        }
+    }
+}
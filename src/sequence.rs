@@ -0,0 +1,322 @@
+/*!
+Sequence numbers and batch checksums for loss detection.
+
+Every event carries a sequence number that increases by one for each event its
+generator emits, starting from 1. When a batch is sent, each of its events is
+also given the batch's ID, size and a checksum over the sequence numbers in
+it. The checksum is order-independent and ignores event contents, so it still
+holds after Vector reorders or transforms events.
+
+`dynamo verify-sequence` reads Vector's output and reports gaps and duplicates
+in each generator's sequence, and batches that arrived incomplete, for
+demonstrating at-least-once delivery. If the output includes heartbeats, events
+missing after the last one received count as lost too.
+*/
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde_json::json;
+
+use crate::{cluster, heartbeat, random};
+
+pub const SEQ_FIELD: &str = "dynamo_seq";
+pub const GENERATOR_FIELD: &str = "dynamo_generator";
+pub const BATCH_FIELD: &str = "dynamo_batch";
+
+/// Each generator's last sequence number, and the timestamp of the event
+/// given it.
+static SEQUENCES: Mutex<Vec<(&'static str, u64, i64)>> = Mutex::new(vec![]);
+static BATCHES: AtomicU64 = AtomicU64::new(0);
+
+/// Gives an event from `generator` the next number in its sequence. The
+/// event should already have its timestamp.
+pub fn number(generator: &'static str, event: &mut serde_json::Value) {
+    let timestamp = event["timestamp"].as_i64().unwrap_or_default();
+    let mut sequences = SEQUENCES
+        .lock()
+        .expect("sequence lock should not be poisoned");
+    let seq = match sequences.iter_mut().find(|(name, _, _)| *name == generator) {
+        Some((_, seq, last)) => {
+            *seq += 1;
+            *last = timestamp;
+            *seq
+        }
+        None => {
+            sequences.push((generator, 1, timestamp));
+            1
+        }
+    };
+    event[GENERATOR_FIELD] = json!(cluster::label(generator));
+    event[SEQ_FIELD] = json!(seq);
+}
+
+/// Each generator's last sequence number, and the timestamp of that event.
+pub fn snapshot() -> Vec<(&'static str, u64, i64)> {
+    return SEQUENCES
+        .lock()
+        .expect("sequence lock should not be poisoned")
+        .clone();
+}
+
+/// The checksum of one event's place in a batch. A batch's checksum is the
+/// wrapping sum of its events', so it doesn't depend on their order.
+fn event_checksum(generator: &str, seq: u64) -> u64 {
+    return random::fnv1a(format!("{}:{}", generator, seq).as_bytes());
+}
+
+/// Marks every event in a batch with the batch's ID, size and checksum.
+pub fn seal(events: &mut [serde_json::Value]) {
+    let id = BATCHES.fetch_add(1, Ordering::Relaxed) + 1;
+    let checksum = events
+        .iter()
+        .map(|e| {
+            let generator = e[GENERATOR_FIELD].as_str().unwrap_or_default();
+            return event_checksum(generator, e[SEQ_FIELD].as_u64().unwrap_or_default());
+        })
+        .fold(0u64, u64::wrapping_add);
+    let batch = json!({
+        "id": id,
+        "size": events.len(),
+        "checksum": format!("{:016x}", checksum),
+    });
+    for event in events.iter_mut() {
+        event[BATCH_FIELD] = batch.clone();
+    }
+}
+
+#[derive(Default)]
+struct Batch {
+    size: u64,
+    checksum: String,
+    received: u64,
+    sum: u64,
+}
+
+/// What the verifier has read so far.
+#[derive(Default)]
+struct Verifier {
+    sequences: BTreeMap<String, Vec<u64>>,
+    /// The count and watermark of each generator's latest heartbeat.
+    heartbeats: BTreeMap<String, (u64, i64)>,
+    /// Batches by the slice that sent them, if any, and ID.
+    batches: HashMap<(String, u64), Batch>,
+    unnumbered: u64,
+}
+
+impl Verifier {
+    fn read(&mut self, event: &serde_json::Value) {
+        let beat = &event[heartbeat::FIELD];
+        if let (Some(generator), Some(count)) = (beat["generator"].as_str(), beat["count"].as_u64())
+        {
+            let latest = self.heartbeats.entry(generator.to_owned()).or_default();
+            if count >= latest.0 {
+                *latest = (count, beat["watermark"].as_i64().unwrap_or_default());
+            }
+        }
+
+        let (generator, seq) = match (event[GENERATOR_FIELD].as_str(), event[SEQ_FIELD].as_u64()) {
+            (Some(generator), Some(seq)) => (generator, seq),
+            _ => {
+                self.unnumbered += 1;
+                return;
+            }
+        };
+        self.sequences
+            .entry(generator.to_owned())
+            .or_default()
+            .push(seq);
+
+        let batch = &event[BATCH_FIELD];
+        if let Some(id) = batch["id"].as_u64() {
+            // Every worker in a cluster numbers its batches from 1.
+            let slice = generator
+                .split_once('@')
+                .map(|(_, s)| s)
+                .unwrap_or_default();
+            let key = (slice.to_owned(), id);
+            let entry = self.batches.entry(key).or_insert_with(|| Batch {
+                size: batch["size"].as_u64().unwrap_or_default(),
+                checksum: batch["checksum"].as_str().unwrap_or_default().to_owned(),
+                ..Default::default()
+            });
+            entry.received += 1;
+            entry.sum = entry.sum.wrapping_add(event_checksum(generator, seq));
+        }
+    }
+
+    /// Prints a report, returning the number of problems found.
+    fn report(&mut self) -> u64 {
+        let mut problems = 0;
+        for generator in self.heartbeats.keys() {
+            self.sequences.entry(generator.clone()).or_default();
+        }
+        for (generator, seqs) in self.sequences.iter_mut() {
+            seqs.sort_unstable();
+            let received = seqs.len();
+            seqs.dedup();
+            let duplicates = received - seqs.len();
+
+            let mut gaps = vec![];
+            let mut missing = 0;
+            let mut expected = 1;
+            for seq in seqs.iter() {
+                if *seq > expected {
+                    missing += seq - expected;
+                    gaps.push(match seq - expected {
+                        1 => expected.to_string(),
+                        _ => format!("{}-{}", expected, seq - 1),
+                    });
+                }
+                expected = seq + 1;
+            }
+            // Events after the last one received, up to the latest heartbeat.
+            let heartbeat = self.heartbeats.get(generator);
+            if let Some((count, _)) = heartbeat.filter(|(count, _)| *count >= expected) {
+                missing += count + 1 - expected;
+                gaps.push(match count + 1 - expected {
+                    1 => expected.to_string(),
+                    _ => format!("{}-{}", expected, count),
+                });
+                expected = count + 1;
+            }
+
+            println!(
+                "{}: received {} up to #{}, {} missing in {} gaps, {} duplicates",
+                generator,
+                received,
+                expected - 1,
+                missing,
+                gaps.len(),
+                duplicates
+            );
+            if !gaps.is_empty() {
+                println!("  missing: {}", gaps.join(", "));
+            }
+            if let Some((count, watermark)) = heartbeat {
+                println!(
+                    "  heartbeat: {} events up to {}, {}",
+                    count,
+                    heartbeat::watermark(*watermark),
+                    if missing == 0 {
+                        "complete"
+                    } else {
+                        "incomplete"
+                    }
+                );
+            }
+            problems += missing + duplicates as u64;
+        }
+
+        let mut keys: Vec<&(String, u64)> = self.batches.keys().collect();
+        keys.sort_unstable();
+        let mut bad = 0;
+        for key in keys {
+            let batch = &self.batches[key];
+            let checksum = format!("{:016x}", batch.sum);
+            if batch.received != batch.size || checksum != batch.checksum {
+                bad += 1;
+                let id = match key {
+                    (slice, id) if slice.is_empty() => id.to_string(),
+                    (slice, id) => format!("{}@{}", id, slice),
+                };
+                println!(
+                    "batch {}: received {} of {} events, checksum {} (expected {})",
+                    id, batch.received, batch.size, checksum, batch.checksum
+                );
+            }
+        }
+        println!(
+            "{} batches checked, {} failed; {} events without a sequence number",
+            self.batches.len(),
+            bad,
+            self.unnumbered
+        );
+        return problems + bad;
+    }
+}
+
+/// Verifies newline-delimited JSON events from `input`. Returns an error if
+/// any events were lost or duplicated.
+pub fn verify(input: impl BufRead) -> Result<(), String> {
+    let mut verifier = Verifier::default();
+    for line in input.lines() {
+        let line = line.map_err(|e| format!("could not read input: {}", e))?;
+        if let Ok(event) = serde_json::from_str::<serde_json::Value>(&line) {
+            verifier.read(&event);
+        }
+    }
+
+    let problems = verifier.report();
+    if problems > 0 {
+        return Err(format!("{} delivery problems found", problems));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_gaps_duplicates_and_incomplete_batches() {
+        let mut events: Vec<serde_json::Value> = (0..6).map(|_| json!({})).collect();
+        for event in events.iter_mut() {
+            number("sequence-test", event);
+        }
+        seal(&mut events[..3]);
+        seal(&mut events[3..]);
+
+        let mut verifier = Verifier::default();
+        for event in events.iter() {
+            verifier.read(event);
+        }
+        assert_eq!(verifier.report(), 0);
+
+        // Lose the second event and deliver the fifth twice.
+        let mut verifier = Verifier::default();
+        for i in [0, 2, 3, 4, 4, 5] {
+            verifier.read(&events[i]);
+        }
+        assert_eq!(verifier.sequences["sequence-test"].len(), 6);
+        // One missing event, one duplicate, and both batches fail.
+        assert_eq!(verifier.report(), 4);
+    }
+
+    #[test]
+    fn tells_slices_apart() {
+        // Two workers' events, with the same sequence numbers and batch ID.
+        let mut verifier = Verifier::default();
+        for slice in ["slice-test@1/2", "slice-test@2/2"] {
+            let mut events: Vec<serde_json::Value> = (1..=3)
+                .map(|seq| json!({ (GENERATOR_FIELD): slice, (SEQ_FIELD): seq }))
+                .collect();
+            seal(&mut events);
+            for event in events.iter_mut() {
+                event[BATCH_FIELD]["id"] = json!(1);
+                verifier.read(event);
+            }
+        }
+        assert_eq!(verifier.sequences.len(), 2);
+        assert_eq!(verifier.report(), 0);
+    }
+
+    #[test]
+    fn heartbeats_reveal_lost_tails() {
+        let mut events: Vec<serde_json::Value> = (0..4).map(|_| json!({})).collect();
+        for event in events.iter_mut() {
+            number("heartbeat-test", event);
+        }
+        let beat = json!({
+            (heartbeat::FIELD): { "generator": "heartbeat-test", "count": 4, "watermark": 0 },
+        });
+
+        // The last two events never arrive, but the heartbeat does.
+        let mut verifier = Verifier::default();
+        for event in events[..2].iter().chain([&beat]) {
+            verifier.read(event);
+        }
+        assert_eq!(verifier.report(), 2);
+    }
+}
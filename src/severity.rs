@@ -0,0 +1,58 @@
+/*!
+Severity of generated events.
+
+Generators set `status` from what they produce: ERROR for 5xx responses and
+leaked card numbers, WARNING for rejected connections, and the log level for
+application logs. Anything else is INFO. Optionally, a percentage of events is
+given a deliberately wrong status, so students have to normalize it from the
+message instead of trusting the field. dynamo's own markers and heartbeats
+always keep theirs.
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{self, json};
+
+use crate::percent::Percent;
+use crate::{heartbeat, random};
+
+pub const STATUSES: [&str; 4] = ["DEBUG", "INFO", "WARNING", "ERROR"];
+
+/// Generators whose events are about the run rather than simulated.
+const EXEMPT: [&str; 2] = ["markers", heartbeat::GENERATOR];
+
+static MISMATCH_PERCENT: Percent = Percent::new();
+static MISMATCHED: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_mismatch_percent(percent: f64) {
+    MISMATCH_PERCENT.set(percent);
+}
+
+/// The status for a log level as written in a log line.
+pub fn from_level(level: &str) -> &'static str {
+    return match level {
+        "DEBUG" => "DEBUG",
+        "WARN" => "WARNING",
+        "ERROR" => "ERROR",
+        _ => "INFO",
+    };
+}
+
+/// Possibly replaces the status of an event from `generator` with a
+/// different, wrong one.
+pub fn mismatch(generator: &str, val: &mut serde_json::Value) {
+    if EXEMPT.contains(&generator) || !MISMATCH_PERCENT.chance() {
+        return;
+    }
+
+    let actual = val["status"].as_str().unwrap_or("INFO").to_owned();
+    let wrong: Vec<&str> = STATUSES.into_iter().filter(|s| *s != actual).collect();
+    val["status"] = json!(random::pick(&wrong));
+    MISMATCHED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn summary() -> serde_json::Value {
+    return json!({
+        "mismatch_percent": MISMATCH_PERCENT.get(),
+        "mismatched": MISMATCHED.load(Ordering::Relaxed),
+    });
+}
@@ -0,0 +1,140 @@
+/*!
+Traffic shaping over the day and the week.
+
+Each day of the week has a rate multiplier, so traffic can dip at weekends and
+peak on Mondays like real business traffic, and a diurnal curve can raise
+traffic in the afternoon and lower it overnight on top of that. Every
+generator's rate is scaled by the multiplier for the current time, in UTC:
+live generators space their events further apart or closer together, and
+`dynamo export --backfill` renders the shape into a bundle of past traffic, so
+a week of history looks like business traffic on dashboards.
+*/
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+use chrono::prelude::*;
+
+const DAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+/// The hour of the day, in UTC, at which the diurnal curve peaks.
+const PEAK_HOUR: f64 = 15.0;
+
+static WEEKDAY_RATES: OnceLock<WeekdayRates> = OnceLock::new();
+static DIURNAL_AMPLITUDE: OnceLock<f64> = OnceLock::new();
+
+/// Rate multipliers from Monday to Sunday.
+#[derive(Clone, Debug)]
+pub struct WeekdayRates([f64; 7]);
+
+/// Parses weekday rates like `mon=1.4,sat=0.5,sunday=0.4`, with days named in
+/// full or by their first three letters. Days that are left out keep a
+/// multiplier of one.
+pub fn parse_weekday_rates(s: &str) -> Result<WeekdayRates, String> {
+    let mut rates = [1.0; 7];
+    for part in s.split(',').filter(|p| !p.is_empty()) {
+        let (day, rate) = part
+            .split_once('=')
+            .ok_or_else(|| format!("expected day=multiplier, got `{}`", part))?;
+        let name = day.trim().to_ascii_lowercase();
+        let index = DAYS
+            .iter()
+            .position(|d| name == *d || name == d[..3])
+            .ok_or_else(|| format!("unknown day of the week `{}`", day))?;
+        rates[index] = match rate.trim().parse::<f64>() {
+            Ok(rate) if rate >= 0.0 && rate.is_finite() => rate,
+            _ => return Err(format!("invalid multiplier `{}` for {}", rate, day)),
+        };
+    }
+    return Ok(WeekdayRates(rates));
+}
+
+/// Parses how far the diurnal curve swings above and below the day's rate,
+/// as a fraction of it.
+pub fn parse_diurnal_amplitude(s: &str) -> Result<f64, String> {
+    return match s.parse::<f64>() {
+        Ok(amplitude) if (0.0..=1.0).contains(&amplitude) => Ok(amplitude),
+        _ => Err(format!("`{}` is not an amplitude from 0 to 1", s)),
+    };
+}
+
+pub fn set_weekday_rates(rates: WeekdayRates) {
+    WEEKDAY_RATES
+        .set(rates)
+        .expect("weekday rates should only be set once");
+}
+
+pub fn set_diurnal_amplitude(amplitude: f64) {
+    DIURNAL_AMPLITUDE
+        .set(amplitude)
+        .expect("diurnal amplitude should only be set once");
+}
+
+fn shaped(rates: Option<&WeekdayRates>, amplitude: f64, at: DateTime<Utc>) -> f64 {
+    let day = match rates {
+        Some(rates) => rates.0[at.weekday().num_days_from_monday() as usize],
+        None => 1.0,
+    };
+    let hour = at.num_seconds_from_midnight() as f64 / 3600.0;
+    let diurnal = 1.0 + amplitude * (2.0 * PI * (hour - PEAK_HOUR) / 24.0).cos();
+    return day * diurnal;
+}
+
+/// How much every generator's rate is scaled by at `at`.
+pub fn multiplier(at: DateTime<Utc>) -> f64 {
+    let amplitude = DIURNAL_AMPLITUDE.get().copied().unwrap_or(0.0);
+    return shaped(WEEKDAY_RATES.get(), amplitude, at);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+
+    use super::{parse_diurnal_amplitude, parse_weekday_rates, shaped};
+
+    #[test]
+    fn parses_days() {
+        let rates = parse_weekday_rates("mon=1.4,Saturday=0.5, sun = 0.4").unwrap();
+        assert_eq!(rates.0, [1.4, 1.0, 1.0, 1.0, 1.0, 0.5, 0.4]);
+        assert_eq!(parse_weekday_rates("").unwrap().0, [1.0; 7]);
+
+        for bad in [
+            "monkey=2",
+            "sunshine=0",
+            "mo=1",
+            "tues=1",
+            "mon",
+            "mon=-1",
+            "mon=x",
+        ] {
+            assert!(parse_weekday_rates(bad).is_err(), "{}", bad);
+        }
+        assert_eq!(parse_diurnal_amplitude("0.5"), Ok(0.5));
+        assert!(parse_diurnal_amplitude("1.5").is_err());
+    }
+
+    #[test]
+    fn shapes_by_day_and_hour() {
+        let rates = parse_weekday_rates("mon=1.4,sun=0").unwrap();
+        // 2024-01-01 was a Monday.
+        let monday = |hour| Utc.with_ymd_and_hms(2024, 1, 1, hour, 0, 0).unwrap();
+        let sunday = Utc.with_ymd_and_hms(2024, 1, 7, 15, 0, 0).unwrap();
+
+        assert_eq!(shaped(None, 0.0, monday(3)), 1.0);
+        assert_eq!(shaped(Some(&rates), 0.0, monday(3)), 1.4);
+        assert_eq!(shaped(Some(&rates), 0.5, sunday), 0.0);
+
+        let peak = shaped(Some(&rates), 0.5, monday(15));
+        let trough = shaped(Some(&rates), 0.5, monday(3));
+        let evening = shaped(Some(&rates), 0.5, monday(21));
+        assert!((peak - 2.1).abs() < 1e-9, "{}", peak);
+        assert!((trough - 0.7).abs() < 1e-9, "{}", trough);
+        assert!((evening - 1.4).abs() < 1e-9, "{}", evening);
+    }
+}
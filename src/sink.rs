@@ -0,0 +1,340 @@
+/*!
+Delivery of batched events to Vector.
+
+The sender encodes each batch for the configured sink, sends it, and accounts
+for every event in the per-generator stats: sent if Vector accepted the
+request, dropped otherwise. Encoding is independent of the generators; see
+the `encoding` module.
+*/
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::ValueEnum;
+use flate2::write::GzEncoder;
+use reqwest::header::HeaderValue;
+
+use crate::agents::{self, Agent};
+use crate::encoding::{self, Encoding};
+use crate::failures::{self, Failure};
+use crate::stats::{self, Counters};
+use crate::{latency, otlp, sequence};
+
+/// A generated event, along with the counters of the generator it came from.
+pub struct Event {
+    pub counters: &'static Counters,
+    pub value: serde_json::Value,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Sink {
+    /// Vector's `datadog_agent` source.
+    DatadogAgent,
+    /// Vector's `opentelemetry` source, over gRPC.
+    Otlp,
+    /// Any HTTP endpoint, such as Vector's `http_server` source, in the
+    /// configured encoding.
+    Http,
+    /// A local file, for Vector's `file` source, one event per line in the
+    /// configured encoding.
+    File,
+}
+
+impl Sink {
+    /// The encoding the sink uses when none is configured.
+    pub fn default_encoding(&self) -> Encoding {
+        return match self {
+            Sink::DatadogAgent => Encoding::JsonArray,
+            Sink::Http | Sink::File => Encoding::Json,
+            Sink::Otlp => Encoding::Protobuf,
+        };
+    }
+
+    /// Checks that the sink can send `encoding`. Only the generic HTTP and
+    /// file sinks can send anything but their default, and files can only
+    /// hold line encodings.
+    pub fn check_encoding(&self, encoding: Encoding) -> Result<(), String> {
+        if *self == Sink::File && !encoding.is_line() {
+            return Err("the file sink only supports line encodings".to_owned());
+        }
+        if ![Sink::Http, Sink::File].contains(self) && encoding != self.default_encoding() {
+            return Err(format!(
+                "the {} sink only supports the {} encoding; use --sink http for others",
+                name(self),
+                name(&self.default_encoding())
+            ));
+        }
+        return Ok(());
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+}
+
+pub struct Sender {
+    pub sink: Sink,
+    pub encoding: Encoding,
+    pub compression: Compression,
+    pub datadog_agent_address: String,
+    pub http_target: String,
+    pub file_path: String,
+    /// Send each batch as one of several mimicked log shipping agents.
+    pub mimic_agents: bool,
+    /// Event fields the HTTP sink sends as headers, as (header, field) pairs.
+    pub header_fields: Vec<(String, String)>,
+    pub otlp_target: String,
+    pub otlp_stress: Option<otlp::StressMode>,
+    pub logs_client: reqwest::Client,
+    pub otlp_client: reqwest::Client,
+}
+
+/// HTTP headers promoted from event fields, as (header, value) pairs.
+type Headers = Vec<(String, String)>;
+
+/// Parses a `header=field` pair for promoting an event field to an HTTP
+/// header. The field may be a dotted path into nested objects.
+pub fn parse_header_field(s: &str) -> Result<(String, String), String> {
+    let (header, field) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected header=field, got `{}`", s))?;
+    reqwest::header::HeaderName::from_bytes(header.trim().as_bytes())
+        .map_err(|_| format!("`{}` is not a valid header name", header))?;
+    if field.trim().is_empty() {
+        return Err(format!("no field given for header `{}`", header));
+    }
+    return Ok((header.trim().to_owned(), field.trim().to_owned()));
+}
+
+/// Whether a promoted value has had to be percent-encoded yet.
+static ENCODED_HEADER: AtomicBool = AtomicBool::new(false);
+
+/// The value of the field at a dotted `path`, as header text. Values that
+/// aren't valid in a header, such as multi-line messages, have their control
+/// characters and `%` percent-encoded rather than failing the request.
+fn field_text(event: &serde_json::Value, path: &str) -> Option<String> {
+    let value = path.split('.').fold(event, |value, key| &value[key]);
+    let text = match value {
+        serde_json::Value::Null => return None,
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    if HeaderValue::from_str(&text).is_ok() {
+        return Some(text);
+    }
+
+    if !ENCODED_HEADER.swap(true, Ordering::Relaxed) {
+        println!(
+            "Field `{}` has values that aren't valid in a header; percent-encoding them",
+            path
+        );
+    }
+    let mut encoded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '%' | '\u{0}'..='\u{1f}' | '\u{7f}' => encoded.push_str(&format!("%{:02X}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    return Some(encoded);
+}
+
+/// Splits `total` bytes between events in proportion to their `sizes`,
+/// handing any rounding remainder to the last event.
+fn attribute(sizes: &[u64], total: u64) -> Vec<u64> {
+    let sum: u64 = sizes.iter().sum::<u64>().max(1);
+    let mut shares: Vec<u64> = sizes.iter().map(|s| s * total / sum).collect();
+    let remainder = total - shares.iter().sum::<u64>();
+    if let Some(last) = shares.last_mut() {
+        *last += remainder;
+    }
+    return shares;
+}
+
+/// The name of a value as given on the command line.
+fn name(value: &impl ValueEnum) -> String {
+    return value
+        .to_possible_value()
+        .expect("values should not be skipped")
+        .get_name()
+        .to_owned();
+}
+
+fn gzip(body: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], flate2::Compression::default());
+    encoder
+        .write_all(body)
+        .expect("compressing in memory should not fail");
+    return encoder
+        .finish()
+        .expect("compressing in memory should not fail");
+}
+
+impl Sender {
+    async fn send_datadog_agent(
+        &self,
+        body: Vec<u8>,
+        agent: Option<&Agent>,
+    ) -> Result<(), Failure> {
+        let mut request = self.logs_client.post(&self.datadog_agent_address);
+        if self.compression == Compression::Gzip {
+            request = request.header("content-encoding", "gzip");
+        }
+        for (name, value) in agent.map(|a| a.headers).unwrap_or_default() {
+            request = request.header(*name, *value);
+        }
+
+        let response = request
+            .header("content-type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| failures::request(&self.datadog_agent_address, &e))?;
+        if !response.status().is_success() {
+            return Err(failures::status(
+                &self.datadog_agent_address,
+                response.status(),
+            ));
+        }
+        return Ok(());
+    }
+
+    async fn send_http(
+        &self,
+        body: Vec<u8>,
+        agent: Option<&Agent>,
+        headers: &[(String, String)],
+    ) -> Result<(), Failure> {
+        let mut request = self.logs_client.post(&self.http_target);
+        if self.compression == Compression::Gzip {
+            request = request.header("content-encoding", "gzip");
+        }
+        for (name, value) in agent.map(|a| a.headers).unwrap_or_default() {
+            request = request.header(*name, *value);
+        }
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+
+        let response = request
+            .header("content-type", self.encoding.content_type())
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| failures::request(&self.http_target, &e))?;
+        if !response.status().is_success() {
+            return Err(failures::status(&self.http_target, response.status()));
+        }
+        return Ok(());
+    }
+
+    fn append_to_file(&self, body: &[u8]) -> Result<(), Failure> {
+        return std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file_path)
+            .and_then(|mut file| file.write_all(body))
+            .map_err(|e| failures::other(&self.file_path, e));
+    }
+
+    /// Sends a batch. With header promotion on the HTTP sink, events are
+    /// grouped by their promoted field values and each group is sent as its
+    /// own request, in order of first appearance.
+    pub async fn send(&self, batch: Vec<Event>) {
+        if self.sink != Sink::Http || self.header_fields.is_empty() {
+            return self.send_batch(batch, &[]).await;
+        }
+
+        let mut groups: Vec<(Headers, Vec<Event>)> = vec![];
+        for event in batch {
+            let headers: Headers = self
+                .header_fields
+                .iter()
+                .filter_map(|(header, field)| {
+                    return field_text(&event.value, field).map(|v| (header.clone(), v));
+                })
+                .collect();
+            match groups.iter_mut().find(|(h, _)| *h == headers) {
+                Some((_, events)) => events.push(event),
+                None => groups.push((headers, vec![event])),
+            }
+        }
+        for (headers, events) in groups {
+            self.send_batch(events, &headers).await;
+        }
+    }
+
+    async fn send_batch(&self, batch: Vec<Event>, headers: &[(String, String)]) {
+        let mut values: Vec<serde_json::Value> = batch.iter().map(|e| e.value.clone()).collect();
+        let agent = self.mimic_agents.then(agents::pick);
+        if let Some(agent) = agent {
+            values.iter_mut().for_each(|v| agent.apply(v));
+        }
+        latency::stamp(&mut values);
+        sequence::seal(&mut values);
+
+        let (sizes, body): (Vec<u64>, Vec<u8>) = match self.sink {
+            Sink::Otlp => {
+                let body = otlp::encode(&values, self.otlp_stress.as_ref());
+                let sizes = values.iter().map(|v| v.to_string().len() as u64).collect();
+                (sizes, body)
+            }
+            Sink::DatadogAgent | Sink::Http | Sink::File => encoding::batch(self.encoding, &values),
+        };
+
+        let uncompressed = body.len() as u64;
+        let body = match (self.sink, self.compression) {
+            (Sink::DatadogAgent | Sink::Http, Compression::Gzip) => gzip(&body),
+            _ => body,
+        };
+        let wire = body.len() as u64;
+
+        let result = match self.sink {
+            Sink::DatadogAgent => self.send_datadog_agent(body, agent).await,
+            Sink::Otlp => otlp::export(&self.otlp_client, &self.otlp_target, body).await,
+            Sink::Http => self.send_http(body, agent, headers).await,
+            Sink::File => self.append_to_file(&body),
+        };
+        if let Err(failure) = &result {
+            failures::record(failure);
+            println!("{}", failure);
+        }
+
+        let uncompressed_shares = attribute(&sizes, uncompressed);
+        let wire_shares = attribute(&sizes, wire);
+        for (i, event) in batch.iter().enumerate() {
+            let counters = event.counters;
+            stats::add(&counters.bytes_uncompressed, uncompressed_shares[i]);
+            stats::add(&counters.bytes_wire, wire_shares[i]);
+            match result {
+                Ok(_) => stats::add(&counters.sent, 1),
+                Err(_) => stats::add(&counters.dropped, 1),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn promotes_fields_to_valid_headers() {
+        let event = json!({
+            "service": "payment-gateway",
+            "http": { "status": 502 },
+            "message": "<Fault>\r\n  <Code>50%</Code>\n</Fault>",
+        });
+        assert_eq!(
+            super::field_text(&event, "service").unwrap(),
+            "payment-gateway"
+        );
+        assert_eq!(super::field_text(&event, "http.status").unwrap(), "502");
+        assert_eq!(super::field_text(&event, "missing"), None);
+
+        let message = super::field_text(&event, "message").unwrap();
+        assert_eq!(message, "<Fault>%0D%0A  <Code>50%25</Code>%0A</Fault>");
+        assert!(super::HeaderValue::from_str(&message).is_ok());
+    }
+}
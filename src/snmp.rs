@@ -0,0 +1,352 @@
+/*!
+SNMP traps from the fleet's agents, sent over UDP.
+
+Each trap is an SNMPv2c `SNMPv2-Trap-PDU` as net-snmp's `snmpd` sends it, for
+network operations lessons with a trap receiver such as `snmptrapd`. dynamo
+sends every trap itself, so each carries `snmpTrapAddress.0` and `sysName.0`
+naming the simulated agent, the way a trap forwarder would.
+
+Most traps are link flaps, `linkDown` or `linkUp` on a host's interface. High
+CPU traps are DISMAN-EVENT-MIB `mteTriggerFired` notifications from a
+`monitor` directive on `hrProcessorLoad`, and follow the incidents that load
+a host: the compromised host's miner while the crypto mining incident is
+active, and the payments service's host once the dependency cascade has
+reached it.
+*/
+use std::time::Duration;
+
+use serde_json::{self, json};
+use tokio::net::UdpSocket;
+
+use crate::hosts::{self, Host};
+use crate::{cascade, clock, mining, pacing, random, stats, warmup};
+
+pub const GENERATOR: &str = "snmp-traps";
+const COMMUNITY: &str = "public";
+/// Hosts restart for patching monthly, so uptimes wrap after 30 days.
+const UPTIME_PERIOD_S: i64 = 30 * 86_400;
+/// The CPU load above which `snmpd`'s monitor fires.
+const CPU_THRESHOLD_PCT: u64 = 90;
+/// Share of traps from a loaded host while an incident is loading it.
+const LOADED_SHARE: f64 = 0.6;
+
+const SYS_UPTIME: &str = "1.3.6.1.2.1.1.3.0";
+const SYS_NAME: &str = "1.3.6.1.2.1.1.5.0";
+const SNMP_TRAP_OID: &str = "1.3.6.1.6.3.1.1.4.1.0";
+const SNMP_TRAP_ADDRESS: &str = "1.3.6.1.6.3.18.1.3.0";
+const LINK_DOWN: &str = "1.3.6.1.6.3.1.1.5.3";
+const LINK_UP: &str = "1.3.6.1.6.3.1.1.5.4";
+const MTE_TRIGGER_FIRED: &str = "1.3.6.1.2.1.88.2.0.1";
+/// `hrProcessorLoad` for the first CPU.
+const HR_PROCESSOR_LOAD: &str = "1.3.6.1.2.1.25.3.3.1.2.196608";
+/// Interfaces on each host, by `ifIndex`.
+const INTERFACES: [(u64, &str); 2] = [(2, "ens5"), (3, "docker0")];
+
+fn varbind(oid: &str, kind: &str, value: serde_json::Value) -> serde_json::Value {
+    return json!({ "oid": oid, "type": kind, "value": value });
+}
+
+/// A trap from `host`, with the varbinds every trap starts with.
+fn trap(
+    host: &Host,
+    name: &str,
+    trap_oid: &str,
+    varbinds: Vec<serde_json::Value>,
+) -> serde_json::Value {
+    let uptime_cs = clock::now().timestamp().rem_euclid(UPTIME_PERIOD_S) * 100;
+    let mut all = vec![
+        varbind(SYS_UPTIME, "timeticks", json!(uptime_cs)),
+        varbind(SNMP_TRAP_OID, "oid", json!(trap_oid)),
+        varbind(SNMP_TRAP_ADDRESS, "ip-address", json!(host.ip)),
+        varbind(SYS_NAME, "octet-string", json!(host.hostname)),
+    ];
+    all.extend(varbinds);
+    return json!({
+        "hostname": host.hostname,
+        "trap": name,
+        "request_id": random::range(1..i32::MAX as i64),
+        "varbinds": all,
+    });
+}
+
+fn link(host: &Host) -> serde_json::Value {
+    let (index, name) = random::pick(&INTERFACES);
+    let up = random::chance(0.5);
+    let (trap_name, trap_oid) = if up {
+        ("linkUp", LINK_UP)
+    } else {
+        ("linkDown", LINK_DOWN)
+    };
+    return trap(
+        host,
+        trap_name,
+        trap_oid,
+        vec![
+            varbind(
+                &format!("1.3.6.1.2.1.2.2.1.1.{}", index),
+                "integer",
+                json!(index),
+            ),
+            varbind(
+                &format!("1.3.6.1.2.1.2.2.1.7.{}", index),
+                "integer",
+                json!(1),
+            ),
+            varbind(
+                &format!("1.3.6.1.2.1.2.2.1.8.{}", index),
+                "integer",
+                json!(if up { 1 } else { 2 }),
+            ),
+            varbind(
+                &format!("1.3.6.1.2.1.2.2.1.2.{}", index),
+                "octet-string",
+                json!(name),
+            ),
+        ],
+    );
+}
+
+fn high_cpu(host: &Host) -> serde_json::Value {
+    let load: u64 = random::range(CPU_THRESHOLD_PCT + 1..=100);
+    return trap(
+        host,
+        "mteTriggerFired",
+        MTE_TRIGGER_FIRED,
+        vec![
+            varbind("1.3.6.1.2.1.88.2.1.1.0", "octet-string", json!("cpuHigh")),
+            varbind("1.3.6.1.2.1.88.2.1.2.0", "octet-string", json!("")),
+            varbind("1.3.6.1.2.1.88.2.1.3.0", "octet-string", json!("")),
+            varbind("1.3.6.1.2.1.88.2.1.4.0", "oid", json!(HR_PROCESSOR_LOAD)),
+            varbind("1.3.6.1.2.1.88.2.1.5.0", "integer", json!(load)),
+        ],
+    );
+}
+
+/// A trap, as JSON with the varbinds in the order they are encoded.
+pub fn generate() -> serde_json::Value {
+    let mut loaded = vec![];
+    if mining::active() {
+        loaded.push(&hosts::COMPROMISED);
+    }
+    if cascade::impacted(cascade::ORIGIN) {
+        loaded.push(hosts::backend(cascade::ORIGIN));
+    }
+    return trap_from(&loaded);
+}
+
+/// A trap from the fleet, where the `loaded` hosts are running hot.
+fn trap_from(loaded: &[&'static Host]) -> serde_json::Value {
+    if !loaded.is_empty() && random::chance(LOADED_SHARE) {
+        let host: &&Host = random::pick(loaded);
+        return high_cpu(host);
+    }
+
+    let linux: Vec<&Host> = hosts::all()
+        .filter(|h| h.platform == hosts::Platform::Linux)
+        .collect();
+    let host: &&Host = random::pick(&linux);
+    return link(host);
+}
+
+/// Appends a BER tag, length and contents.
+fn tlv(buf: &mut Vec<u8>, tag: u8, contents: &[u8]) {
+    buf.push(tag);
+    let len = contents.len();
+    if len < 0x80 {
+        buf.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        buf.push(0x80 | bytes.len() as u8);
+        buf.extend_from_slice(&bytes);
+    }
+    buf.extend_from_slice(contents);
+}
+
+/// The shortest two's complement encoding of `value`.
+fn integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7
+        && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    return bytes[start..].to_vec();
+}
+
+fn oid(dotted: &str) -> Result<Vec<u8>, String> {
+    let arcs: Vec<u64> = dotted
+        .split('.')
+        .map(|arc| arc.parse().map_err(|_| format!("invalid OID {}", dotted)))
+        .collect::<Result<_, _>>()?;
+    if arcs.len() < 2 {
+        return Err(format!("invalid OID {}", dotted));
+    }
+    let mut buf = vec![];
+    for arc in std::iter::once(arcs[0] * 40 + arcs[1]).chain(arcs[2..].iter().copied()) {
+        let mut groups = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            groups.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        buf.extend(groups.into_iter().rev());
+    }
+    return Ok(buf);
+}
+
+fn value(buf: &mut Vec<u8>, varbind: &serde_json::Value) -> Result<(), String> {
+    let value = &varbind["value"];
+    match varbind["type"].as_str().unwrap_or_default() {
+        "integer" => tlv(buf, 0x02, &integer(value.as_i64().unwrap_or_default())),
+        "octet-string" => tlv(buf, 0x04, value.as_str().unwrap_or_default().as_bytes()),
+        "oid" => tlv(buf, 0x06, &oid(value.as_str().unwrap_or_default())?),
+        "ip-address" => {
+            let ip: std::net::Ipv4Addr = value
+                .as_str()
+                .unwrap_or_default()
+                .parse()
+                .map_err(|_| format!("invalid IP address {}", value))?;
+            tlv(buf, 0x40, &ip.octets());
+        }
+        // TimeTicks are unsigned, so a leading zero keeps large values
+        // positive.
+        "timeticks" => tlv(buf, 0x43, &integer(value.as_i64().unwrap_or_default())),
+        kind => return Err(format!("unknown varbind type {}", kind)),
+    }
+    return Ok(());
+}
+
+/// Encodes a trap as an SNMPv2c message.
+pub fn encode(trap: &serde_json::Value) -> Result<Vec<u8>, String> {
+    let mut varbinds = vec![];
+    for varbind in trap["varbinds"].as_array().into_iter().flatten() {
+        let mut contents = vec![];
+        tlv(
+            &mut contents,
+            0x06,
+            &oid(varbind["oid"].as_str().unwrap_or_default())?,
+        );
+        value(&mut contents, varbind)?;
+        tlv(&mut varbinds, 0x30, &contents);
+    }
+
+    let mut pdu = vec![];
+    tlv(
+        &mut pdu,
+        0x02,
+        &integer(trap["request_id"].as_i64().unwrap_or_default()),
+    );
+    tlv(&mut pdu, 0x02, &integer(0)); // error-status
+    tlv(&mut pdu, 0x02, &integer(0)); // error-index
+    tlv(&mut pdu, 0x30, &varbinds);
+
+    let mut message = vec![];
+    tlv(&mut message, 0x02, &integer(1)); // version: v2c
+    tlv(&mut message, 0x04, COMMUNITY.as_bytes());
+    tlv(&mut message, 0xa7, &pdu);
+
+    let mut buf = vec![];
+    tlv(&mut buf, 0x30, &message);
+    return Ok(buf);
+}
+
+/// Sends traps to the receiver at `target` at `rate_limit_per_s`. A zero
+/// rate disables the generator.
+pub fn start(target: String, rate_limit_per_s: usize) {
+    if rate_limit_per_s == 0 {
+        return;
+    }
+
+    let mut pacer = pacing::for_rate(rate_limit_per_s);
+    let counters = stats::counters(GENERATOR);
+    tokio::spawn(async move {
+        let socket = match UdpSocket::bind("0.0.0.0:0").await {
+            Ok(socket) => socket,
+            Err(e) => {
+                println!("Could not open a socket for SNMP traps: {}", e);
+                return;
+            }
+        };
+        let mut report_at = tokio::time::Instant::now();
+        loop {
+            pacer.acquire().await;
+            if !warmup::admit() {
+                continue;
+            }
+
+            let message = match encode(&generate()) {
+                Ok(message) => message,
+                Err(e) => {
+                    println!("Could not encode an SNMP trap: {}", e);
+                    continue;
+                }
+            };
+            stats::add(&counters.generated, 1);
+            stats::add(&counters.bytes_uncompressed, message.len() as u64);
+            stats::add(&counters.bytes_wire, message.len() as u64);
+
+            // Failures repeat for every trap, so report them at most once
+            // a second.
+            match socket.send_to(&message, &target).await {
+                Ok(_) => stats::add(&counters.sent, 1),
+                Err(e) => {
+                    if tokio::time::Instant::now() >= report_at {
+                        println!("Could not send SNMP trap to {}: {}", target, e);
+                        report_at = tokio::time::Instant::now() + Duration::from_secs(1);
+                    }
+                    stats::add(&counters.dropped, 1);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::hosts;
+
+    #[test]
+    fn loaded_hosts_report_high_cpu() {
+        let loaded = [&hosts::COMPROMISED];
+        let traps: Vec<serde_json::Value> = (0..50).map(|_| super::trap_from(&loaded)).collect();
+        assert!(traps.iter().any(
+            |t| t["trap"] == "mteTriggerFired" && t["hostname"] == hosts::COMPROMISED.hostname
+        ));
+    }
+
+    #[test]
+    fn encodes_ber() {
+        assert_eq!(super::integer(0), [0]);
+        assert_eq!(super::integer(128), [0, 128]);
+        assert_eq!(super::integer(-1), [0xff]);
+        assert_eq!(super::integer(256), [1, 0]);
+        assert_eq!(
+            super::oid("1.3.6.1.2.1.88.2.0.1").unwrap(),
+            [0x2b, 6, 1, 2, 1, 0x58, 2, 0, 1]
+        );
+        assert_eq!(super::oid("1.3.6.1.4.1.2021").unwrap()[5..], [0x8f, 0x65]);
+    }
+
+    #[test]
+    fn encodes_traps() {
+        let trap = json!({
+            "request_id": 1,
+            "varbinds": [{ "oid": "1.3.6.1.2.1.1.3.0", "type": "timeticks", "value": 5 }],
+        });
+        let mut expected = vec![0x30, 39, 0x02, 1, 1, 0x04, 6];
+        expected.extend(b"public");
+        expected.extend([
+            0xa7, 26, 0x02, 1, 1, 0x02, 1, 0, 0x02, 1, 0, 0x30, 15, 0x30, 13,
+        ]);
+        expected.extend([0x06, 8, 0x2b, 6, 1, 2, 1, 1, 3, 0, 0x43, 1, 5]);
+        assert_eq!(super::encode(&trap).unwrap(), expected);
+    }
+}
@@ -0,0 +1,81 @@
+/*!
+SOAP payment gateway access records, as XML.
+
+storedog's payments service talks to its card processor through a legacy SOAP
+gateway that logs one XML record per call, for lessons on converting XML to
+JSON in a pipeline. While the dependency cascade has reached the payments
+service, gateway calls slow down and some of them end in SOAP faults.
+*/
+use serde_json::{self, json};
+
+use crate::{cascade, clock, hosts, random};
+
+pub const SERVICE: &str = "payment-gateway";
+const OPERATIONS: [&str; 4] = [
+    "AuthorizePayment",
+    "CapturePayment",
+    "RefundPayment",
+    "VoidAuthorization",
+];
+const ENDPOINT: &str = "https://gateway.cardprocessor.example/soap/v2";
+
+fn escape(s: &str) -> String {
+    return s
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;");
+}
+
+fn record() -> (String, bool) {
+    let slow = cascade::impacted(cascade::ORIGIN);
+    let fault = slow && random::chance(0.3);
+    let duration_ms = if slow {
+        random::range(2500..6000)
+    } else {
+        random::range(40..300)
+    };
+
+    let outcome = if fault {
+        "<Status code=\"500\">Fault</Status>\
+         <Fault><faultcode>soap:Server</faultcode>\
+         <faultstring>Upstream processor timed out</faultstring></Fault>"
+            .to_owned()
+    } else {
+        format!(
+            "<Status code=\"200\">OK</Status><AuthCode>{:06}</AuthCode>",
+            random::range(0..1_000_000)
+        )
+    };
+
+    let xml = format!(
+        "<GatewayAccess timestamp=\"{}\" requestId=\"{}\">\
+         <Client ip=\"{}\" merchantId=\"STOREDOG-01\"/>\
+         <Operation>{}</Operation>\
+         <Endpoint>{}</Endpoint>\
+         <Customer>{}</Customer>\
+         {}\
+         <DurationMs>{}</DurationMs>\
+         </GatewayAccess>",
+        clock::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+        random::uuid_v4(),
+        hosts::backend("storedog-payments").ip,
+        random::pick(&OPERATIONS),
+        escape(ENDPOINT),
+        escape(&random::username()),
+        outcome,
+        duration_ms,
+    );
+    return (xml, fault);
+}
+
+pub fn generate() -> serde_json::Value {
+    let (xml, fault) = record();
+    return json!({
+        "message": xml,
+        "service": SERVICE,
+        "hostname": hosts::backend("storedog-payments").hostname,
+        "status": if fault { "ERROR" } else { "INFO" },
+    });
+}
@@ -0,0 +1,114 @@
+/*!
+Single sign-on events from Storedog's identity provider.
+
+Employees sign in through Okta before they connect to the VPN, from the same
+home addresses the VPN logs show. Each event is an Okta System Log event,
+serialized as JSON in the message like the billing line items: mostly
+successful sign-ins, some MFA verifications and the occasional mistyped
+password.
+
+During the impossible travel incident, the traveler's account signs in from
+home and from the remote country in turn, from the same addresses as its VPN
+connections, so students can confirm the trip in a second source.
+*/
+use std::cell::Cell;
+
+use chrono::SecondsFormat;
+use serde_json::{self, json};
+
+use crate::vpn::{self, Employee};
+use crate::{clock, random};
+
+pub const GENERATOR: &str = "sso";
+pub const SERVICE: &str = "okta";
+const DOMAIN: &str = "storedog.example";
+/// Share of sign-ins from the traveler during the incident.
+const TRAVELER_SHARE: f64 = 0.5;
+
+thread_local! {
+    /// Whether the traveler's next sign-in comes from the remote country,
+    /// alternating like their VPN connections.
+    static NEXT_REMOTE: Cell<bool> = const { Cell::new(false) };
+}
+
+fn event(
+    username: &str,
+    ip: &str,
+    country: &str,
+    event_type: &str,
+    display_message: &str,
+    failure: Option<&str>,
+) -> serde_json::Value {
+    let outcome = match failure {
+        Some(reason) => json!({ "result": "FAILURE", "reason": reason }),
+        None => json!({ "result": "SUCCESS" }),
+    };
+    let severity = if failure.is_some() { "WARN" } else { "INFO" };
+    let record = json!({
+        "uuid": random::uuid_v4(),
+        "published": clock::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        "eventType": event_type,
+        "displayMessage": display_message,
+        "severity": severity,
+        "outcome": outcome,
+        "actor": {
+            "type": "User",
+            "alternateId": format!("{}@{}", username, DOMAIN),
+            "displayName": username,
+        },
+        "client": {
+            "ipAddress": ip,
+            "geographicalContext": { "country": country },
+        },
+    });
+    return json!({
+        "message": record.to_string(),
+        "service": SERVICE,
+        "status": severity,
+    });
+}
+
+fn sign_in(employee: &Employee, ip: &str, country: &str) -> serde_json::Value {
+    return event(
+        employee.username,
+        ip,
+        country,
+        "user.session.start",
+        "User login to Okta",
+        None,
+    );
+}
+
+pub fn generate() -> serde_json::Value {
+    if vpn::INCIDENT.active() && random::chance(TRAVELER_SHARE) {
+        let remote = NEXT_REMOTE.with(|next| next.replace(!next.get()));
+        return match remote {
+            true => sign_in(vpn::TRAVELER, vpn::REMOTE_IP, vpn::REMOTE_COUNTRY),
+            false => sign_in(vpn::TRAVELER, vpn::TRAVELER.ip, vpn::TRAVELER.country),
+        };
+    }
+
+    let employee = random::pick(&vpn::EMPLOYEES);
+    let roll: f64 = random::range(0.0..1.0);
+    if roll < 0.7 {
+        return sign_in(employee, employee.ip, employee.country);
+    }
+    if roll < 0.95 {
+        return event(
+            employee.username,
+            employee.ip,
+            employee.country,
+            "user.authentication.auth_via_mfa",
+            "Authentication of user via MFA",
+            None,
+        );
+    }
+    return event(
+        employee.username,
+        employee.ip,
+        employee.country,
+        "user.session.start",
+        "User login to Okta",
+        Some("INVALID_CREDENTIALS"),
+    );
+}
@@ -0,0 +1,110 @@
+/*!
+Per-generator throughput accounting.
+
+Every generator gets a set of counters tracking what actually happened to its
+events: how many were generated, how many Vector accepted, how many were
+dropped on the way, and how many bytes they took up before and after
+compression. Bytes are attributed to generators in proportion to their share
+of each request, so per-generator wire bytes add up to the request totals.
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use serde_json::{self, json};
+
+use crate::{failures, queue};
+
+pub struct Counters {
+    pub name: &'static str,
+    pub generated: AtomicU64,
+    pub sent: AtomicU64,
+    pub dropped: AtomicU64,
+    pub bytes_uncompressed: AtomicU64,
+    pub bytes_wire: AtomicU64,
+}
+
+static REGISTRY: Mutex<Vec<&'static Counters>> = Mutex::new(vec![]);
+
+/// The counters for `name`, created on first use.
+pub fn counters(name: &'static str) -> &'static Counters {
+    let mut registry = REGISTRY.lock().expect("stats lock should not be poisoned");
+    if let Some(counters) = registry.iter().find(|c| c.name == name) {
+        return counters;
+    }
+
+    let counters = Box::leak(Box::new(Counters {
+        name,
+        generated: AtomicU64::new(0),
+        sent: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+        bytes_uncompressed: AtomicU64::new(0),
+        bytes_wire: AtomicU64::new(0),
+    }));
+    registry.push(counters);
+    return counters;
+}
+
+pub fn add(counter: &AtomicU64, n: u64) {
+    counter.fetch_add(n, Ordering::Relaxed);
+}
+
+fn counts(counters: &[&Counters]) -> serde_json::Value {
+    let sum = |f: fn(&Counters) -> &AtomicU64| -> u64 {
+        return counters.iter().map(|c| f(c).load(Ordering::Relaxed)).sum();
+    };
+    return json!({
+        "events_generated": sum(|c| &c.generated),
+        "events_sent": sum(|c| &c.sent),
+        "events_dropped": sum(|c| &c.dropped),
+        "bytes_uncompressed": sum(|c| &c.bytes_uncompressed),
+        "bytes_wire": sum(|c| &c.bytes_wire),
+    });
+}
+
+pub fn snapshot() -> serde_json::Value {
+    let registry = REGISTRY.lock().expect("stats lock should not be poisoned");
+    let mut generators = serde_json::Map::new();
+    for counters in registry.iter() {
+        generators.insert(counters.name.to_owned(), counts(&[counters]));
+    }
+
+    return json!({
+        "generators": generators,
+        "total": counts(&registry),
+        "send_failures": failures::summary(),
+        "queue": queue::snapshot(),
+    });
+}
+
+/// What happened to the run's events, printed on exit.
+pub fn summary() -> String {
+    let snapshot = snapshot();
+    let total = &snapshot["total"];
+    let mut summary = format!(
+        "Generated {} events: {} sent, {} dropped, {} bytes on the wire",
+        total["events_generated"],
+        total["events_sent"],
+        total["events_dropped"],
+        total["bytes_wire"]
+    );
+    let failures: Vec<String> = snapshot["send_failures"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(_, count)| count.as_u64().unwrap_or_default() > 0)
+        .map(|(class, count)| format!("{} {}", count, class))
+        .collect();
+    if !failures.is_empty() {
+        summary.push_str(&format!("\nFailed sends: {}", failures.join(", ")));
+    }
+    let queue = &snapshot["queue"];
+    if queue["dropped"].as_u64().unwrap_or_default() > 0 {
+        summary.push_str(&format!(
+            "\nThe queue ({}, depth {}) dropped {} events",
+            queue["policy"].as_str().unwrap_or_default(),
+            queue["depth"],
+            queue["dropped"]
+        ));
+    }
+    return summary;
+}
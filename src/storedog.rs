@@ -0,0 +1,112 @@
+/*!
+HTTP access logs from storedog, the sample e-commerce store.
+
+Alongside normal and failing requests, storedog leaks customer credit card
+numbers in the error it logs when a charge fails. Its requests also carry the
+symptoms of the latency regression and the cardinality explosion.
+*/
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{self, json};
+
+use crate::{answer_key, cardinality, cascade, clock, flags, hosts, orders, random};
+
+/// Leaks from the card-leak scenario so far.
+static LEAKS: AtomicU64 = AtomicU64::new(0);
+
+fn generate_apache_log_line(method: &str, path: &str, status: usize) -> String {
+    let addr = random::ipv4_address();
+    let username = random::username();
+
+    let ts = clock::now().format("%d/%b/%Y:%H:%M:%S %z");
+
+    // TODO: handle time generation
+    return format!(
+        "{} - {} [{}] \"{} {} {}\" {} {}",
+        addr, username, ts, method, path, "HTTP/1.1", status, 1024
+    );
+}
+
+/// A page on the store.
+fn browse_path() -> String {
+    return format!("/{}", random::buzzword().replace(' ', "%20"));
+}
+
+/// A storedog access log event from one of the web servers, tagged with a
+/// per-request UUID during the cardinality explosion.
+fn request(method: &str, path: &str, status: usize) -> serde_json::Value {
+    let mut event = json!({
+        "message": generate_apache_log_line(method, path, status),
+        "service": "storedog",
+        "hostname": hosts::web().hostname,
+        "duration_ms": duration_ms(),
+        "status": if status >= 500 { "ERROR" } else { "INFO" },
+    });
+    if let Some(tags) = cardinality::request_tags() {
+        event["ddtags"] = json!(tags);
+    }
+    return event;
+}
+
+/// Response time for a storedog request. Requests are slowed down dramatically
+/// while the `storedog.checkout-v2` flag is enabled.
+fn duration_ms() -> u64 {
+    if flags::checkout_v2_enabled() {
+        return random::range(800..2500);
+    }
+    return random::range(20..120);
+}
+
+/// A successful request. Checkouts come with the business events for the
+/// order they placed, unless the dependency cascade makes them fail.
+pub fn ok() -> serde_json::Value {
+    if !orders::is_checkout() {
+        return request("GET", &browse_path(), 200);
+    }
+    // Once the dependency cascade reaches the storefront, checkouts fail.
+    if cascade::impacted("storedog") {
+        let mut request = request("POST", orders::CHECKOUT_PATH, 502);
+        request["duration_ms"] = json!(random::range(5000..5200));
+        return request;
+    }
+
+    let request = request("POST", orders::CHECKOUT_PATH, 200);
+    let hostname = request["hostname"].as_str().unwrap_or_default().to_owned();
+    let mut events = vec![request];
+    events.extend(orders::checkout_completed(&hostname));
+    return json!(events);
+}
+
+pub fn error() -> serde_json::Value {
+    return request("GET", &browse_path(), 500);
+}
+
+/// A checkout whose card charge fails, logging the card number.
+pub fn leak() -> serde_json::Value {
+    // The card error is logged by the web server that served the request.
+    let request = request("POST", orders::CHECKOUT_PATH, 504);
+    let hostname = request["hostname"].clone();
+    return json!([
+        request,
+        {
+            "message": format!("ERROR could not charge card {}!", random::credit_card_number()),
+            "service": "storedog",
+            "hostname": hostname,
+            "status": "ERROR",
+        },
+    ]);
+}
+
+/// Records a leak from the card-leak scenario in the answer key, along with
+/// how many it has leaked so far.
+pub fn record_leak(events: &serde_json::Value) {
+    let count = LEAKS.fetch_add(1, Ordering::Relaxed) + 1;
+    answer_key::record(json!({
+        "card_leak": {
+            "count": count,
+            "leaked_at": answer_key::now(),
+            "hostname": events[1]["hostname"],
+            "message": events[1]["message"],
+        },
+    }));
+}
@@ -0,0 +1,218 @@
+/*!
+On-demand anomaly triggers.
+
+Instructors can fire an anomaly exactly when the class is watching, either
+through the admin API (`dynamo trigger <scenario>` is a thin client for it) or
+with single-key triggers typed into a running dynamo. Triggered anomalies are
+recorded in the answer key like scheduled ones.
+*/
+use std::io::BufRead;
+use std::net::SocketAddr;
+
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+
+use crate::incident::Incident;
+use crate::sink::Event;
+use crate::{
+    answer_key, applog, buffer, cardinality, cascade, cluster, dns, flags, ftp, iot, mining,
+    storedog, vpn,
+};
+
+pub struct Scenario {
+    pub name: &'static str,
+    pub key: char,
+    pub description: &'static str,
+}
+
+pub const SCENARIOS: [Scenario; 11] = [
+    Scenario {
+        name: "card-leak",
+        key: 'l',
+        description: "log a failed charge that leaks a credit card number",
+    },
+    Scenario {
+        name: "latency-regression",
+        key: 'r',
+        description: "enable the storedog.checkout-v2 flag",
+    },
+    Scenario {
+        name: "crypto-mining",
+        key: 'm',
+        description: "compromise a host and start mining",
+    },
+    Scenario {
+        name: "cardinality-explosion",
+        key: 'c',
+        description: "tag every storedog request with a unique ID",
+    },
+    Scenario {
+        name: "debug-flood",
+        key: 'd',
+        description: "enable DEBUG logging on storedog-payments",
+    },
+    Scenario {
+        name: "dependency-cascade",
+        key: 'f',
+        description: "slow down storedog-payments and let the failure cascade",
+    },
+    Scenario {
+        name: "buffer-stress",
+        key: 'b',
+        description: "send a burst large enough to overflow Vector's memory buffers",
+    },
+    Scenario {
+        name: "dga-lookups",
+        key: 'g',
+        description: "have the compromised host look up algorithmically generated domains",
+    },
+    Scenario {
+        name: "bulk-download",
+        key: 'x',
+        description: "have one user download the customer export over FTP",
+    },
+    Scenario {
+        name: "impossible-travel",
+        key: 't',
+        description: "have one employee sign in and connect to the VPN from two distant countries",
+    },
+    Scenario {
+        name: "stuck-sensor",
+        key: 's',
+        description: "freeze the walk-in cooler's temperature sensor on one reading",
+    },
+];
+
+/// Scenarios that happen once when fired instead of lasting until stopped.
+/// They only happen on the instance they are fired on, so a cluster leaks one
+/// card or sends one burst rather than one per worker.
+pub const ONE_OFFS: [&str; 2] = ["card-leak", "buffer-stress"];
+
+fn incident(name: &str) -> Option<&'static Incident> {
+    return match name {
+        "crypto-mining" => Some(&mining::INCIDENT),
+        "cardinality-explosion" => Some(&cardinality::INCIDENT),
+        "debug-flood" => Some(&applog::FLOOD),
+        "dependency-cascade" => Some(&cascade::INCIDENT),
+        "dga-lookups" => Some(&dns::INCIDENT),
+        "bulk-download" => Some(&ftp::INCIDENT),
+        "impossible-travel" => Some(&vpn::INCIDENT),
+        "stuck-sensor" => Some(&iot::INCIDENT),
+        _ => None,
+    };
+}
+
+/// The scenario that starts and stops `incident`, if it can be triggered.
+pub fn scenario_of(incident: &Incident) -> Option<&'static str> {
+    return SCENARIOS
+        .iter()
+        .map(|s| s.name)
+        .find(|name| self::incident(name).is_some_and(|i| std::ptr::eq(i, incident)));
+}
+
+/// Starts, or with `stop` ends, the named scenario in this process, and on
+/// any workers it coordinates.
+pub async fn fire(tx: &Sender<Event>, name: &str, stop: bool) -> Result<String, String> {
+    let scenario = SCENARIOS
+        .iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| format!("unknown scenario `{}`", name))?;
+
+    match (scenario.name, stop) {
+        ("card-leak", true) => return Err("card-leak is a one-off and can't be stopped".to_owned()),
+        ("card-leak", false) => {
+            let events = storedog::leak();
+            for event in events.as_array().into_iter().flatten() {
+                crate::emit(tx, "storedog-leak", event.clone()).await;
+            }
+            storedog::record_leak(&events);
+        }
+        ("buffer-stress", false) => buffer::start(tx)?,
+        ("buffer-stress", true) => buffer::stop()?,
+        ("latency-regression", _) => {
+            flags::set_checkout_v2(tx, !stop).await;
+        }
+        (name, stop) => {
+            let incident = incident(name).expect("every other scenario should be an incident");
+            incident.set(!stop);
+        }
+    }
+
+    if !ONE_OFFS.contains(&scenario.name) {
+        cluster::forward(scenario.name, stop);
+    }
+
+    let verb = if stop { "stopped" } else { "triggered" };
+    answer_key::record(json!({
+        "triggers": { (scenario.name): { (format!("{}_at", verb)): answer_key::now() } },
+    }));
+    return Ok(format!("{} {}", scenario.name, verb));
+}
+
+/// Asks the instance serving the admin API at `address` to fire a scenario,
+/// and prints the outcome.
+pub async fn remote(address: SocketAddr, name: &str, stop: bool) -> Result<(), String> {
+    println!("{}", request(address, name, stop).await?);
+    return Ok(());
+}
+
+/// Asks the instance serving the admin API at `address` to fire a scenario,
+/// returning the outcome.
+pub async fn request(address: SocketAddr, name: &str, stop: bool) -> Result<String, String> {
+    let action = if stop { "stop" } else { "start" };
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/trigger/{}/{}", address, name, action))
+        .send()
+        .await
+        .map_err(|e| format!("could not reach dynamo at {}: {}", address, e))?;
+
+    let status = response.status();
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("unexpected response from dynamo: {}", e))?;
+    if !status.is_success() {
+        return Err(body["error"]
+            .as_str()
+            .unwrap_or("trigger failed")
+            .to_owned());
+    }
+    return Ok(body["result"].as_str().unwrap_or_default().to_owned());
+}
+
+/// Reads single-key triggers from stdin, one per line. An uppercase key stops
+/// the scenario instead.
+pub fn listen_for_keys(tx: &Sender<Event>) {
+    println!("Keyboard triggers (press the key, then Enter; uppercase stops):");
+    for scenario in SCENARIOS.iter() {
+        println!(
+            "  {}  {}: {}",
+            scenario.key, scenario.name, scenario.description
+        );
+    }
+
+    let tx2 = tx.clone();
+    let (keys_tx, mut keys) = tokio::sync::mpsc::channel(8);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines().map_while(Result::ok) {
+            if let Some(key) = line.trim().chars().next() {
+                if keys_tx.blocking_send(key).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Some(key) = keys.recv().await {
+            let scenario = SCENARIOS.iter().find(|s| s.key == key.to_ascii_lowercase());
+            let result = match scenario {
+                Some(scenario) => fire(&tx2, scenario.name, key.is_uppercase()).await,
+                None => Err(format!("no scenario for key `{}`", key)),
+            };
+            match result {
+                Ok(message) | Err(message) => println!("{}", message),
+            }
+        }
+    });
+}
@@ -0,0 +1,467 @@
+/*!
+Reference grammars for generated output.
+
+Every message format dynamo emits has a checker here, so that property tests
+can assert that any seed produces lines a student's parser could handle, and
+so that `dynamo validate-output` can run the same checks against a live stream
+(for example the JSON output of a Vector `console` sink).
+*/
+use std::io::BufRead;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::billing;
+
+/// Apache common log format, with the request path restricted to characters
+/// that are valid unencoded in a URL.
+const CLF: &str = r#"^(\d{1,3}(?:\.\d{1,3}){3}) - (\S+) \[(\d{2}/[A-Z][a-z]{2}/\d{4}:\d{2}:\d{2}:\d{2} [+-]\d{4})\] "(GET|POST) (/[!-~]*) HTTP/1\.1" (\d{3}) (\d+)$"#;
+const CARD_ERROR: &str = r"^ERROR could not charge card \d{15,16}!$";
+const APPLOG: &str = r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}\.\d{3}Z (DEBUG|INFO|WARN|ERROR) \[(storedog-[a-z]+)\] .+$";
+const ORDER_EVENT: &str = r"^(order_placed|payment_captured|refund_issued) order_id=[0-9a-f-]{36} customer=\S+ (items=\d+ )?amount=\d+\.\d{2} currency=USD$";
+/// Point-of-sale records, with any run of non-alphanumeric characters
+/// allowed between fields so that every delimiter and fixed-width padding
+/// parse. W3C directive lines are accepted too.
+const POS: &str = r"^(#Fields: .+|\d{4}-\d{2}-\d{2}[^A-Za-z0-9]+\d{2}:\d{2}:\d{2}[^A-Za-z0-9]+STORE-\d{4}[^A-Za-z0-9]+T\d{2}[^A-Za-z0-9]+\d{9}[^A-Za-z0-9]+[A-Za-z0-9]+[^A-Za-z0-9]+(CASH|CARD|GIFT)[^A-Za-z0-9]+\d+\.\d{2}[^A-Za-z0-9]+(APPROVED|DECLINED|VOID) *)$";
+/// An XML start, end or empty-element tag, with double-quoted attributes.
+const XML_TAG: &str = r#"<(/?)([A-Za-z][\w:.-]*)(?:\s+[\w:.-]+="[^"<]*")*\s*(/?)>"#;
+/// Character data between tags: no markup, and only the predefined entities.
+const XML_TEXT: &str = r"^(?:[^<>&]|&(?:amp|lt|gt|quot|apos);)*$";
+/// Windows DNS Server debug log packet lines.
+const WINDOWS_DNS: &str = r"^\d{1,2}/\d{1,2}/\d{4} \d{1,2}:\d{2}:\d{2} (AM|PM) [0-9A-F]{4} PACKET  [0-9A-F]{16} (UDP|TCP) (Snd|Rcv) [0-9.]{7,15} +[0-9a-f]{4} [R ] Q \[[0-9a-f]{4} [A ][T ][D ][R ] +(NOERROR|NXDOMAIN|SERVFAIL)\] (A|AAAA) +(\(\d+\)[a-z0-9-]+)+\(0\)$";
+/// vsftpd xferlog transfer records.
+const XFERLOG: &str = r"^[A-Z][a-z]{2} [A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2} \d{4} \d+ \S+ \d+ /\S+ [ab] _ [oid] [agr] \S+ ftp [01] \S+ [ci]$";
+/// OpenVPN server log lines.
+const OPENVPN: &str = r"^[A-Z][a-z]{2} [A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2} \d{4} (\S+:\d+ \[\S+\] Peer Connection Initiated with \[AF_INET\]\S+:\d+|\S+/\S+:\d+ MULTI_sva: pool returned IPv4=[\d.]+, IPv6=\(Not enabled\)|\S+/\S+:\d+ SIGTERM\[soft,remote-exit\] received, client-instance exiting|\S+:\d+ TLS Auth Error: Auth Username/Password verification failed for peer)$";
+/// Fields every Zeek `ssl.log` record has.
+const ZEEK_SSL_FIELDS: [&str; 8] = [
+    "ts",
+    "uid",
+    "id.orig_h",
+    "id.orig_p",
+    "id.resp_h",
+    "id.resp_p",
+    "version",
+    "established",
+];
+/// macOS unified log entries, as `log show --style default` prints them.
+const MACOS_UNIFIED: &str = r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}\.\d{6}[+-]\d{4} 0x[0-9a-f]+ +(Default|Info|Debug|Error|Fault) +0x[0-9a-f]+ +\d+ +\d+ +[\w.-]+: \([\w.-]+\) (\[[\w.-]+:[\w.-]+\] )?\S.*$";
+/// Fields every Falco alert has, and the output fields every one of dynamo's
+/// rules fills in.
+const FALCO_FIELDS: [&str; 6] = ["time", "rule", "priority", "output", "source", "hostname"];
+const FALCO_OUTPUT_FIELDS: [&str; 4] = ["evt.time", "evt.type", "proc.name", "proc.exepath"];
+/// Fields every Okta System Log event has, as JSON pointers.
+const OKTA_FIELDS: [&str; 6] = [
+    "/uuid",
+    "/published",
+    "/eventType",
+    "/outcome/result",
+    "/actor/alternateId",
+    "/client/ipAddress",
+];
+const FLAG_CHANGE: &str = r"^Flag \S+ (enabled|disabled) by \S+ for 100% of traffic$";
+
+/// Number of space-separated fields in a version 2 VPC flow log record.
+const FLOW_LOG_FIELDS: usize = 14;
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    return cell.get_or_init(|| Regex::new(pattern).expect("reference grammar should compile"));
+}
+
+fn clf() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, CLF);
+}
+
+fn card_error() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, CARD_ERROR);
+}
+
+fn applog() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, APPLOG);
+}
+
+fn order_event() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, ORDER_EVENT);
+}
+
+fn pos() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, POS);
+}
+
+fn xml_tag() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, XML_TAG);
+}
+
+fn xml_text() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, XML_TEXT);
+}
+
+fn windows_dns() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, WINDOWS_DNS);
+}
+
+fn xferlog() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, XFERLOG);
+}
+
+fn macos_unified() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, MACOS_UNIFIED);
+}
+
+fn openvpn() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, OPENVPN);
+}
+
+fn flag_change() -> &'static Regex {
+    static CELL: OnceLock<Regex> = OnceLock::new();
+    return regex(&CELL, FLAG_CHANGE);
+}
+
+pub fn apache_line(line: &str) -> Result<(), String> {
+    let captures = clf()
+        .captures(line)
+        .ok_or_else(|| format!("not a common log format line: {}", line))?;
+    for octet in captures[1].split('.') {
+        octet
+            .parse::<u8>()
+            .map_err(|_| format!("invalid client address {}", &captures[1]))?;
+    }
+    chrono::DateTime::parse_from_str(&captures[3], "%d/%b/%Y:%H:%M:%S %z")
+        .map_err(|e| format!("invalid timestamp {}: {}", &captures[3], e))?;
+    return Ok(());
+}
+
+pub fn vpc_flow_line(line: &str) -> Result<(), String> {
+    let fields: Vec<&str> = line.split(' ').collect();
+    if fields.len() != FLOW_LOG_FIELDS {
+        return Err(format!(
+            "expected {} fields, got {}: {}",
+            FLOW_LOG_FIELDS,
+            fields.len(),
+            line
+        ));
+    }
+    if fields[0] != "2" {
+        return Err(format!("unsupported flow log version {}", fields[0]));
+    }
+    for (i, name) in [(3, "srcaddr"), (4, "dstaddr")] {
+        fields[i]
+            .parse::<std::net::Ipv4Addr>()
+            .map_err(|_| format!("invalid {} {}", name, fields[i]))?;
+    }
+    for (i, name) in [(5, "srcport"), (6, "dstport")] {
+        fields[i]
+            .parse::<u16>()
+            .map_err(|_| format!("invalid {} {}", name, fields[i]))?;
+    }
+    let mut numbers = vec![];
+    for (i, name) in [
+        (7, "protocol"),
+        (8, "packets"),
+        (9, "bytes"),
+        (10, "start"),
+        (11, "end"),
+    ] {
+        let n = fields[i]
+            .parse::<u64>()
+            .map_err(|_| format!("invalid {} {}", name, fields[i]))?;
+        numbers.push(n);
+    }
+    if numbers[3] > numbers[4] {
+        return Err(format!("flow starts after it ends: {}", line));
+    }
+    if !["ACCEPT", "REJECT"].contains(&fields[12]) {
+        return Err(format!("invalid action {}", fields[12]));
+    }
+    if !["OK", "NODATA", "SKIPDATA"].contains(&fields[13]) {
+        return Err(format!("invalid log status {}", fields[13]));
+    }
+    return Ok(());
+}
+
+/// Splits a CSV line into fields, handling double-quoted fields.
+fn csv_fields(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', false) if field.is_empty() => quoted = true,
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => {
+                quoted = false;
+                if !matches!(chars.peek(), None | Some(',')) {
+                    return Err(format!("text after closing quote: {}", line));
+                }
+            }
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("unterminated quote: {}", line));
+    }
+    fields.push(field);
+    return Ok(fields);
+}
+
+/// Checks a CSV header or row against the expected columns.
+pub fn csv_line(line: &str, columns: &[&str]) -> Result<(), String> {
+    let fields = csv_fields(line)?;
+    if fields.len() != columns.len() {
+        return Err(format!(
+            "expected {} fields, got {}: {}",
+            columns.len(),
+            fields.len(),
+            line
+        ));
+    }
+    if fields.iter().zip(columns).all(|(f, c)| f == c) {
+        return Ok(());
+    }
+    chrono::DateTime::parse_from_rfc3339(&fields[0])
+        .map_err(|e| format!("invalid usage start date {}: {}", fields[0], e))?;
+    for i in [6, 7] {
+        fields[i]
+            .parse::<f64>()
+            .map_err(|_| format!("invalid {} {}", columns[i], fields[i]))?;
+    }
+    return Ok(());
+}
+
+/// Checks that `message` is a single well-formed XML element named `root`.
+/// This covers the subset of XML dynamo emits: no prolog, comments or CDATA.
+pub fn xml_document(message: &str, root: &str) -> Result<(), String> {
+    let mut open: Vec<&str> = vec![];
+    let mut end = 0;
+    for tag in xml_tag().captures_iter(message) {
+        let whole = tag.get(0).expect("a match should have a whole group");
+        let text = &message[end..whole.start()];
+        if !xml_text().is_match(text) || (open.is_empty() && !text.trim().is_empty()) {
+            return Err(format!("invalid character data `{}`: {}", text, message));
+        }
+        end = whole.end();
+
+        let name = tag.get(2).expect("a tag should have a name").as_str();
+        if open.is_empty() && whole.start() > 0 {
+            return Err(format!("more than one root element: {}", message));
+        }
+        if open.is_empty() && name != root {
+            return Err(format!("expected a <{}> root element: {}", root, message));
+        }
+        if &tag[1] == "/" {
+            if open.pop() != Some(name) {
+                return Err(format!("unexpected </{}>: {}", name, message));
+            }
+        } else if &tag[3] != "/" {
+            open.push(name);
+        }
+    }
+
+    if end == 0 || !open.is_empty() || end != message.len() {
+        return Err(format!("not a complete XML element: {}", message));
+    }
+    return Ok(());
+}
+
+fn matches(re: &Regex, message: &str) -> Result<(), String> {
+    if !re.is_match(message) {
+        return Err(format!("does not match {}: {}", re.as_str(), message));
+    }
+    return Ok(());
+}
+
+/// Checks that `message` is a Falco alert serialized as JSON, with the fields
+/// its output was built from.
+pub fn falco_alert(message: &str) -> Result<(), String> {
+    let alert: serde_json::Value =
+        serde_json::from_str(message).map_err(|e| format!("Falco alert is not JSON: {}", e))?;
+    if let Some(missing) = FALCO_FIELDS.iter().find(|f| alert[**f].is_null()) {
+        return Err(format!("Falco alert is missing {}", missing));
+    }
+    if let Some(missing) = FALCO_OUTPUT_FIELDS
+        .iter()
+        .find(|f| alert["output_fields"][**f].is_null())
+    {
+        return Err(format!("Falco alert is missing output field {}", missing));
+    }
+    return Ok(());
+}
+
+/// Checks that `message` is an Okta System Log event serialized as JSON, with
+/// the user and address a sign-in is correlated by.
+pub fn okta_event(message: &str) -> Result<(), String> {
+    let event: serde_json::Value =
+        serde_json::from_str(message).map_err(|e| format!("Okta event is not JSON: {}", e))?;
+    if let Some(missing) = OKTA_FIELDS.iter().find(|f| event.pointer(f).is_none()) {
+        return Err(format!("Okta event is missing {}", missing));
+    }
+    return Ok(());
+}
+
+/// Checks that `message` is a Zeek log record serialized as JSON, with every
+/// one of `fields`.
+pub fn zeek_record(message: &str, fields: &[&str]) -> Result<(), String> {
+    let record: serde_json::Value =
+        serde_json::from_str(message).map_err(|e| format!("Zeek record is not JSON: {}", e))?;
+    if !record["ts"].is_f64() && !record["ts"].is_u64() {
+        return Err("Zeek record has no numeric ts".to_owned());
+    }
+    if let Some(missing) = fields.iter().find(|f| record[**f].is_null()) {
+        return Err(format!("Zeek record is missing {}", missing));
+    }
+    return Ok(());
+}
+
+/// Checks a single event's message against the grammar for its service.
+pub fn event(event: &serde_json::Value) -> Result<(), String> {
+    let service = event["service"].as_str().unwrap_or_default();
+    let message = event["message"]
+        .as_str()
+        .ok_or_else(|| format!("event has no message: {}", event))?;
+
+    return match service {
+        "storedog" if message.starts_with("ERROR") => matches(card_error(), message),
+        "storedog" => apache_line(message),
+        "storedog-orders" => matches(order_event(), message),
+        s if s.starts_with("storedog-") => matches(applog(), message),
+        "aws.vpc_flow_logs" => vpc_flow_line(message),
+        "feature-flags" => matches(flag_change(), message),
+        "pos-terminal" => matches(pos(), message),
+        "payment-gateway" => xml_document(message, "GatewayAccess"),
+        "windows-dns" => matches(windows_dns(), message),
+        "vsftpd" => matches(xferlog(), message),
+        "openvpn" => matches(openvpn(), message),
+        "macos" => matches(macos_unified(), message),
+        "falco" => falco_alert(message),
+        "zeek" => zeek_record(message, &ZEEK_SSL_FIELDS),
+        "okta" => okta_event(message),
+        "aws.billing_export" => csv_line(message, &billing::CSV_COLUMNS),
+        "aws.billing" => serde_json::from_str::<serde_json::Value>(message)
+            .map(|_| ())
+            .map_err(|e| format!("billing message is not JSON: {}", e)),
+        // Services without a reference grammar are accepted as-is.
+        _ => Ok(()),
+    };
+}
+
+/// Checks everything a generator returned, which may be one event or many.
+pub fn generated(value: &serde_json::Value) -> Result<(), String> {
+    return match value.as_array() {
+        Some(events) => events.iter().try_for_each(event),
+        None => event(value),
+    };
+}
+
+/// Validates newline-delimited JSON events from `input`, printing each
+/// failure. Returns an error if any event was invalid.
+pub fn run(input: impl BufRead) -> Result<(), String> {
+    let mut checked = 0;
+    let mut failed = 0;
+    for (n, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| format!("could not read input: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        checked += 1;
+        let result = serde_json::from_str(&line)
+            .map_err(|e| format!("not JSON: {}", e))
+            .and_then(|v| generated(&v));
+        if let Err(e) = result {
+            failed += 1;
+            println!("line {}: {}", n + 1, e);
+        }
+    }
+
+    println!("{} of {} events valid", checked - failed, checked);
+    if failed > 0 {
+        return Err(format!("{} invalid events", failed));
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::prelude::*;
+    use proptest::prelude::*;
+
+    use crate::{
+        applog, billing, clock, dns, falco, flags, ftp, macos, noise, pos, random, soap, sso,
+        storedog, vpc, vpn, Generator,
+    };
+
+    fn feature_flag() -> serde_json::Value {
+        return flags::flag_change_event(flags::CHECKOUT_V2, random::chance(0.5));
+    }
+
+    const GENERATORS: [(&str, Generator); 18] = [
+        ("storedog::ok", storedog::ok),
+        ("storedog::error", storedog::error),
+        ("storedog::leak", storedog::leak),
+        ("applog::generate", applog::generate),
+        ("vpc::accept", vpc::accept),
+        ("vpc::ssh_attack", vpc::ssh_attack),
+        ("vpc::mining", vpc::mining),
+        ("feature_flag", feature_flag),
+        ("billing::generate", billing::generate),
+        ("pos::generate", pos::generate),
+        ("soap::generate", soap::generate),
+        ("dns::generate", dns::generate),
+        ("ftp::generate", ftp::generate),
+        ("vpn::generate", vpn::generate),
+        ("sso::generate", sso::generate),
+        ("noise::generate", noise::generate),
+        ("macos::generate", macos::generate),
+        ("falco::generate", falco::generate),
+    ];
+
+    proptest! {
+        #[test]
+        fn generated_lines_parse(seed in any::<u64>(), at in 1_000_000_000i64..4_102_444_800) {
+            random::reseed(seed);
+            clock::freeze(Utc.timestamp_opt(at, 0).single());
+            for (name, generator) in GENERATORS.iter() {
+                let value = generator();
+                let result = super::generated(&value);
+                prop_assert!(result.is_ok(), "{} produced an invalid line: {:?}", name, result);
+            }
+            clock::freeze(None);
+        }
+    }
+
+    #[test]
+    fn grammars_reject_malformed_lines() {
+        assert!(super::apache_line(
+            "1.2.3.4 - bob [01/Apr/2023:12:00:00 +0000] \"GET /a b HTTP/1.1\" 200 1024"
+        )
+        .is_err());
+        assert!(super::vpc_flow_line(
+            "2 1234567890 eni-1 1.2.3.4 5.6.7.8 70000 22 6 1 1 1 2 REJECT OK"
+        )
+        .is_err());
+        assert!(super::vpc_flow_line(
+            "2 1234567890 eni-1 1.2.3.4 5.6.7.8 40000 22 6 1 1 1 2 REJECT"
+        )
+        .is_err());
+        assert!(super::xml_document("<a><b>x</a></b>", "a").is_err());
+        assert!(super::xml_document("<a>x & y</a>", "a").is_err());
+        assert!(super::xml_document("<a/><a/>", "a").is_err());
+        assert!(super::xml_document("<a x=\"1\"><b/>&amp;</a>", "a").is_ok());
+    }
+}
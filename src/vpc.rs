@@ -0,0 +1,107 @@
+/*!
+AWS VPC flow logs in the default version 2 format.
+
+Besides normal HTTPS traffic, the flow logs show the SSH brute-force attack as
+a stream of rejected connections to port 22, and the crypto mining incident as
+accepted connections from the compromised host to the mining pool.
+
+Every flow is also exported to the NetFlow collector, if there is one.
+*/
+use serde_json::{self, json};
+
+use crate::netflow::{self, Flow};
+use crate::{attackers, clock, hosts, mining, random};
+
+/// A flow from a random outside client to `server_ip`.
+fn generate_vpc_flow_line(action: &str, status: &str, port: usize, server_ip: &str) -> String {
+    let client_ip = random::ipv4_address();
+    return generate_vpc_flow_line_between(&client_ip, server_ip, action, status, port);
+}
+
+fn generate_vpc_flow_line_between(
+    client_ip: &str,
+    server_ip: &str,
+    action: &str,
+    status: &str,
+    port: usize,
+) -> String {
+    let start = clock::now()
+        .checked_sub_signed(chrono::Duration::seconds(random::range(5..30)))
+        .expect("could not create start time for log");
+    let end = clock::now();
+
+    let client_port: i32 = random::range(30000..65536);
+    let request_bytes: i32 = random::range(230..9000);
+    let request_packets: i32 = random::range(5..1000);
+
+    if netflow::enabled() {
+        netflow::observe(Flow {
+            src: client_ip.parse().expect("flow addresses should be IPv4"),
+            dst: server_ip.parse().expect("flow addresses should be IPv4"),
+            src_port: client_port as u16,
+            dst_port: port as u16,
+            protocol: 6,
+            packets: request_packets as u32,
+            bytes: request_bytes as u32,
+            start_s: start.timestamp(),
+            end_s: end.timestamp(),
+            accepted: action == "ACCEPT",
+        });
+    }
+
+    return format!(
+        "{} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+        2,
+        "1234567890",
+        "eni-sdvu4NphZxGvp1MDz",
+        client_ip,
+        server_ip,
+        client_port,
+        port,
+        6,
+        request_packets,
+        request_bytes,
+        start.timestamp(),
+        end.timestamp(),
+        action,
+        status,
+    );
+}
+
+pub fn accept() -> serde_json::Value {
+    return json!([{
+        "message": generate_vpc_flow_line("ACCEPT", "OK", 443, hosts::web().ip),
+        "service": "aws.vpc_flow_logs",
+    }]);
+}
+
+pub fn ssh_attack() -> serde_json::Value {
+    return json!({
+        "message": generate_vpc_flow_line_between(
+            &attackers::pick().ip,
+            hosts::web().ip,
+            "REJECT",
+            "OK",
+            22,
+        ),
+        "service": "aws.vpc_flow_logs",
+        "status": "WARNING",
+    });
+}
+
+pub fn mining() -> serde_json::Value {
+    if !mining::active() {
+        return json!([]);
+    }
+
+    return json!({
+        "message": generate_vpc_flow_line_between(
+            mining::COMPROMISED_HOST_IP,
+            mining::POOL_IP,
+            "ACCEPT",
+            "OK",
+            mining::POOL_PORT,
+        ),
+        "service": "aws.vpc_flow_logs",
+    });
+}
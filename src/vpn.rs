@@ -0,0 +1,183 @@
+/*!
+OpenVPN connection logs and Zeek TLS handshake records for remote access.
+
+Employees connect to the VPN from home. Every connection shows up twice: as a
+Zeek `ssl.log` record of the TLS handshake, serialized as JSON in the message
+like the billing line items, and as OpenVPN's own log lines for the peer
+connection and the address it was given. Disconnects and failed logins are
+logged by OpenVPN alone.
+
+During the impossible travel incident, one employee's account keeps connecting
+both from home and from a country thousands of kilometers away, minutes apart.
+The same employees sign in through SSO from the same addresses, in `sso`.
+*/
+use std::cell::Cell;
+
+use serde_json::{self, json};
+
+use crate::incident::Incident;
+use crate::{clock, hosts, random};
+
+pub const OPENVPN_SERVICE: &str = "openvpn";
+pub const ZEEK_SERVICE: &str = "zeek";
+const SERVER_NAME: &str = "vpn.storedog.example";
+const PORT: u16 = 443;
+
+pub struct Employee {
+    pub username: &'static str,
+    pub country: &'static str,
+    /// The address the employee connects from at home.
+    pub ip: &'static str,
+}
+
+pub const EMPLOYEES: [Employee; 4] = [
+    Employee {
+        username: "akim",
+        country: "US",
+        ip: "73.162.14.201",
+    },
+    Employee {
+        username: "bnovak",
+        country: "US",
+        ip: "24.8.112.56",
+    },
+    Employee {
+        username: "cschulz",
+        country: "DE",
+        ip: "91.12.203.77",
+    },
+    Employee {
+        username: "dokafor",
+        country: "GB",
+        ip: "86.14.90.3",
+    },
+];
+
+/// The employee whose account is used from two places at once, and where from.
+pub const TRAVELER: &Employee = &EMPLOYEES[0];
+pub const REMOTE_COUNTRY: &str = "RU";
+pub const REMOTE_IP: &str = "95.165.133.12";
+/// Share of VPN activity from the traveler during the incident.
+const TRAVELER_SHARE: f64 = 0.5;
+
+thread_local! {
+    /// Whether the traveler's next connection comes from the remote country.
+    /// The traveler alternates between the two, so every pair of their
+    /// connections is an impossible trip.
+    static NEXT_REMOTE: Cell<bool> = const { Cell::new(false) };
+}
+
+pub static INCIDENT: Incident = Incident::new("impossible_travel", details);
+
+fn details() -> serde_json::Value {
+    return json!({
+        "username": TRAVELER.username,
+        "home": { "country": TRAVELER.country, "ip": TRAVELER.ip },
+        "remote": { "country": REMOTE_COUNTRY, "ip": REMOTE_IP },
+    });
+}
+
+/// A Zeek connection UID.
+fn zeek_uid() -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+    let id: String = (0..17)
+        .map(|_| ALPHABET[random::range(0..ALPHABET.len())] as char)
+        .collect();
+    return format!("C{}", id);
+}
+
+fn zeek_ssl(client_ip: &str, client_port: u16) -> serde_json::Value {
+    let resumed = random::chance(0.2);
+    let record = json!({
+        "ts": clock::now().timestamp_millis() as f64 / 1000.0,
+        "uid": zeek_uid(),
+        "id.orig_h": client_ip,
+        "id.orig_p": client_port,
+        "id.resp_h": hosts::VPN.ip,
+        "id.resp_p": PORT,
+        "version": "TLSv13",
+        "cipher": "TLS_AES_256_GCM_SHA384",
+        "curve": "x25519",
+        "server_name": SERVER_NAME,
+        "resumed": resumed,
+        "established": true,
+        "ssl_history": if resumed { "CsiI" } else { "CsxiI" },
+    });
+    return json!({
+        "message": record.to_string(),
+        "service": ZEEK_SERVICE,
+        "hostname": hosts::SENSOR.hostname,
+    });
+}
+
+fn openvpn(body: String, status: &str) -> serde_json::Value {
+    return json!({
+        "message": format!("{} {}", clock::now().format("%a %b %e %H:%M:%S %Y"), body),
+        "service": OPENVPN_SERVICE,
+        "hostname": hosts::VPN.hostname,
+        "status": status,
+    });
+}
+
+fn connect(username: &str, client_ip: &str) -> serde_json::Value {
+    let port = random::range(1024..65535);
+    let peer = format!("{}:{}", client_ip, port);
+    let pool_ip = format!("10.8.0.{}", random::range(2..254));
+    return json!([
+        zeek_ssl(client_ip, port),
+        openvpn(
+            format!(
+                "{} [{}] Peer Connection Initiated with [AF_INET]{}",
+                peer, username, peer
+            ),
+            "INFO",
+        ),
+        openvpn(
+            format!(
+                "{}/{} MULTI_sva: pool returned IPv4={}, IPv6=(Not enabled)",
+                username, peer, pool_ip
+            ),
+            "INFO",
+        ),
+    ]);
+}
+
+fn disconnect(username: &str, client_ip: &str) -> serde_json::Value {
+    let peer = format!("{}:{}", client_ip, random::range(1024..65535));
+    return openvpn(
+        format!(
+            "{}/{} SIGTERM[soft,remote-exit] received, client-instance exiting",
+            username, peer
+        ),
+        "INFO",
+    );
+}
+
+fn auth_failure(client_ip: &str) -> serde_json::Value {
+    let peer = format!("{}:{}", client_ip, random::range(1024..65535));
+    return openvpn(
+        format!(
+            "{} TLS Auth Error: Auth Username/Password verification failed for peer",
+            peer
+        ),
+        "WARNING",
+    );
+}
+
+pub fn generate() -> serde_json::Value {
+    if INCIDENT.active() && random::chance(TRAVELER_SHARE) {
+        let remote = NEXT_REMOTE.with(|next| next.replace(!next.get()));
+        let client_ip = if remote { REMOTE_IP } else { TRAVELER.ip };
+        return connect(TRAVELER.username, client_ip);
+    }
+
+    let employee = random::pick(&EMPLOYEES);
+    let roll: f64 = random::range(0.0..1.0);
+    if roll < 0.6 {
+        return connect(employee.username, employee.ip);
+    }
+    if roll < 0.9 {
+        return disconnect(employee.username, employee.ip);
+    }
+    return auth_failure(employee.ip);
+}
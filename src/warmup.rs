@@ -0,0 +1,70 @@
+/*!
+Warmup before steady state.
+
+With a warmup configured, every generator starts at a small fraction of its
+configured rate and ramps up linearly over the warmup period. When the ramp
+ends, a marker event is emitted and the answer key records when steady state
+was reached, so benchmarks and graded windows can start from a known point.
+*/
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
+
+use crate::sink::Event;
+use crate::{answer_key, random, webhook};
+
+/// Fraction of the configured rates generators start at.
+const INITIAL_FRACTION: f64 = 0.05;
+
+static WARMUP: OnceLock<(Instant, Duration)> = OnceLock::new();
+
+/// Fraction of the configured rates generators should currently run at.
+fn fraction() -> f64 {
+    let (started, duration) = match WARMUP.get() {
+        Some(warmup) => warmup,
+        None => return 1.0,
+    };
+    let progress = started.elapsed().as_secs_f64() / duration.as_secs_f64();
+    return progress.clamp(INITIAL_FRACTION, 1.0);
+}
+
+/// Whether a generator should use a permit from its pacer, thinning its
+/// output while warming up.
+pub fn admit() -> bool {
+    let fraction = fraction();
+    return fraction >= 1.0 || random::chance(fraction);
+}
+
+/// Starts a warmup of `duration_s` seconds. A zero duration starts generators
+/// at full rate.
+pub fn start(tx: &Sender<Event>, duration_s: u64) {
+    if duration_s == 0 {
+        return;
+    }
+
+    let duration = Duration::from_secs(duration_s);
+    WARMUP
+        .set((Instant::now(), duration))
+        .expect("warmup should only be started once");
+    let started = json!({ "duration_s": duration_s, "started_at": answer_key::now() });
+    answer_key::record(json!({ "warmup": started.clone() }));
+    webhook::phase("warmup", true, started);
+
+    let tx2 = tx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(duration).await;
+        let marker = json!({
+            "message": format!("Steady state reached after {}s warmup", duration_s),
+            "service": "dynamo",
+            "marker": "steady_state",
+        });
+        crate::emit(&tx2, "markers", marker).await;
+
+        let reached = json!({ "duration_s": duration_s, "steady_state_at": answer_key::now() });
+        answer_key::record(json!({ "warmup": reached.clone() }));
+        webhook::phase("warmup", false, reached);
+    });
+}
@@ -0,0 +1,45 @@
+/*!
+Progress webhook for LMS integrations and grading bots.
+
+When configured, dynamo posts to the webhook every time a scenario phase
+begins or ends, with the same ground truth that goes into the answer key, so
+lab progress can be tracked without polling.
+*/
+use std::sync::OnceLock;
+
+use serde_json::{self, json};
+
+use crate::answer_key;
+
+static URL: OnceLock<String> = OnceLock::new();
+
+pub fn set_url(url: String) {
+    URL.set(url).expect("webhook URL should only be set once");
+}
+
+/// Notifies the webhook, if one is configured, that `phase` began or ended.
+/// Delivery happens in the background and failures are only logged.
+pub fn phase(phase: &'static str, began: bool, ground_truth: serde_json::Value) {
+    let url = match URL.get() {
+        Some(url) => url,
+        None => return,
+    };
+
+    let body = json!({
+        "event": if began { "phase_began" } else { "phase_ended" },
+        "phase": phase,
+        "at": answer_key::now(),
+        "ground_truth": ground_truth,
+    });
+    tokio::spawn(async move {
+        let result = reqwest::Client::new()
+            .post(url)
+            .json(&body)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        if let Err(e) = result {
+            println!("Could not notify progress webhook: {}", e);
+        }
+    });
+}